@@ -98,6 +98,84 @@ impl Page {
   }
 }
 
+/// 游标（键集）分页，通过上一页返回的游标定位，查询代价不随页数增加
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+pub struct CursorPagination {
+  #[prost(int64, tag = "1")]
+  pub page_size: i64,
+
+  #[serde(default = "default_sort_bys")]
+  #[prost(message, repeated, tag = "2")]
+  pub sort_bys: ::prost::alloc::vec::Vec<SortBy>,
+
+  /// 上一次返回的 `next_cursor` 或 `prev_cursor`，为空时查询第一页
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[prost(string, optional, tag = "3")]
+  pub cursor: ::core::option::Option<::prost::alloc::string::String>,
+
+  /// 是否返回总记录数
+  #[serde(default)]
+  #[prost(enumeration = "CursorTotal", tag = "4")]
+  pub total: i32,
+}
+
+impl CursorPagination {
+  pub fn page_size(&self) -> i64 {
+    if self.page_size > 0 {
+      self.page_size
+    } else {
+      default_page_size()
+    }
+  }
+
+  pub fn sort_bys(&self) -> Vec<&SortBy> {
+    self.sort_bys.iter().collect()
+  }
+}
+
+#[derive(
+  Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration, Serialize_repr, Deserialize_repr,
+)]
+#[repr(i32)]
+#[allow(non_camel_case_types)]
+pub enum CursorTotal {
+  /// 不统计总记录数
+  NONE = 0,
+  /// 使用 `count(*)` 统计
+  EXACT = 1,
+  /// 使用数据库的统计信息估算，忽略过滤条件
+  APPROXIMATE = 2,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
+pub struct CursorPage {
+  #[prost(int64, tag = "1")]
+  pub page_size: i64,
+
+  /// 下一页的游标，没有下一页时为空
+  #[prost(string, optional, tag = "2")]
+  pub next_cursor: ::core::option::Option<::prost::alloc::string::String>,
+
+  /// 上一页的游标，没有上一页时为空
+  #[prost(string, optional, tag = "3")]
+  pub prev_cursor: ::core::option::Option<::prost::alloc::string::String>,
+
+  #[prost(int64, optional, tag = "4")]
+  pub total_size: ::core::option::Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CursorPagePayload<T> {
+  pub page: CursorPage,
+  pub items: Vec<T>,
+}
+
+impl<T> CursorPagePayload<T> {
+  pub fn new(page: CursorPage, items: Vec<T>) -> Self {
+    Self { page, items }
+  }
+}
+
 #[derive(Clone, PartialEq, ::prost::Message, Serialize, Deserialize)]
 pub struct SortBy {
   #[prost(string, tag = "1")]
@@ -0,0 +1,27 @@
+//! crate: ultimate_common_macros
+//! `ultimate-common` 的过程宏，请通过 `ultimate_common` 重新导出的路径使用。
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod masked;
+
+/// 为结构体生成带脱敏的 `serde::Serialize` 与 `Debug` 实现。
+///
+/// 字段通过 `#[masked(...)]` 属性指定脱敏策略，未标注的字段原样输出：
+///
+/// - `phone`、`email`、`id_card`、`bank_card`、`name`、`address`
+/// - `keep(first = N, last = M)`：保留前 N 位与后 M 位
+/// - `redact`：完全遮盖
+/// - `hash`：输出以盲索引密钥计算的 HMAC-SHA256 摘要
+///
+/// 详见 `ultimate_common::model::sensitive::MaskStrategy`。
+///
+/// 生成的 `Serialize` 替代 `#[derive(Serialize)]`（二者不能同时使用），并支持以下 serde 属性：
+/// 结构体上的 `rename`、`rename_all`，字段上的 `skip`、`skip_serializing`、`skip_serializing_if`、`rename`。
+/// 只影响反序列化的属性被忽略，其它属性（如 `flatten`、`serialize_with`）为编译错误。
+/// 跳过序列化的字段同样不会出现在 `Debug` 输出中。
+#[proc_macro_derive(Masked, attributes(masked, serde))]
+pub fn derive_masked(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  masked::expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
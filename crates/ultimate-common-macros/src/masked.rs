@@ -0,0 +1,306 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::meta::ParseNestedMeta;
+use syn::{parse_quote, Attribute, Data, DeriveInput, ExprPath, Field, Fields, Generics, LitInt, LitStr};
+
+pub fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+  let fields = match &input.data {
+    Data::Struct(data) => match &data.fields {
+      Fields::Named(fields) => &fields.named,
+      _ => return Err(syn::Error::new_spanned(&input.ident, "Masked only supports structs with named fields")),
+    },
+    _ => return Err(syn::Error::new_spanned(&input.ident, "Masked only supports structs")),
+  };
+
+  let container = SerdeContainer::parse(&input.attrs)?;
+  let ident = &input.ident;
+  let name = ident.to_string();
+  let ser_name = container.rename.clone().unwrap_or_else(|| name.clone());
+
+  let mut ser_fields = Vec::with_capacity(fields.len());
+  let mut ser_lens = Vec::with_capacity(fields.len());
+  let mut debug_fields = Vec::with_capacity(fields.len());
+  let mut has_skipped = false;
+  for field in fields {
+    let field_ident = field.ident.as_ref().unwrap();
+    let field_name = field_ident.unraw().to_string();
+    let serde = SerdeField::parse(field)?;
+    if serde.skip {
+      has_skipped = true;
+      continue;
+    }
+
+    let value = match parse_strategy(field)? {
+      Some(strategy) => {
+        quote! { &::ultimate_common::model::sensitive::MaskedField::new(&self.#field_ident, #strategy) }
+      }
+      None => quote! { &self.#field_ident },
+    };
+    let ser_field_name = match (&serde.rename, container.rename_all) {
+      (Some(rename), _) => rename.clone(),
+      (None, Some(rule)) => rule.apply(&field_name),
+      (None, None) => field_name.clone(),
+    };
+    match &serde.skip_serializing_if {
+      Some(path) => {
+        ser_fields.push(quote! {
+          if #path(&self.#field_ident) {
+            state.skip_field(#ser_field_name)?;
+          } else {
+            state.serialize_field(#ser_field_name, #value)?;
+          }
+        });
+        ser_lens.push(quote! { if #path(&self.#field_ident) { 0 } else { 1 } });
+      }
+      None => {
+        ser_fields.push(quote! { state.serialize_field(#ser_field_name, #value)?; });
+        ser_lens.push(quote! { 1 });
+      }
+    }
+    debug_fields.push(quote! { .field(#field_name, #value) });
+  }
+  let debug_finish = if has_skipped {
+    quote! { finish_non_exhaustive }
+  } else {
+    quote! { finish }
+  };
+
+  let serde_path = quote! { ::ultimate_common::model::sensitive::__private::serde };
+  let ser_generics = with_bound(&input.generics, parse_quote! { #serde_path::Serialize });
+  let (ser_impl_generics, ty_generics, ser_where_clause) = ser_generics.split_for_impl();
+  let debug_generics = with_bound(&input.generics, parse_quote! { ::core::fmt::Debug });
+  let (debug_impl_generics, _, debug_where_clause) = debug_generics.split_for_impl();
+
+  Ok(quote! {
+    impl #ser_impl_generics #serde_path::Serialize for #ident #ty_generics #ser_where_clause {
+      fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+      where
+        S: #serde_path::Serializer,
+      {
+        use #serde_path::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct(#ser_name, 0 #(+ #ser_lens)*)?;
+        #(#ser_fields)*
+        state.end()
+      }
+    }
+
+    impl #debug_impl_generics ::core::fmt::Debug for #ident #ty_generics #debug_where_clause {
+      fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+        f.debug_struct(#name)#(#debug_fields)*.#debug_finish()
+      }
+    }
+  })
+}
+
+/// 为每个类型参数加上 `bound` 约束
+fn with_bound(generics: &Generics, bound: syn::TypeParamBound) -> Generics {
+  let mut generics = generics.clone();
+  let predicates: Vec<syn::WherePredicate> = generics
+    .type_params()
+    .map(|param| {
+      let ident = &param.ident;
+      parse_quote! { #ident: #bound }
+    })
+    .collect();
+  generics.make_where_clause().predicates.extend(predicates);
+  generics
+}
+
+/// 结构体上的 serde 属性，只支持影响序列化的 `rename`、`rename_all`，忽略只影响反序列化的属性
+#[derive(Default)]
+struct SerdeContainer {
+  rename: Option<String>,
+  rename_all: Option<RenameRule>,
+}
+
+impl SerdeContainer {
+  fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+    let mut container = SerdeContainer::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+      attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("rename") {
+          container.rename = serialize_name(&meta)?;
+        } else if meta.path.is_ident("rename_all") {
+          if let Some(rule) = serialize_name(&meta)? {
+            let rename_all = RenameRule::from_str(&rule);
+            container.rename_all =
+              Some(rename_all.ok_or_else(|| meta.error(format!("unknown rename rule `{}`", rule)))?);
+          }
+        } else if ["deny_unknown_fields", "default", "from", "try_from", "expecting"]
+          .iter()
+          .any(|name| meta.path.is_ident(name))
+        {
+          skip_value(&meta)?;
+        } else {
+          return Err(meta.error("this serde attribute is not supported by Masked"));
+        }
+        Ok(())
+      })?;
+    }
+    Ok(container)
+  }
+}
+
+/// 字段上的 serde 属性，只支持 `skip`、`skip_serializing`、`skip_serializing_if`、`rename`，
+/// 忽略只影响反序列化的属性
+#[derive(Default)]
+struct SerdeField {
+  skip: bool,
+  rename: Option<String>,
+  skip_serializing_if: Option<ExprPath>,
+}
+
+impl SerdeField {
+  fn parse(field: &Field) -> syn::Result<Self> {
+    let mut serde = SerdeField::default();
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+      attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("skip") || meta.path.is_ident("skip_serializing") {
+          serde.skip = true;
+        } else if meta.path.is_ident("rename") {
+          serde.rename = serialize_name(&meta)?;
+        } else if meta.path.is_ident("skip_serializing_if") {
+          serde.skip_serializing_if = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+        } else if ["skip_deserializing", "default", "alias", "deserialize_with", "borrow"]
+          .iter()
+          .any(|name| meta.path.is_ident(name))
+        {
+          skip_value(&meta)?;
+        } else {
+          return Err(meta.error("this serde attribute is not supported by Masked"));
+        }
+        Ok(())
+      })?;
+    }
+    Ok(serde)
+  }
+}
+
+/// 解析 `rename = "..."` 或 `rename(serialize = "...")` 中序列化使用的名称，只指定了 `deserialize` 时返回 None
+fn serialize_name(meta: &ParseNestedMeta) -> syn::Result<Option<String>> {
+  if meta.input.peek(syn::Token![=]) {
+    return Ok(Some(meta.value()?.parse::<LitStr>()?.value()));
+  }
+  let mut name = None;
+  meta.parse_nested_meta(|m| {
+    let value = m.value()?.parse::<LitStr>()?.value();
+    if m.path.is_ident("serialize") {
+      name = Some(value);
+    } else if !m.path.is_ident("deserialize") {
+      return Err(m.error("expected `serialize` or `deserialize`"));
+    }
+    Ok(())
+  })?;
+  Ok(name)
+}
+
+/// 跳过不影响序列化的属性的值
+fn skip_value(meta: &ParseNestedMeta) -> syn::Result<()> {
+  if meta.input.peek(syn::Token![=]) {
+    meta.value()?.parse::<syn::Lit>()?;
+  } else if meta.input.peek(syn::token::Paren) {
+    meta.parse_nested_meta(|m| skip_value(&m))?;
+  }
+  Ok(())
+}
+
+/// 同 serde 的 `rename_all` 规则，字段名为 snake_case
+#[derive(Clone, Copy)]
+enum RenameRule {
+  Lower,
+  Upper,
+  Pascal,
+  Camel,
+  Snake,
+  ScreamingSnake,
+  Kebab,
+  ScreamingKebab,
+}
+
+impl RenameRule {
+  fn from_str(rule: &str) -> Option<Self> {
+    let rule = match rule {
+      "lowercase" => Self::Lower,
+      "UPPERCASE" => Self::Upper,
+      "PascalCase" => Self::Pascal,
+      "camelCase" => Self::Camel,
+      "snake_case" => Self::Snake,
+      "SCREAMING_SNAKE_CASE" => Self::ScreamingSnake,
+      "kebab-case" => Self::Kebab,
+      "SCREAMING-KEBAB-CASE" => Self::ScreamingKebab,
+      _ => return None,
+    };
+    Some(rule)
+  }
+
+  fn apply(&self, field: &str) -> String {
+    match self {
+      Self::Lower | Self::Snake => field.to_string(),
+      Self::Upper | Self::ScreamingSnake => field.to_ascii_uppercase(),
+      Self::Pascal => field
+        .split('_')
+        .map(|word| {
+          let mut chars = word.chars();
+          chars.next().map(|c| c.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+        })
+        .collect(),
+      Self::Camel => {
+        let pascal = Self::Pascal.apply(field);
+        let mut chars = pascal.chars();
+        chars.next().map(|c| c.to_ascii_lowercase().to_string() + chars.as_str()).unwrap_or_default()
+      }
+      Self::Kebab => field.replace('_', "-"),
+      Self::ScreamingKebab => field.to_ascii_uppercase().replace('_', "-"),
+    }
+  }
+}
+
+fn parse_strategy(field: &Field) -> syn::Result<Option<TokenStream>> {
+  let mut strategy = None;
+  for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("masked")) {
+    attr.parse_nested_meta(|meta| {
+      if strategy.is_some() {
+        return Err(meta.error("only one masking strategy is allowed per field"));
+      }
+
+      let path = quote! { ::ultimate_common::model::sensitive::MaskStrategy };
+      let s = if meta.path.is_ident("phone") {
+        quote! { #path::Phone }
+      } else if meta.path.is_ident("email") {
+        quote! { #path::Email }
+      } else if meta.path.is_ident("id_card") {
+        quote! { #path::IdCard }
+      } else if meta.path.is_ident("bank_card") {
+        quote! { #path::BankCard }
+      } else if meta.path.is_ident("name") {
+        quote! { #path::Name }
+      } else if meta.path.is_ident("address") {
+        quote! { #path::Address }
+      } else if meta.path.is_ident("redact") {
+        quote! { #path::Redact }
+      } else if meta.path.is_ident("hash") {
+        quote! { #path::Hash }
+      } else if meta.path.is_ident("keep") {
+        let mut first = 0usize;
+        let mut last = 0usize;
+        meta.parse_nested_meta(|m| {
+          if m.path.is_ident("first") {
+            first = m.value()?.parse::<LitInt>()?.base10_parse()?;
+          } else if m.path.is_ident("last") {
+            last = m.value()?.parse::<LitInt>()?.base10_parse()?;
+          } else {
+            return Err(m.error("expected `first` or `last`"));
+          }
+          Ok(())
+        })?;
+        quote! { #path::Keep { first: #first, last: #last } }
+      } else {
+        return Err(meta.error("unsupported masking strategy"));
+      };
+      strategy = Some(s);
+      Ok(())
+    })?;
+  }
+  Ok(strategy)
+}
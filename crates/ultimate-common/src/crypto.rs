@@ -0,0 +1,153 @@
+//! 基于 AES-256-GCM 的字段级加密，以及用于等值查询的 HMAC 盲索引。
+//!
+//! 密文格式为 `<key_id>:<base64url(nonce || ciphertext)>`，`key_id` 用于密钥轮换：
+//! 新数据总是使用当前密钥加密，旧数据可使用保留在 [Keyring] 中的历史密钥解密。
+use std::{collections::HashMap, sync::OnceLock};
+
+use aes_gcm::{
+  aead::{Aead, AeadCore, KeyInit, OsRng},
+  Aes256Gcm, Nonce,
+};
+use serde::Serialize;
+
+use crate::{
+  digest::{b64u_decode, b64u_encode, hmac_sha256_string},
+  Error, Result,
+};
+
+const NONCE_LEN: usize = 12;
+
+static KEYRING: OnceLock<Keyring> = OnceLock::new();
+
+/// 初始化全局密钥环，应在程序启动时调用一次。
+pub fn init_keyring(keyring: Keyring) -> Result<()> {
+  KEYRING.set(keyring).map_err(|_| Error::KeyringAlreadyInitialized)
+}
+
+/// 获取全局密钥环
+pub fn keyring() -> Result<&'static Keyring> {
+  KEYRING.get().ok_or(Error::KeyringNotInitialized)
+}
+
+/// 使用全局密钥环计算值的盲索引，用于对加密列做等值查询。
+///
+/// 值先按 JSON 序列化再计算 HMAC，与 [crate::model::Encrypted::blind_index] 的结果一致。
+pub fn blind_index<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+  keyring()?.blind_index(&serde_json::to_vec(value)?)
+}
+
+/// 获取密文使用的密钥 ID
+pub fn key_id_of(ciphertext: &str) -> Option<&str> {
+  ciphertext.split_once(':').map(|(key_id, _)| key_id)
+}
+
+/// 加密密钥环
+#[derive(Clone)]
+pub struct Keyring {
+  current_key_id: String,
+  keys: HashMap<String, Aes256Gcm>,
+  blind_index_key: Option<Vec<u8>>,
+}
+
+impl Keyring {
+  /// 构造一个密钥环
+  ///
+  /// # Arguments
+  ///
+  /// * `key_id` - 当前密钥 ID，新数据将使用此密钥加密
+  /// * `key` - 32 字节的 AES-256 密钥
+  pub fn new(key_id: impl Into<String>, key: &[u8]) -> Result<Self> {
+    let key_id = key_id.into();
+    let mut keys = HashMap::new();
+    keys.insert(key_id.clone(), new_cipher(key)?);
+    Ok(Self { current_key_id: key_id, keys, blind_index_key: None })
+  }
+
+  /// 添加历史密钥，只用于解密已有数据
+  pub fn with_key(mut self, key_id: impl Into<String>, key: &[u8]) -> Result<Self> {
+    self.keys.insert(key_id.into(), new_cipher(key)?);
+    Ok(self)
+  }
+
+  /// 设置盲索引使用的 HMAC 密钥。盲索引密钥不参与轮换，修改后需重建所有盲索引列。
+  pub fn with_blind_index_key(mut self, key: impl Into<Vec<u8>>) -> Self {
+    self.blind_index_key = Some(key.into());
+    self
+  }
+
+  pub fn current_key_id(&self) -> &str {
+    &self.current_key_id
+  }
+
+  /// 使用当前密钥加密
+  pub fn encrypt(&self, plaintext: &[u8]) -> Result<String> {
+    let cipher = self.keys.get(&self.current_key_id).ok_or_else(|| Error::KeyNotFound(self.current_key_id.clone()))?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let encrypted = cipher.encrypt(&nonce, plaintext).map_err(|_| Error::EncryptFail)?;
+
+    let mut payload = Vec::with_capacity(NONCE_LEN + encrypted.len());
+    payload.extend_from_slice(&nonce);
+    payload.extend_from_slice(&encrypted);
+    Ok(format!("{}:{}", self.current_key_id, b64u_encode(payload)))
+  }
+
+  /// 根据密文中的密钥 ID 选择密钥解密
+  pub fn decrypt(&self, ciphertext: &str) -> Result<Vec<u8>> {
+    let (key_id, payload) = ciphertext.split_once(':').ok_or(Error::DecryptFail)?;
+    let cipher = self.keys.get(key_id).ok_or_else(|| Error::KeyNotFound(key_id.to_string()))?;
+    let payload = b64u_decode(payload)?;
+    if payload.len() < NONCE_LEN {
+      return Err(Error::DecryptFail);
+    }
+
+    let (nonce, encrypted) = payload.split_at(NONCE_LEN);
+    cipher.decrypt(Nonce::from_slice(nonce), encrypted).map_err(|_| Error::DecryptFail)
+  }
+
+  /// 计算盲索引（HMAC-SHA256，16 进制小写）
+  pub fn blind_index(&self, plaintext: &[u8]) -> Result<String> {
+    let key = self.blind_index_key.as_deref().ok_or(Error::BlindIndexKeyMissing)?;
+    hmac_sha256_string(key, plaintext).map_err(|_| Error::KeyFail)
+  }
+}
+
+fn new_cipher(key: &[u8]) -> Result<Aes256Gcm> {
+  Aes256Gcm::new_from_slice(key).map_err(|_| Error::KeyFail)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const KEY_1: &[u8] = b"0123456789ABCDEF0123456789ABCDEF";
+  const KEY_2: &[u8] = b"FEDCBA9876543210FEDCBA9876543210";
+
+  #[test]
+  fn test_encrypt_and_rotate() -> Result<()> {
+    let old = Keyring::new("1", KEY_1)?;
+    let ciphertext = old.encrypt(b"13883712048")?;
+    assert_eq!(key_id_of(&ciphertext), Some("1"));
+    assert_ne!(old.encrypt(b"13883712048")?, ciphertext, "nonce should be random");
+
+    let new = Keyring::new("2", KEY_2)?.with_key("1", KEY_1)?;
+    assert_eq!(new.decrypt(&ciphertext)?, b"13883712048");
+    assert_eq!(key_id_of(&new.encrypt(b"13883712048")?), Some("2"));
+
+    let other = Keyring::new("2", KEY_2)?;
+    assert!(matches!(other.decrypt(&ciphertext), Err(Error::KeyNotFound(_))));
+
+    assert!(matches!(Keyring::new("3", b"short"), Err(Error::KeyFail)));
+    Ok(())
+  }
+
+  #[test]
+  fn test_blind_index() -> Result<()> {
+    let keyring = Keyring::new("1", KEY_1)?;
+    assert!(matches!(keyring.blind_index(b"a"), Err(Error::BlindIndexKeyMissing)));
+
+    let keyring = keyring.with_blind_index_key(KEY_2);
+    assert_eq!(keyring.blind_index(b"a")?, keyring.blind_index(b"a")?);
+    assert_ne!(keyring.blind_index(b"a")?, keyring.blind_index(b"b")?);
+    Ok(())
+  }
+}
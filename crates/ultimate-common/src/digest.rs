@@ -1,4 +1,4 @@
-use base64ct::{Base64UrlUnpadded, Encoding};
+use base64ct::{Base64, Base64UrlUnpadded, Encoding};
 pub use hmac::digest::InvalidLength;
 use hmac::{
   digest::{
@@ -21,6 +21,13 @@ pub fn hmac_sha256(secret: &[u8], s: &[u8]) -> Result<CtOutput<HmacSha256>, Inva
   Ok(result)
 }
 
+/// 以常量时间校验 HMAC-SHA256 签名
+pub fn hmac_sha256_verify(secret: &[u8], s: &[u8], signature: &[u8]) -> Result<bool, InvalidLength> {
+  let mut mac = HmacSha256::new_from_slice(secret)?;
+  mac.update(s);
+  Ok(mac.verify_slice(signature).is_ok())
+}
+
 #[inline]
 pub fn hmac_sha256_string(secret: &[u8], s: &[u8]) -> Result<String, InvalidLength> {
   let bytes = hmac_sha256(secret, s)?.into_bytes();
@@ -49,6 +56,10 @@ pub fn b64u_decode(b64u: &str) -> Result<Vec<u8>, Error> {
   Base64UrlUnpadded::decode_vec(b64u).map_err(|_| Error::FailToB64uDecode(b64u.to_string()))
 }
 
+pub fn b64_decode(b64: &str) -> Result<Vec<u8>, Error> {
+  Base64::decode_vec(b64).map_err(|_| Error::FailToB64uDecode(b64.to_string()))
+}
+
 pub fn b64u_decode_to_string(b64u: &str) -> Result<String, Error> {
   b64u_decode(b64u).ok().and_then(|r| String::from_utf8(r).ok()).ok_or(Error::FailToB64uDecode(b64u.to_string()))
 }
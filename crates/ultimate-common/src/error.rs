@@ -9,6 +9,12 @@ pub enum Error {
   #[error("Parse date fail, data is {0}")]
   DateFailParse(String),
 
+  #[error("Invalid time zone: {0}")]
+  InvalidTimeZone(String),
+
+  #[error("Local time zone already initialized.")]
+  LocalZoneAlreadyInitialized,
+
   #[error("Key fail.")]
   KeyFail,
 
@@ -20,6 +26,47 @@ pub enum Error {
 
   #[error("Wrong format: {0}")]
   WrongFormat(&'static str),
+
+  // -- Crypto
+  #[error("Encrypt fail.")]
+  EncryptFail,
+
+  #[error("Decrypt fail.")]
+  DecryptFail,
+
+  #[error("Encryption key not found, key id is {0}")]
+  KeyNotFound(String),
+
+  #[error("Invalid encryption key {0}, expect base64 encoded 32 bytes")]
+  InvalidEncryptionKey(String),
+
+  #[error("Blind index key missing.")]
+  BlindIndexKeyMissing,
+
+  #[error("Keyring not initialized.")]
+  KeyringNotInitialized,
+
+  #[error("Keyring already initialized.")]
+  KeyringAlreadyInitialized,
+
+  // -- Id
+  #[error("Invalid worker id: {0}")]
+  InvalidWorkerId(String),
+
+  #[error("Clock moved backwards {0} milliseconds.")]
+  ClockMovedBackwards(u64),
+
+  #[error("Id overflow.")]
+  IdOverflow,
+
+  #[error("Id generator already initialized.")]
+  IdGeneratorAlreadyInitialized,
+
+  #[error(transparent)]
+  JsonError(#[from] serde_json::Error),
+
+  #[error(transparent)]
+  IoError(#[from] std::io::Error),
 }
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -0,0 +1,259 @@
+//! 分布式 ID 生成器。
+//!
+//! - [Snowflake]：64 位趋势递增整数，由时间戳、工作节点 ID 和序列号组成，适合分库分表
+//! - [UuidV7Generator]：按时间排序的 UUIDv7（需启用 `uuid` feature）
+//! - [UlidGenerator]：单调递增的 ULID（需启用 `ulid` feature）
+use std::{
+  sync::{Mutex, OnceLock},
+  thread,
+  time::Duration,
+};
+
+use crate::{
+  time::{from_milliseconds, now_epoch_millis, UtcDateTime},
+  Error, Result,
+};
+
+/// 读取 Snowflake 工作节点 ID 的环境变量，与 `ultimate.app.worker_id` 配置项对应
+pub const WORKER_ID_ENV: &str = "ULTIMATE__APP__WORKER_ID";
+
+const WORKER_ID_BITS: u32 = 10;
+const SEQUENCE_BITS: u32 = 12;
+const MAX_WORKER_ID: u16 = (1 << WORKER_ID_BITS) - 1;
+const MAX_SEQUENCE: u16 = (1 << SEQUENCE_BITS) - 1;
+
+/// 默认纪元：2024-01-01T00:00:00Z
+const DEFAULT_EPOCH_MILLIS: i64 = 1_704_067_200_000;
+
+/// 默认允许等待的时钟回拨时长
+const DEFAULT_MAX_BACKWARD: Duration = Duration::from_millis(10);
+
+static SNOWFLAKE: OnceLock<Snowflake> = OnceLock::new();
+
+/// ID 生成器
+pub trait IdGenerator: Send + Sync {
+  type Id;
+
+  fn next_id(&self) -> Result<Self::Id>;
+}
+
+/// 初始化全局 Snowflake 生成器，应在程序启动时调用一次。
+pub fn init_snowflake(snowflake: Snowflake) -> Result<()> {
+  SNOWFLAKE.set(snowflake).map_err(|_| Error::IdGeneratorAlreadyInitialized)
+}
+
+/// 获取全局 Snowflake 生成器。
+///
+/// 未通过 [init_snowflake] 初始化时，从环境变量 [WORKER_ID_ENV] 读取工作节点 ID，未设置时返回错误：
+/// 多个节点使用相同的默认工作节点 ID 会生成重复的 ID。
+pub fn snowflake() -> Result<&'static Snowflake> {
+  if let Some(snowflake) = SNOWFLAKE.get() {
+    return Ok(snowflake);
+  }
+  let snowflake = Snowflake::from_env()?;
+  Ok(SNOWFLAKE.get_or_init(|| snowflake))
+}
+
+/// Snowflake ID 生成器。
+///
+/// ID 由 1 位符号位（恒为 0）、41 位毫秒时间戳（相对纪元，约可用 69 年）、10 位工作节点 ID 和 12 位序列号组成，
+/// 同一节点每毫秒最多生成 4096 个 ID。
+///
+/// 时钟回拨不超过 `max_backward`（默认 10 毫秒）时释放锁并等待时钟追上，超过时返回 [Error::ClockMovedBackwards]。
+///
+/// # Examples
+///
+/// ```rust
+/// use ultimate_common::id::{IdGenerator, Snowflake};
+///
+/// let g = Snowflake::new(7).unwrap();
+/// let a = g.next_id().unwrap();
+/// let b = g.next_id().unwrap();
+/// assert!(a < b);
+/// assert_eq!(Snowflake::worker_id_of(a), 7);
+/// ```
+#[derive(Debug)]
+pub struct Snowflake {
+  worker_id: u16,
+  epoch_millis: i64,
+  max_backward: Duration,
+  state: Mutex<SnowflakeState>,
+}
+
+#[derive(Debug, Default)]
+struct SnowflakeState {
+  last_millis: i64,
+  sequence: u16,
+}
+
+impl Snowflake {
+  /// 构造 Snowflake 生成器，`worker_id` 取值 0 ~ 1023，且在所有节点间唯一
+  pub fn new(worker_id: u16) -> Result<Self> {
+    if worker_id > MAX_WORKER_ID {
+      return Err(Error::InvalidWorkerId(worker_id.to_string()));
+    }
+    Ok(Self {
+      worker_id,
+      epoch_millis: DEFAULT_EPOCH_MILLIS,
+      max_backward: DEFAULT_MAX_BACKWARD,
+      state: Mutex::new(SnowflakeState::default()),
+    })
+  }
+
+  /// 从环境变量 [WORKER_ID_ENV] 读取工作节点 ID 构造，未设置时返回 [Error::MissingEnv]
+  pub fn from_env() -> Result<Self> {
+    let v = std::env::var(WORKER_ID_ENV).map_err(|_| Error::MissingEnv(WORKER_ID_ENV))?;
+    Self::new(v.trim().parse().map_err(|_| Error::InvalidWorkerId(v))?)
+  }
+
+  /// 设置纪元。所有节点必须使用相同的纪元，且已有数据后不可修改。
+  pub fn with_epoch(mut self, epoch: UtcDateTime) -> Self {
+    self.epoch_millis = epoch.timestamp_millis();
+    self
+  }
+
+  /// 设置允许等待的最大时钟回拨时长
+  pub fn with_max_backward(mut self, max_backward: Duration) -> Self {
+    self.max_backward = max_backward;
+    self
+  }
+
+  pub fn worker_id(&self) -> u16 {
+    self.worker_id
+  }
+
+  /// 解析 ID 的生成时间
+  pub fn datetime_of(&self, id: i64) -> Result<UtcDateTime> {
+    from_milliseconds((id >> (WORKER_ID_BITS + SEQUENCE_BITS)) + self.epoch_millis)
+  }
+
+  /// 解析 ID 的工作节点 ID
+  pub fn worker_id_of(id: i64) -> u16 {
+    ((id >> SEQUENCE_BITS) & MAX_WORKER_ID as i64) as u16
+  }
+
+  fn current_millis(&self) -> i64 {
+    now_epoch_millis() - self.epoch_millis
+  }
+}
+
+impl IdGenerator for Snowflake {
+  type Id = i64;
+
+  fn next_id(&self) -> Result<i64> {
+    let (mut state, mut millis) = loop {
+      let state = self.state.lock().unwrap();
+      let millis = self.current_millis();
+      if millis >= state.last_millis {
+        break (state, millis);
+      }
+
+      let backward = (state.last_millis - millis) as u64;
+      if backward > self.max_backward.as_millis() as u64 {
+        return Err(Error::ClockMovedBackwards(backward));
+      }
+      // 等待时钟追上时不持有锁，避免阻塞其它线程
+      drop(state);
+      thread::sleep(Duration::from_millis(backward));
+    };
+
+    if millis == state.last_millis {
+      state.sequence = (state.sequence + 1) & MAX_SEQUENCE;
+      if state.sequence == 0 {
+        // 当前毫秒序列号已用完，等待下一毫秒
+        while millis <= state.last_millis {
+          thread::yield_now();
+          millis = self.current_millis();
+        }
+      }
+    } else {
+      state.sequence = 0;
+    }
+    state.last_millis = millis;
+
+    Ok(
+      (millis << (WORKER_ID_BITS + SEQUENCE_BITS)) | ((self.worker_id as i64) << SEQUENCE_BITS) | state.sequence as i64,
+    )
+  }
+}
+
+/// UUIDv7 生成器，同一进程内生成的 UUID 保证单调递增
+#[cfg(feature = "uuid")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidV7Generator;
+
+#[cfg(feature = "uuid")]
+impl IdGenerator for UuidV7Generator {
+  type Id = uuid::Uuid;
+
+  fn next_id(&self) -> Result<uuid::Uuid> {
+    Ok(uuid::Uuid::now_v7())
+  }
+}
+
+/// 单调递增的 ULID 生成器，同一毫秒内在上一个 ULID 的随机部分上递增
+#[cfg(feature = "ulid")]
+#[derive(Default)]
+pub struct UlidGenerator {
+  generator: Mutex<ulid::Generator>,
+}
+
+#[cfg(feature = "ulid")]
+impl UlidGenerator {
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
+
+#[cfg(feature = "ulid")]
+impl IdGenerator for UlidGenerator {
+  type Id = ulid::Ulid;
+
+  fn next_id(&self) -> Result<ulid::Ulid> {
+    self.generator.lock().unwrap().generate().map_err(|_| Error::IdOverflow)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+
+  use super::*;
+
+  #[test]
+  fn test_snowflake() {
+    let g = Snowflake::new(MAX_WORKER_ID).unwrap();
+    let ids: Vec<i64> = (0..10_000).map(|_| g.next_id().unwrap()).collect();
+    assert!(ids.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+    assert!(ids.iter().all(|id| Snowflake::worker_id_of(*id) == MAX_WORKER_ID));
+
+    let delta = crate::time::now_utc() - g.datetime_of(ids[0]).unwrap();
+    assert!(delta.num_seconds().abs() < 5);
+
+    assert!(Snowflake::new(MAX_WORKER_ID + 1).is_err());
+  }
+
+  #[test]
+  fn test_snowflake_clock_backward() {
+    let g = Snowflake::new(1).unwrap();
+    g.state.lock().unwrap().last_millis = g.current_millis() + 60_000;
+    assert!(matches!(g.next_id(), Err(Error::ClockMovedBackwards(_))));
+
+    g.state.lock().unwrap().last_millis = g.current_millis() + 5;
+    assert!(g.next_id().is_ok());
+  }
+
+  #[test]
+  fn test_snowflake_from_env() {
+    std::env::remove_var(WORKER_ID_ENV);
+    assert!(matches!(Snowflake::from_env(), Err(Error::MissingEnv(WORKER_ID_ENV))));
+    assert!(snowflake().is_err());
+
+    std::env::set_var(WORKER_ID_ENV, " 12 ");
+    assert_eq!(Snowflake::from_env().unwrap().worker_id(), 12);
+    std::env::set_var(WORKER_ID_ENV, "1024");
+    assert!(matches!(Snowflake::from_env(), Err(Error::InvalidWorkerId(_))));
+    std::env::remove_var(WORKER_ID_ENV);
+  }
+}
@@ -1,8 +1,14 @@
 //! crate: ultimate_common
 //! 常用 Rust 工具库。
+
+// 使 `#[derive(Masked)]` 等过程宏生成的 `::ultimate_common` 路径在本 crate 内也可用
+extern crate self as ultimate_common;
+
+pub mod crypto;
 pub mod digest;
 pub mod env;
 mod error;
+pub mod id;
 pub mod meta;
 pub mod model;
 pub mod regex;
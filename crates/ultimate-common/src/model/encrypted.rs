@@ -0,0 +1,299 @@
+use core::{fmt, ops::Deref};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+  crypto::{self, Keyring},
+  model::sensitive::{MaskStrategy, MaskedValue},
+  Result,
+};
+
+/// 加密字符串，存储时为 AES-GCM 密文
+pub type EncryptedString = Encrypted<String>;
+
+/// 字段级加密的值。
+///
+/// 构造时即使用全局密钥环（见 [crypto::init_keyring]）加密，内存中同时持有明文与密文：
+/// 写入数据库（sqlx、sea-query）时使用密文，从数据库读取时自动解密。
+///
+/// 使用 serde 序列化时输出的是**明文**，对外输出时可结合 `#[derive(Masked)]` 脱敏；
+/// `Debug` 只输出密钥 ID。
+///
+/// # Examples
+///
+/// ```rust
+/// use ultimate_common::{crypto::Keyring, model::EncryptedString};
+///
+/// let keyring = Keyring::new("1", b"0123456789ABCDEF0123456789ABCDEF").unwrap();
+/// let phone = EncryptedString::new_with("13883712048".to_string(), &keyring).unwrap();
+/// assert_eq!(phone.key_id(), "1");
+///
+/// let decrypted = EncryptedString::from_ciphertext_with(phone.ciphertext().to_string(), &keyring).unwrap();
+/// assert_eq!(decrypted.value(), "13883712048");
+/// ```
+#[derive(Clone)]
+pub struct Encrypted<T> {
+  value: T,
+  ciphertext: String,
+}
+
+impl<T: Serialize> Encrypted<T> {
+  /// 使用全局密钥环加密
+  pub fn new(value: T) -> Result<Self> {
+    Self::new_with(value, crypto::keyring()?)
+  }
+
+  pub fn new_with(value: T, keyring: &Keyring) -> Result<Self> {
+    let ciphertext = keyring.encrypt(&serde_json::to_vec(&value)?)?;
+    Ok(Self { value, ciphertext })
+  }
+
+  /// 使用全局密钥环计算盲索引
+  pub fn blind_index(&self) -> Result<String> {
+    crypto::blind_index(&self.value)
+  }
+
+  /// 密文不是由当前密钥加密时需要轮换
+  pub fn needs_rotation(&self) -> Result<bool> {
+    Ok(self.key_id() != crypto::keyring()?.current_key_id())
+  }
+
+  /// 使用全局密钥环的当前密钥重新加密
+  pub fn rotate(self) -> Result<Self> {
+    Self::new(self.value)
+  }
+}
+
+impl<T: DeserializeOwned> Encrypted<T> {
+  /// 使用全局密钥环解密
+  pub fn from_ciphertext(ciphertext: String) -> Result<Self> {
+    Self::from_ciphertext_with(ciphertext, crypto::keyring()?)
+  }
+
+  pub fn from_ciphertext_with(ciphertext: String, keyring: &Keyring) -> Result<Self> {
+    let value = serde_json::from_slice(&keyring.decrypt(&ciphertext)?)?;
+    Ok(Self { value, ciphertext })
+  }
+}
+
+impl<T> Encrypted<T> {
+  pub fn value(&self) -> &T {
+    &self.value
+  }
+
+  pub fn into_value(self) -> T {
+    self.value
+  }
+
+  pub fn ciphertext(&self) -> &str {
+    &self.ciphertext
+  }
+
+  pub fn key_id(&self) -> &str {
+    crypto::key_id_of(&self.ciphertext).unwrap_or_default()
+  }
+}
+
+impl<T> Deref for Encrypted<T> {
+  type Target = T;
+
+  fn deref(&self) -> &Self::Target {
+    &self.value
+  }
+}
+
+impl<T> fmt::Debug for Encrypted<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Encrypted").field("key_id", &self.key_id()).finish_non_exhaustive()
+  }
+}
+
+impl<T: PartialEq> PartialEq for Encrypted<T> {
+  fn eq(&self, other: &Self) -> bool {
+    self.value == other.value
+  }
+}
+
+impl<T: Serialize> Serialize for Encrypted<T> {
+  fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+  where
+    S: serde::Serializer,
+  {
+    self.value.serialize(serializer)
+  }
+}
+
+impl<'de, T: Serialize + Deserialize<'de>> Deserialize<'de> for Encrypted<T> {
+  fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+  where
+    D: serde::Deserializer<'de>,
+  {
+    let value = T::deserialize(deserializer)?;
+    Encrypted::new(value).map_err(serde::de::Error::custom)
+  }
+}
+
+impl<T: MaskedValue> MaskedValue for Encrypted<T> {
+  fn serialize_masked<S>(&self, strategy: MaskStrategy, serializer: S) -> core::result::Result<S::Ok, S::Error>
+  where
+    S: serde::Serializer,
+  {
+    self.value.serialize_masked(strategy, serializer)
+  }
+
+  fn fmt_masked(&self, strategy: MaskStrategy, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self.value.fmt_masked(strategy, f)
+  }
+}
+
+#[cfg(feature = "sqlx")]
+mod sqlx_impls {
+  use serde::de::DeserializeOwned;
+  use sqlx::{
+    database::{HasArguments, HasValueRef},
+    encode::IsNull,
+    error::BoxDynError,
+    Database, Decode, Encode, Type,
+  };
+
+  use super::Encrypted;
+
+  impl<T, DB> Type<DB> for Encrypted<T>
+  where
+    DB: Database,
+    String: Type<DB>,
+  {
+    fn type_info() -> DB::TypeInfo {
+      <String as Type<DB>>::type_info()
+    }
+
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+      <String as Type<DB>>::compatible(ty)
+    }
+  }
+
+  impl<'q, T, DB> Encode<'q, DB> for Encrypted<T>
+  where
+    DB: Database,
+    String: Encode<'q, DB>,
+  {
+    fn encode_by_ref(&self, buf: &mut <DB as HasArguments<'q>>::ArgumentBuffer) -> IsNull {
+      self.ciphertext.encode_by_ref(buf)
+    }
+  }
+
+  impl<'r, T, DB> Decode<'r, DB> for Encrypted<T>
+  where
+    T: DeserializeOwned,
+    DB: Database,
+    String: Decode<'r, DB>,
+  {
+    fn decode(value: <DB as HasValueRef<'r>>::ValueRef) -> Result<Self, BoxDynError> {
+      let ciphertext = <String as Decode<DB>>::decode(value)?;
+      Ok(Encrypted::from_ciphertext(ciphertext)?)
+    }
+  }
+}
+
+#[cfg(feature = "sea-query")]
+mod sea_query_impls {
+  use sea_query::{Nullable, Value};
+
+  use super::Encrypted;
+
+  impl<T> From<Encrypted<T>> for Value {
+    fn from(value: Encrypted<T>) -> Self {
+      Value::String(Some(Box::new(value.ciphertext)))
+    }
+  }
+
+  impl<T> Nullable for Encrypted<T> {
+    fn null() -> Value {
+      Value::String(None)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::model::sensitive::Masked;
+
+  #[derive(Masked)]
+  struct Contact {
+    #[masked(phone)]
+    phone: EncryptedString,
+  }
+
+  #[test]
+  fn test_encrypted() -> Result<()> {
+    let keyring = Keyring::new("1", b"0123456789ABCDEF0123456789ABCDEF")?;
+    let phone = EncryptedString::new_with("13883712048".to_string(), &keyring)?;
+    assert!(!phone.ciphertext().contains("13883712048"));
+    assert_eq!(format!("{:?}", phone), r#"Encrypted { key_id: "1", .. }"#);
+
+    let contact = Contact { phone: phone.clone() };
+    assert_eq!(serde_json::to_string(&contact)?, r#"{"phone":"138****2048"}"#);
+
+    let decrypted = EncryptedString::from_ciphertext_with(phone.ciphertext().to_string(), &keyring)?;
+    assert_eq!(decrypted, phone);
+    Ok(())
+  }
+
+  /// 写入、读取数据库时使用全局密钥环
+  #[cfg(any(feature = "sqlx", feature = "sea-query"))]
+  fn init_test_keyring() -> &'static Keyring {
+    let _ = crypto::init_keyring(Keyring::new("1", b"0123456789ABCDEF0123456789ABCDEF").unwrap());
+    crypto::keyring().unwrap()
+  }
+
+  #[cfg(feature = "sqlx")]
+  #[tokio::test]
+  async fn test_encrypted_sqlx_round_trip() -> anyhow::Result<()> {
+    init_test_keyring();
+    let pool = sqlx::SqlitePool::connect("sqlite::memory:").await?;
+    sqlx::query("CREATE TABLE contact (id INTEGER PRIMARY KEY, phone TEXT, email TEXT)").execute(&pool).await?;
+
+    let phone = EncryptedString::new("13883712048".to_string())?;
+    let email: Option<Encrypted<String>> = None;
+    sqlx::query("INSERT INTO contact (id, phone, email) VALUES (1, ?, ?)")
+      .bind(&phone)
+      .bind(&email)
+      .execute(&pool)
+      .await?;
+
+    // 数据库中存储的是密文
+    let stored: String = sqlx::query_scalar("SELECT phone FROM contact WHERE id = 1").fetch_one(&pool).await?;
+    assert_eq!(stored, phone.ciphertext());
+
+    let (decoded, email): (EncryptedString, Option<EncryptedString>) =
+      sqlx::query_as("SELECT phone, email FROM contact WHERE id = 1").fetch_one(&pool).await?;
+    assert_eq!(decoded.value(), "13883712048");
+    assert_eq!(decoded.ciphertext(), phone.ciphertext());
+    assert!(email.is_none());
+
+    // 密文无法解密时解码失败
+    sqlx::query("UPDATE contact SET phone = '1:invalid' WHERE id = 1").execute(&pool).await?;
+    let result: core::result::Result<EncryptedString, sqlx::Error> =
+      sqlx::query_scalar("SELECT phone FROM contact WHERE id = 1").fetch_one(&pool).await;
+    assert!(result.is_err());
+    Ok(())
+  }
+
+  #[cfg(feature = "sea-query")]
+  #[test]
+  fn test_encrypted_sea_query_value() -> Result<()> {
+    use sea_query::{Nullable, Value};
+
+    let keyring = init_test_keyring();
+    let phone = EncryptedString::new("13883712048".to_string())?;
+    let value: Value = phone.clone().into();
+    let Value::String(Some(ciphertext)) = value else { panic!("expect string value, but got: {:?}", value) };
+    assert_eq!(*ciphertext, phone.ciphertext());
+    assert_eq!(EncryptedString::from_ciphertext_with(*ciphertext, keyring)?, phone);
+
+    assert_eq!(EncryptedString::null(), Value::String(None));
+    assert_eq!(Value::from(None::<EncryptedString>), Value::String(None));
+    Ok(())
+  }
+}
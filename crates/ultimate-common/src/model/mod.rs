@@ -1 +1,4 @@
+mod encrypted;
 pub mod sensitive;
+
+pub use encrypted::*;
@@ -0,0 +1,388 @@
+use core::fmt;
+use std::{borrow::Cow, cell::Cell};
+
+use serde::{Serialize, Serializer};
+
+use crate::crypto::{self, Keyring};
+
+const MASK_CHAR: char = '*';
+
+/// 完全遮盖时的输出，不泄露原始值的长度
+const REDACTED: &str = "******";
+
+thread_local! {
+  static UNMASKED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// 在闭包内渲染未脱敏的原始值，用于可信的输出端（如：内部审计、加密存储）。
+///
+/// 作用范围为当前线程，闭包返回后恢复之前的状态。
+///
+/// # Examples
+///
+/// ```rust
+/// use ultimate_common::model::sensitive::*;
+///
+/// #[derive(Masked)]
+/// struct User {
+///   #[masked(phone)]
+///   phone: String,
+/// }
+///
+/// let u = User { phone: "13883712048".to_string() };
+/// assert_eq!(serde_json::to_string(&u).unwrap(), r#"{"phone":"138****2048"}"#);
+///
+/// let text = with_unmasked(|| serde_json::to_string(&u).unwrap());
+/// assert_eq!(text, r#"{"phone":"13883712048"}"#);
+/// ```
+pub fn with_unmasked<R>(f: impl FnOnce() -> R) -> R {
+  struct Restore(bool);
+  impl Drop for Restore {
+    fn drop(&mut self) {
+      UNMASKED.with(|v| v.set(self.0));
+    }
+  }
+
+  let _restore = Restore(UNMASKED.with(|v| v.replace(true)));
+  f()
+}
+
+/// 当前线程是否渲染未脱敏的原始值
+pub fn is_unmasked() -> bool {
+  UNMASKED.with(|v| v.get())
+}
+
+/// 脱敏策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskStrategy {
+  /// 手机号，保留前 3 位与后 4 位：`138****2048`
+  Phone,
+  /// 邮箱，只保留用户名的首字符与域名：`y******@example.com`
+  Email,
+  /// 身份证号，保留前 3 位与后 4 位：`110***********1234`
+  IdCard,
+  /// 银行卡号，保留前 6 位与后 4 位：`622202*********0123`
+  BankCard,
+  /// 姓名，两个字时保留姓，多于两个字时保留首尾：`张*`、`欧*锋`
+  Name,
+  /// 地址，保留前 6 个字符：`北京市海淀区******`
+  Address,
+  /// 保留前 `first` 位与后 `last` 位，中间遮盖
+  Keep { first: usize, last: usize },
+  /// 完全遮盖
+  Redact,
+  /// 输出以盲索引密钥（[Keyring::with_blind_index_key]）计算的 HMAC-SHA256（16 进制小写），便于关联而不暴露原始值。
+  /// 未初始化全局密钥环或未设置盲索引密钥时完全遮盖
+  Hash,
+}
+
+impl MaskStrategy {
+  /// 对字符串按策略进行脱敏。按字符（而非字节）计算长度，可正确处理中文。
+  ///
+  /// # Examples
+  ///
+  /// ```rust
+  /// use ultimate_common::model::sensitive::MaskStrategy;
+  /// assert_eq!(MaskStrategy::Phone.mask("13883712048"), "138****2048");
+  /// assert_eq!(MaskStrategy::Email.mask("yangbajing@example.com"), "y*********@example.com");
+  /// assert_eq!(MaskStrategy::Name.mask("张三"), "张*");
+  /// assert_eq!(MaskStrategy::Keep { first: 1, last: 1 }.mask("abcd"), "a**d");
+  /// ```
+  pub fn mask(&self, v: &str) -> String {
+    match self {
+      MaskStrategy::Phone => keep(v, 3, 4),
+      MaskStrategy::Email => mask_email(v),
+      MaskStrategy::IdCard => keep(v, 3, 4),
+      MaskStrategy::BankCard => keep(v, 6, 4),
+      MaskStrategy::Name => mask_name(v),
+      MaskStrategy::Address => keep(v, 6, 0),
+      MaskStrategy::Keep { first, last } => keep(v, *first, *last),
+      MaskStrategy::Redact => REDACTED.to_string(),
+      MaskStrategy::Hash => hash(v, crypto::keyring().ok()),
+    }
+  }
+}
+
+/// 无密钥的摘要可以通过枚举手机号、证件号等取值范围反查，因此只使用带密钥的 HMAC
+fn hash(v: &str, keyring: Option<&Keyring>) -> String {
+  keyring.and_then(|keyring| keyring.blind_index(v.as_bytes()).ok()).unwrap_or_else(|| REDACTED.to_string())
+}
+
+/// 保留前 `first` 与后 `last` 个字符，若字符串不够长则全部遮盖
+fn keep(v: &str, first: usize, last: usize) -> String {
+  let len = v.chars().count();
+  if len <= first + last {
+    return MASK_CHAR.to_string().repeat(len);
+  }
+
+  v.chars().enumerate().map(|(i, c)| if i < first || i >= len - last { c } else { MASK_CHAR }).collect()
+}
+
+fn mask_email(v: &str) -> String {
+  match v.split_once('@') {
+    Some((local, domain)) => format!("{}@{}", keep(local, 1, 0), domain),
+    None => keep(v, 1, 0),
+  }
+}
+
+fn mask_name(v: &str) -> String {
+  match v.chars().count() {
+    0 | 1 => keep(v, 0, 0),
+    2 => keep(v, 1, 0),
+    _ => keep(v, 1, 1),
+  }
+}
+
+/// 可被脱敏输出的值
+pub trait MaskedValue {
+  fn serialize_masked<S>(&self, strategy: MaskStrategy, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer;
+
+  fn fmt_masked(&self, strategy: MaskStrategy, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+
+impl MaskedValue for str {
+  fn serialize_masked<S>(&self, strategy: MaskStrategy, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    if is_unmasked() {
+      serializer.serialize_str(self)
+    } else {
+      serializer.serialize_str(&strategy.mask(self))
+    }
+  }
+
+  fn fmt_masked(&self, strategy: MaskStrategy, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if is_unmasked() {
+      fmt::Debug::fmt(self, f)
+    } else {
+      fmt::Debug::fmt(&strategy.mask(self), f)
+    }
+  }
+}
+
+impl MaskedValue for String {
+  fn serialize_masked<S>(&self, strategy: MaskStrategy, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    self.as_str().serialize_masked(strategy, serializer)
+  }
+
+  fn fmt_masked(&self, strategy: MaskStrategy, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self.as_str().fmt_masked(strategy, f)
+  }
+}
+
+impl MaskedValue for Cow<'_, str> {
+  fn serialize_masked<S>(&self, strategy: MaskStrategy, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    self.as_ref().serialize_masked(strategy, serializer)
+  }
+
+  fn fmt_masked(&self, strategy: MaskStrategy, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self.as_ref().fmt_masked(strategy, f)
+  }
+}
+
+impl<T: MaskedValue + ?Sized> MaskedValue for &T {
+  fn serialize_masked<S>(&self, strategy: MaskStrategy, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    (**self).serialize_masked(strategy, serializer)
+  }
+
+  fn fmt_masked(&self, strategy: MaskStrategy, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    (**self).fmt_masked(strategy, f)
+  }
+}
+
+impl<T: MaskedValue> MaskedValue for Option<T> {
+  fn serialize_masked<S>(&self, strategy: MaskStrategy, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    match self {
+      Some(v) => serializer.serialize_some(&MaskedField::new(v, strategy)),
+      None => serializer.serialize_none(),
+    }
+  }
+
+  fn fmt_masked(&self, strategy: MaskStrategy, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Some(v) => f.debug_tuple("Some").field(&MaskedField::new(v, strategy)).finish(),
+      None => f.write_str("None"),
+    }
+  }
+}
+
+impl<T: MaskedValue> MaskedValue for Vec<T> {
+  fn serialize_masked<S>(&self, strategy: MaskStrategy, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    serializer.collect_seq(self.iter().map(|v| MaskedField::new(v, strategy)))
+  }
+
+  fn fmt_masked(&self, strategy: MaskStrategy, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_list().entries(self.iter().map(|v| MaskedField::new(v, strategy))).finish()
+  }
+}
+
+/// 对字段值按策略脱敏后输出，由 `#[derive(Masked)]` 生成的代码使用
+pub struct MaskedField<'a, T: ?Sized> {
+  value: &'a T,
+  strategy: MaskStrategy,
+}
+
+impl<'a, T: MaskedValue + ?Sized> MaskedField<'a, T> {
+  pub fn new(value: &'a T, strategy: MaskStrategy) -> Self {
+    Self { value, strategy }
+  }
+}
+
+impl<T: MaskedValue + ?Sized> Serialize for MaskedField<'_, T> {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    self.value.serialize_masked(self.strategy, serializer)
+  }
+}
+
+impl<T: MaskedValue + ?Sized> fmt::Debug for MaskedField<'_, T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self.value.fmt_masked(self.strategy, f)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::model::sensitive::Masked;
+
+  #[derive(Masked)]
+  struct Customer {
+    id: i64,
+    #[masked(name)]
+    name: String,
+    #[masked(phone)]
+    phone: Option<String>,
+    #[masked(email)]
+    email: Option<String>,
+    #[masked(id_card)]
+    id_card: String,
+    #[masked(bank_card)]
+    bank_card: String,
+    #[masked(address)]
+    address: String,
+    #[masked(keep(first = 2, last = 1))]
+    nickname: String,
+    #[masked(redact)]
+    password: String,
+    #[masked(hash)]
+    open_id: String,
+  }
+
+  fn customer() -> Customer {
+    Customer {
+      id: 1,
+      name: "欧阳锋".to_string(),
+      phone: Some("13883712048".to_string()),
+      email: None,
+      id_card: "110101199003071234".to_string(),
+      bank_card: "6222021234567890123".to_string(),
+      address: "北京市海淀区中关村大街1号".to_string(),
+      nickname: "bajing".to_string(),
+      password: "2024.Ultimate".to_string(),
+      open_id: "abc".to_string(),
+    }
+  }
+
+  #[test]
+  fn test_mask_strategy() {
+    assert_eq!(MaskStrategy::Phone.mask("123"), "***");
+    assert_eq!(MaskStrategy::Email.mask("a@example.com"), "*@example.com");
+    assert_eq!(MaskStrategy::IdCard.mask("110101199003071234"), "110***********1234");
+    assert_eq!(MaskStrategy::BankCard.mask("6222021234567890123"), "622202*********0123");
+    assert_eq!(MaskStrategy::Name.mask("张"), "*");
+    assert_eq!(MaskStrategy::Name.mask("欧阳锋"), "欧*锋");
+    assert_eq!(MaskStrategy::Address.mask("北京市海淀区中关村"), "北京市海淀区***");
+    assert_eq!(MaskStrategy::Redact.mask("abc"), "******");
+  }
+
+  #[test]
+  fn test_mask_hash() {
+    let keyring = Keyring::new("1", b"0123456789ABCDEF0123456789ABCDEF").unwrap();
+    assert_eq!(hash("abc", Some(&keyring)), REDACTED);
+    assert_eq!(hash("abc", None), REDACTED);
+
+    let keyring = keyring.with_blind_index_key(b"hmac-key".to_vec());
+    let hashed = hash("abc", Some(&keyring));
+    assert_eq!(hashed, keyring.blind_index(b"abc").unwrap());
+    assert_ne!(hashed, crate::digest::sha256_string(b"abc"));
+    assert_ne!(hashed, hash("abd", Some(&keyring)));
+  }
+
+  #[test]
+  fn test_derive_masked() {
+    let c = customer();
+    let v = serde_json::to_value(&c).unwrap();
+    assert_eq!(v["id"], 1);
+    assert_eq!(v["name"], "欧*锋");
+    assert_eq!(v["phone"], "138****2048");
+    assert!(v["email"].is_null());
+    assert_eq!(v["nickname"], "ba***g");
+    assert_eq!(v["password"], "******");
+    assert_eq!(v["open_id"], MaskStrategy::Hash.mask("abc"));
+
+    let text = format!("{:?}", c);
+    assert!(text.contains(r#"phone: Some("138****2048")"#));
+    assert!(!text.contains("2024.Ultimate"));
+
+    let v = with_unmasked(|| serde_json::to_value(&c).unwrap());
+    assert_eq!(v["phone"], "13883712048");
+    assert_eq!(v["password"], "2024.Ultimate");
+    assert!(!is_unmasked());
+  }
+
+  #[derive(Masked, serde::Deserialize)]
+  #[serde(rename = "Account", rename_all = "camelCase", deny_unknown_fields)]
+  struct Account<T> {
+    #[serde(rename = "uid")]
+    user_id: i64,
+    #[masked(phone)]
+    phone_number: String,
+    #[serde(skip)]
+    secret: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    remark: Option<String>,
+    extra: T,
+  }
+
+  #[test]
+  fn test_derive_masked_serde_attrs() {
+    let a = Account {
+      user_id: 1,
+      phone_number: "13883712048".to_string(),
+      secret: "s3cret".to_string(),
+      remark: None,
+      extra: vec![1, 2],
+    };
+    let text = serde_json::to_string(&a).unwrap();
+    assert_eq!(text, r#"{"uid":1,"phoneNumber":"138****2048","extra":[1,2]}"#);
+    let debug = format!("{:?}", a);
+    assert!(!debug.contains("s3cret"), "{}", debug);
+    assert!(debug.ends_with(".. }"), "{}", debug);
+
+    let a = Account { remark: Some("r".to_string()), ..a };
+    assert_eq!(serde_json::to_value(&a).unwrap()["remark"], "r");
+    let de: Account<Vec<i32>> = serde_json::from_str(r#"{"uid":2,"phoneNumber":"1","extra":[]}"#).unwrap();
+    assert_eq!((de.user_id, de.secret.as_str()), (2, ""));
+  }
+}
@@ -1,7 +1,10 @@
+mod masked;
 mod sensitive_string;
 mod uri_string;
 
+pub use masked::*;
 pub use sensitive_string::SensitiveString;
+pub use ultimate_common_macros::Masked;
 pub use uri_string::UriString;
 
 pub trait ToSensitive {
@@ -11,3 +14,8 @@ pub trait ToSensitive {
 pub trait AsUnderlying {
   fn as_underlying(&self) -> &str;
 }
+
+#[doc(hidden)]
+pub mod __private {
+  pub use serde;
+}
@@ -0,0 +1,234 @@
+use std::{collections::HashSet, path::Path};
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::Deserialize;
+
+use crate::{Error, Result};
+
+/// 内置的中国法定节假日及调休数据，以国务院办公厅每年发布的放假安排为准
+const CN_HOLIDAYS: &str = include_str!("holidays_cn.json");
+
+/// 工作日历，用于工作日计算。实现者只需提供 [HolidayCalendar::is_workday]。
+///
+/// 日历中必须存在工作日，否则查找工作日的方法将不会返回。
+pub trait HolidayCalendar {
+  /// 是否为工作日
+  fn is_workday(&self, date: NaiveDate) -> bool;
+
+  /// 是否为休息日
+  fn is_holiday(&self, date: NaiveDate) -> bool {
+    !self.is_workday(date)
+  }
+
+  /// `date` 之后（不含当天）的第一个工作日
+  fn next_workday(&self, date: NaiveDate) -> NaiveDate {
+    let mut d = date + Duration::days(1);
+    while !self.is_workday(d) {
+      d += Duration::days(1);
+    }
+    d
+  }
+
+  /// `date` 之前（不含当天）的最后一个工作日
+  fn prev_workday(&self, date: NaiveDate) -> NaiveDate {
+    let mut d = date - Duration::days(1);
+    while !self.is_workday(d) {
+      d -= Duration::days(1);
+    }
+    d
+  }
+
+  /// 从 `date` 开始加上 `n` 个工作日，`n` 为负数时向前计算。`n` 为 0 时返回 `date` 本身。
+  fn add_workdays(&self, date: NaiveDate, n: i64) -> NaiveDate {
+    let mut d = date;
+    for _ in 0..n.unsigned_abs() {
+      d = if n > 0 { self.next_workday(d) } else { self.prev_workday(d) };
+    }
+    d
+  }
+
+  /// `[start, end)` 区间内的工作日数，`end` 早于 `start` 时返回负数
+  fn workdays_between(&self, start: NaiveDate, end: NaiveDate) -> i64 {
+    if end < start {
+      return -self.workdays_between(end, start);
+    }
+    start.iter_days().take_while(|d| *d < end).filter(|d| self.is_workday(*d)).count() as i64
+  }
+}
+
+/// 只以周六、周日为休息日的日历
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WeekendCalendar;
+
+impl HolidayCalendar for WeekendCalendar {
+  fn is_workday(&self, date: NaiveDate) -> bool {
+    !matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+  }
+}
+
+impl<F> HolidayCalendar for F
+where
+  F: Fn(NaiveDate) -> bool,
+{
+  fn is_workday(&self, date: NaiveDate) -> bool {
+    self(date)
+  }
+}
+
+/// 工作日历：周一至周五为工作日，再叠加节假日、调休上班日和公司自定义的停工日。
+///
+/// 判断优先级为：停工日 > 调休上班日 > 节假日 > 周末。数据未覆盖的年份只按周末计算。
+///
+/// 日历数据为 JSON 格式，日期可写为 `yyyy-MM-dd` 或闭区间 `yyyy-MM-dd~yyyy-MM-dd`：
+///
+/// ```json
+/// {
+///   "holidays": ["2025-01-01", "2025-01-28~2025-02-04"],
+///   "workdays": ["2025-01-26", "2025-02-08"],
+///   "closures": ["2025-12-31"]
+/// }
+/// ```
+///
+/// # Examples
+///
+/// ```rust
+/// use ultimate_common::time::{HolidayCalendar, NaiveDate, WorkCalendar};
+///
+/// let date = |s: &str| s.parse::<NaiveDate>().unwrap();
+/// let cal = WorkCalendar::china().with_closure(date("2025-02-05"));
+///
+/// assert!(cal.is_holiday(date("2025-01-28"))); // 春节
+/// assert!(cal.is_workday(date("2025-01-26"))); // 周日调休上班
+/// assert!(cal.is_holiday(date("2025-02-05"))); // 公司停工
+/// assert_eq!(cal.next_workday(date("2025-01-27")), date("2025-02-06"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct WorkCalendar {
+  holidays: HashSet<NaiveDate>,
+  workdays: HashSet<NaiveDate>,
+  closures: HashSet<NaiveDate>,
+}
+
+#[derive(Deserialize)]
+struct WorkCalendarData {
+  #[serde(default)]
+  holidays: Vec<String>,
+  #[serde(default)]
+  workdays: Vec<String>,
+  #[serde(default)]
+  closures: Vec<String>,
+}
+
+impl WorkCalendar {
+  /// 空日历，只以周末为休息日
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// 使用内置数据的中国法定节假日日历
+  pub fn china() -> Self {
+    Self::from_json(CN_HOLIDAYS).expect("invalid embedded holiday data")
+  }
+
+  pub fn from_json(s: &str) -> Result<Self> {
+    let data: WorkCalendarData = serde_json::from_str(s)?;
+    Ok(Self {
+      holidays: parse_dates(&data.holidays)?,
+      workdays: parse_dates(&data.workdays)?,
+      closures: parse_dates(&data.closures)?,
+    })
+  }
+
+  pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+    let s = std::fs::read_to_string(path)?;
+    Self::from_json(&s)
+  }
+
+  /// 合并另一日历的数据，如：在内置数据上追加新一年的放假安排
+  pub fn merge(mut self, other: WorkCalendar) -> Self {
+    self.holidays.extend(other.holidays);
+    self.workdays.extend(other.workdays);
+    self.closures.extend(other.closures);
+    self
+  }
+
+  /// 追加公司停工日，停工日即使是调休上班日也不再是工作日
+  pub fn with_closure(mut self, date: NaiveDate) -> Self {
+    self.add_closure(date);
+    self
+  }
+
+  /// 追加 `[start, end]` 闭区间内的所有日期为停工日
+  pub fn with_closures(mut self, start: NaiveDate, end: NaiveDate) -> Self {
+    self.closures.extend(start.iter_days().take_while(|d| *d <= end));
+    self
+  }
+
+  pub fn add_closure(&mut self, date: NaiveDate) {
+    self.closures.insert(date);
+  }
+}
+
+impl HolidayCalendar for WorkCalendar {
+  fn is_workday(&self, date: NaiveDate) -> bool {
+    if self.closures.contains(&date) {
+      false
+    } else if self.workdays.contains(&date) {
+      true
+    } else if self.holidays.contains(&date) {
+      false
+    } else {
+      WeekendCalendar.is_workday(date)
+    }
+  }
+}
+
+fn parse_dates(items: &[String]) -> Result<HashSet<NaiveDate>> {
+  let mut dates = HashSet::new();
+  for item in items {
+    let (start, end) = item.split_once('~').unwrap_or((item, item));
+    let start = parse_date(start)?;
+    let end = parse_date(end)?;
+    if end < start {
+      return Err(Error::DateFailParse(item.clone()));
+    }
+    dates.extend(start.iter_days().take_while(|d| *d <= end));
+  }
+  Ok(dates)
+}
+
+fn parse_date(s: &str) -> Result<NaiveDate> {
+  NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").map_err(|_| Error::DateFailParse(s.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn date(s: &str) -> NaiveDate {
+    parse_date(s).unwrap()
+  }
+
+  #[test]
+  fn test_work_calendar_china() {
+    let cal = WorkCalendar::china();
+    // 2024 国庆：10-01 ~ 10-07 放假，09-29（周日）、10-12（周六）上班
+    assert!(cal.is_workday(date("2024-09-29")));
+    assert!(cal.is_holiday(date("2024-10-07")));
+    assert!(cal.is_workday(date("2024-10-12")));
+    assert_eq!(cal.add_workdays(date("2024-09-30"), 1), date("2024-10-08"));
+    assert_eq!(cal.add_workdays(date("2024-10-08"), -1), date("2024-09-30"));
+    assert_eq!(cal.workdays_between(date("2024-10-01"), date("2024-11-01")), 19);
+
+    let cal = cal.with_closures(date("2024-10-08"), date("2024-10-09"));
+    assert_eq!(cal.next_workday(date("2024-09-30")), date("2024-10-10"));
+  }
+
+  #[test]
+  fn test_work_calendar_from_json() {
+    let cal = WorkCalendar::from_json(r#"{"holidays":["2030-01-01~2030-01-02"],"workdays":["2030-01-05"]}"#).unwrap();
+    assert!(cal.is_holiday(date("2030-01-02")));
+    assert!(cal.is_workday(date("2030-01-05")));
+    assert!(WorkCalendar::from_json(r#"{"holidays":["2030-01-02~2030-01-01"]}"#).is_err());
+  }
+}
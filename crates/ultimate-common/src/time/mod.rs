@@ -0,0 +1,136 @@
+//! 时间工具。
+//!
+//! 本地时区默认为 `Asia/Shanghai`，可在程序启动时通过 [init_local_zone] 设置为任意 IANA 时区。
+use std::sync::OnceLock;
+
+pub use chrono::{
+  DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Timelike,
+  Utc, Weekday,
+};
+pub use chrono_tz::Tz;
+
+use crate::{Error, Result};
+
+mod calendar;
+mod parse;
+mod period;
+pub mod serde_fmt;
+
+pub use calendar::*;
+pub use parse::*;
+pub use period::*;
+
+pub type OffsetDateTime = DateTime<FixedOffset>;
+pub type UtcDateTime = DateTime<Utc>;
+pub type ZonedDateTime = DateTime<Tz>;
+
+/// 未设置本地时区时使用的默认时区
+pub const DEFAULT_ZONE: Tz = Tz::Asia__Shanghai;
+
+static LOCAL_ZONE: OnceLock<Tz> = OnceLock::new();
+
+/// 设置本地时区，应在程序启动时调用一次。
+pub fn init_local_zone(tz: Tz) -> Result<()> {
+  LOCAL_ZONE.set(tz).map_err(|_| Error::LocalZoneAlreadyInitialized)
+}
+
+/// 本地时区
+pub fn local_zone() -> Tz {
+  *LOCAL_ZONE.get_or_init(|| DEFAULT_ZONE)
+}
+
+/// 解析 IANA 时区名，如：`Asia/Shanghai`、`Europe/London`
+pub fn parse_zone(name: &str) -> Result<Tz> {
+  name.parse::<Tz>().map_err(|_| Error::InvalidTimeZone(name.to_string()))
+}
+
+/// 本地时区当前的 UTC 偏移（夏令时期间会变化）
+pub fn local_offset() -> FixedOffset {
+  local_zone().offset_from_utc_datetime(&now_utc().naive_utc()).fix()
+}
+
+#[inline]
+pub fn now_utc() -> UtcDateTime {
+  Utc::now()
+}
+
+#[inline]
+pub fn now_local() -> OffsetDateTime {
+  now_zoned().fixed_offset()
+}
+
+/// 本地时区的当前时间
+#[inline]
+pub fn now_zoned() -> ZonedDateTime {
+  now_utc().with_timezone(&local_zone())
+}
+
+#[inline]
+pub fn now() -> UtcDateTime {
+  Utc::now()
+}
+
+pub fn now_epoch_millis() -> i64 {
+  let now = now_utc();
+  now.timestamp_millis()
+}
+
+#[inline]
+pub fn now_epoch_seconds() -> i64 {
+  now_utc().timestamp()
+}
+
+pub fn format_time(time: UtcDateTime) -> Result<String> {
+  Ok(time.to_rfc3339())
+}
+
+pub fn now_utc_plus_sec_str(sec: u64) -> Result<String> {
+  let new_time = now_utc() + Duration::seconds(sec as i64);
+  format_time(new_time)
+}
+
+/// 从 Unix 纪元毫秒数构造时间
+pub fn from_milliseconds(milliseconds: i64) -> Result<UtcDateTime> {
+  DateTime::from_timestamp_millis(milliseconds).ok_or_else(|| Error::DateFailParse(milliseconds.to_string()))
+}
+
+/// 从 Unix 纪元秒数构造时间
+pub fn from_seconds(seconds: i64) -> Result<UtcDateTime> {
+  DateTime::from_timestamp(seconds, 0).ok_or_else(|| Error::DateFailParse(seconds.to_string()))
+}
+
+/// 解析时间，支持的格式见 [parse_datetime]。不带时区的时间按本地时区解析。
+pub fn parse_utc(moment: &str) -> Result<UtcDateTime> {
+  parse_datetime(moment, &local_zone())
+}
+
+#[cfg(feature = "prost")]
+pub fn to_prost_timestamp(d: &UtcDateTime) -> prost_types::Timestamp {
+  prost_types::Timestamp { seconds: d.timestamp(), nanos: d.timestamp_subsec_nanos() as i32 }
+}
+
+#[cfg(feature = "prost")]
+pub fn from_prost_timestamp(t: &prost_types::Timestamp) -> Option<UtcDateTime> {
+  DateTime::from_timestamp(t.seconds, t.nanos as u32)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_convert_std() {
+    let now_utc = now_utc();
+    println!("now is: {}", now_utc);
+
+    let now_local = now();
+    println!("now is {}", now_local);
+  }
+
+  #[test]
+  fn test_from_milliseconds() {
+    assert_eq!(from_milliseconds(0).unwrap(), DateTime::UNIX_EPOCH);
+    assert_eq!(from_milliseconds(1_000).unwrap().to_rfc3339(), "1970-01-01T00:00:01+00:00");
+    assert!(from_milliseconds(i64::MAX).is_err());
+  }
+}
@@ -0,0 +1,58 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone};
+
+use super::{from_milliseconds, from_seconds, UtcDateTime};
+use crate::{Error, Result};
+
+/// 不带时区的时间格式，按指定时区解析
+const NAIVE_DATETIME_FORMATS: [&str; 3] = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"];
+
+/// 小于此绝对值的纯数字按秒解析，否则按毫秒解析（约为 5138 年的秒数、1973 年的毫秒数）
+const EPOCH_SECONDS_LIMIT: i64 = 100_000_000_000;
+
+/// 解析常见格式的时间：
+///
+/// - RFC 3339：`2024-06-01T08:30:00+08:00`、`2024-06-01T00:30:00Z`
+/// - `yyyy-MM-dd HH:mm:ss`（可带小数秒，也可使用 `T` 分隔），按 `tz` 解析
+/// - `yyyy-MM-dd`，为 `tz` 当天的零点
+/// - Unix 纪元秒数或毫秒数，如：`1717201800`、`1717201800000`
+///
+/// # Examples
+///
+/// ```rust
+/// use ultimate_common::time::{parse_datetime, Tz};
+///
+/// let a = parse_datetime("2024-06-01T08:30:00+08:00", &Tz::UTC).unwrap();
+/// let b = parse_datetime("2024-06-01 08:30:00", &Tz::Asia__Shanghai).unwrap();
+/// let c = parse_datetime("1717201800000", &Tz::UTC).unwrap();
+/// assert_eq!(a, b);
+/// assert_eq!(a, c);
+/// assert!(parse_datetime("2024-13-01", &Tz::UTC).is_err());
+/// ```
+pub fn parse_datetime<Tz: TimeZone>(s: &str, tz: &Tz) -> Result<UtcDateTime> {
+  let s = s.trim();
+
+  if let Ok(d) = DateTime::parse_from_rfc3339(s) {
+    return Ok(d.to_utc());
+  }
+
+  if let Ok(n) = s.parse::<i64>() {
+    return if n.abs() < EPOCH_SECONDS_LIMIT { from_seconds(n) } else { from_milliseconds(n) };
+  }
+
+  for fmt in NAIVE_DATETIME_FORMATS {
+    if let Ok(d) = NaiveDateTime::parse_from_str(s, fmt) {
+      return from_naive(d, tz, s);
+    }
+  }
+
+  if let Ok(d) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+    return from_naive(d.and_time(Default::default()), tz, s);
+  }
+
+  Err(Error::DateFailParse(s.to_string()))
+}
+
+/// 将不带时区的时间解释为 `tz` 时区的时间。夏令时切换导致时间重复时取较早者，不存在时返回错误。
+fn from_naive<Tz: TimeZone>(d: NaiveDateTime, tz: &Tz, s: &str) -> Result<UtcDateTime> {
+  tz.from_local_datetime(&d).earliest().map(|d| d.to_utc()).ok_or_else(|| Error::DateFailParse(s.to_string()))
+}
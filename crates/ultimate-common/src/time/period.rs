@@ -0,0 +1,99 @@
+//! 按时区计算日、周、月、季度的起止时间。
+//!
+//! 所有函数都使用输入时间所在的时区计算边界，可先通过 `with_timezone` 转换到目标时区。
+//! 结束时间为下一周期开始前的最后 1 纳秒。
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveTime, TimeZone};
+
+/// 在时区 `tz` 中 `date` 当天的开始时间。
+///
+/// 通常为零点；若零点因夏令时切换不存在，则取当天最早的有效时间。
+pub fn start_of_date<Tz: TimeZone>(tz: &Tz, date: NaiveDate) -> DateTime<Tz> {
+  let midnight = date.and_time(NaiveTime::MIN);
+  (0..=180)
+    .find_map(|minutes| tz.from_local_datetime(&(midnight + Duration::minutes(minutes))).earliest())
+    .expect("no valid local time within 3 hours after midnight")
+}
+
+pub fn start_of_day<Tz: TimeZone>(dt: &DateTime<Tz>) -> DateTime<Tz> {
+  start_of_date(&dt.timezone(), dt.date_naive())
+}
+
+pub fn end_of_day<Tz: TimeZone>(dt: &DateTime<Tz>) -> DateTime<Tz> {
+  end_before(&dt.timezone(), dt.date_naive() + Duration::days(1))
+}
+
+/// 一周的开始，周一为一周的第一天
+pub fn start_of_week<Tz: TimeZone>(dt: &DateTime<Tz>) -> DateTime<Tz> {
+  start_of_date(&dt.timezone(), first_day_of_week(dt.date_naive()))
+}
+
+pub fn end_of_week<Tz: TimeZone>(dt: &DateTime<Tz>) -> DateTime<Tz> {
+  end_before(&dt.timezone(), first_day_of_week(dt.date_naive()) + Duration::weeks(1))
+}
+
+pub fn start_of_month<Tz: TimeZone>(dt: &DateTime<Tz>) -> DateTime<Tz> {
+  start_of_date(&dt.timezone(), first_day_of_month(dt.date_naive()))
+}
+
+pub fn end_of_month<Tz: TimeZone>(dt: &DateTime<Tz>) -> DateTime<Tz> {
+  end_before(&dt.timezone(), first_day_of_month(dt.date_naive()) + Months::new(1))
+}
+
+pub fn start_of_quarter<Tz: TimeZone>(dt: &DateTime<Tz>) -> DateTime<Tz> {
+  start_of_date(&dt.timezone(), first_day_of_quarter(dt.date_naive()))
+}
+
+pub fn end_of_quarter<Tz: TimeZone>(dt: &DateTime<Tz>) -> DateTime<Tz> {
+  end_before(&dt.timezone(), first_day_of_quarter(dt.date_naive()) + Months::new(3))
+}
+
+/// 季度，取值 1 ~ 4
+pub fn quarter_of(date: NaiveDate) -> u32 {
+  date.month0() / 3 + 1
+}
+
+fn end_before<Tz: TimeZone>(tz: &Tz, next_date: NaiveDate) -> DateTime<Tz> {
+  start_of_date(tz, next_date) - Duration::nanoseconds(1)
+}
+
+fn first_day_of_week(date: NaiveDate) -> NaiveDate {
+  date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+fn first_day_of_month(date: NaiveDate) -> NaiveDate {
+  date.with_day(1).unwrap()
+}
+
+fn first_day_of_quarter(date: NaiveDate) -> NaiveDate {
+  NaiveDate::from_ymd_opt(date.year(), (quarter_of(date) - 1) * 3 + 1, 1).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono_tz::Tz;
+
+  use super::*;
+  use crate::time::parse_datetime;
+
+  #[test]
+  fn test_periods_in_zone() {
+    // 北京时间为 2024-08-15 07:00，纽约（夏令时 -04:00）为 2024-08-14 19:00
+    let utc = parse_datetime("2024-08-14T23:00:00Z", &Tz::UTC).unwrap();
+
+    let shanghai = utc.with_timezone(&Tz::Asia__Shanghai);
+    assert_eq!(start_of_day(&shanghai).to_rfc3339(), "2024-08-15T00:00:00+08:00");
+    assert_eq!(end_of_day(&shanghai).to_rfc3339(), "2024-08-15T23:59:59.999999999+08:00");
+    assert_eq!(start_of_week(&shanghai).to_rfc3339(), "2024-08-12T00:00:00+08:00");
+    assert_eq!(start_of_month(&shanghai).to_rfc3339(), "2024-08-01T00:00:00+08:00");
+    assert_eq!(end_of_month(&shanghai).to_rfc3339(), "2024-08-31T23:59:59.999999999+08:00");
+    assert_eq!(start_of_quarter(&shanghai).to_rfc3339(), "2024-07-01T00:00:00+08:00");
+    assert_eq!(end_of_quarter(&shanghai).to_rfc3339(), "2024-09-30T23:59:59.999999999+08:00");
+
+    let new_york = utc.with_timezone(&Tz::America__New_York);
+    assert_eq!(start_of_day(&new_york).to_rfc3339(), "2024-08-14T00:00:00-04:00");
+    // 11 月 3 日结束夏令时，当天有 25 个小时
+    let d =
+      parse_datetime("2024-11-03 12:00:00", &Tz::America__New_York).unwrap().with_timezone(&Tz::America__New_York);
+    assert_eq!(end_of_day(&d) - start_of_day(&d), Duration::hours(25) - Duration::nanoseconds(1));
+  }
+}
@@ -0,0 +1,189 @@
+//! 时间的 serde 适配器，配合 `#[serde(with = "...")]` 使用。
+//!
+//! 反序列化时都接受 [super::parse_datetime] 支持的格式，以及 JSON 数字形式的纪元秒或毫秒数。
+//!
+//! # Examples
+//!
+//! ```rust
+//! use serde::{Deserialize, Serialize};
+//! use ultimate_common::time::{serde_fmt, UtcDateTime};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Order {
+//!   #[serde(with = "serde_fmt::epoch_millis")]
+//!   ctime: UtcDateTime,
+//!   #[serde(with = "serde_fmt::opt_flexible", default)]
+//!   mtime: Option<UtcDateTime>,
+//! }
+//!
+//! let o: Order = serde_json::from_str(r#"{"ctime":"2024-06-01 08:30:00+08:00","mtime":1717201800}"#).unwrap();
+//! assert_eq!(o.mtime, Some(o.ctime));
+//! assert_eq!(serde_json::to_string(&o).unwrap(), r#"{"ctime":1717201800000,"mtime":"2024-06-01T00:30:00+00:00"}"#);
+//! ```
+use core::fmt;
+
+use serde::{de, Deserializer, Serializer};
+
+use super::{local_zone, parse_datetime, UtcDateTime};
+
+const LOCAL_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+struct FlexibleVisitor;
+
+impl de::Visitor<'_> for FlexibleVisitor {
+  type Value = UtcDateTime;
+
+  fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    formatter.write_str("a datetime string or epoch seconds/milliseconds")
+  }
+
+  fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+  where
+    E: de::Error,
+  {
+    parse_datetime(v, &local_zone()).map_err(E::custom)
+  }
+
+  fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+  where
+    E: de::Error,
+  {
+    self.visit_str(&v.to_string())
+  }
+
+  fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+  where
+    E: de::Error,
+  {
+    self.visit_str(&v.to_string())
+  }
+}
+
+struct OptionVisitor;
+
+impl<'de> de::Visitor<'de> for OptionVisitor {
+  type Value = Option<UtcDateTime>;
+
+  fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    formatter.write_str("null, a datetime string or epoch seconds/milliseconds")
+  }
+
+  fn visit_none<E>(self) -> Result<Self::Value, E>
+  where
+    E: de::Error,
+  {
+    Ok(None)
+  }
+
+  fn visit_unit<E>(self) -> Result<Self::Value, E>
+  where
+    E: de::Error,
+  {
+    Ok(None)
+  }
+
+  fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    deserializer.deserialize_any(FlexibleVisitor).map(Some)
+  }
+}
+
+fn deserialize_flexible<'de, D>(deserializer: D) -> Result<UtcDateTime, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  deserializer.deserialize_any(FlexibleVisitor)
+}
+
+fn deserialize_opt_flexible<'de, D>(deserializer: D) -> Result<Option<UtcDateTime>, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  deserializer.deserialize_option(OptionVisitor)
+}
+
+/// 序列化为 RFC 3339 字符串
+pub mod flexible {
+  use super::*;
+
+  pub fn serialize<S: Serializer>(v: &UtcDateTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&v.to_rfc3339())
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<UtcDateTime, D::Error> {
+    deserialize_flexible(deserializer)
+  }
+}
+
+pub mod opt_flexible {
+  use super::*;
+
+  pub fn serialize<S: Serializer>(v: &Option<UtcDateTime>, serializer: S) -> Result<S::Ok, S::Error> {
+    match v {
+      Some(v) => flexible::serialize(v, serializer),
+      None => serializer.serialize_none(),
+    }
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<UtcDateTime>, D::Error> {
+    deserialize_opt_flexible(deserializer)
+  }
+}
+
+/// 序列化为本地时区的 `yyyy-MM-dd HH:mm:ss`
+pub mod local_datetime {
+  use super::*;
+
+  pub fn serialize<S: Serializer>(v: &UtcDateTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&v.with_timezone(&local_zone()).format(LOCAL_DATETIME_FORMAT))
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<UtcDateTime, D::Error> {
+    deserialize_flexible(deserializer)
+  }
+}
+
+pub mod opt_local_datetime {
+  use super::*;
+
+  pub fn serialize<S: Serializer>(v: &Option<UtcDateTime>, serializer: S) -> Result<S::Ok, S::Error> {
+    match v {
+      Some(v) => local_datetime::serialize(v, serializer),
+      None => serializer.serialize_none(),
+    }
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<UtcDateTime>, D::Error> {
+    deserialize_opt_flexible(deserializer)
+  }
+}
+
+/// 序列化为 Unix 纪元毫秒数
+pub mod epoch_millis {
+  use super::*;
+
+  pub fn serialize<S: Serializer>(v: &UtcDateTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_i64(v.timestamp_millis())
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<UtcDateTime, D::Error> {
+    deserialize_flexible(deserializer)
+  }
+}
+
+pub mod opt_epoch_millis {
+  use super::*;
+
+  pub fn serialize<S: Serializer>(v: &Option<UtcDateTime>, serializer: S) -> Result<S::Ok, S::Error> {
+    match v {
+      Some(v) => epoch_millis::serialize(v, serializer),
+      None => serializer.serialize_none(),
+    }
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<UtcDateTime>, D::Error> {
+    deserialize_opt_flexible(deserializer)
+  }
+}
@@ -0,0 +1,72 @@
+-- ACS (Access Control System) 基于权限的访问控制
+create schema if not exists acs;
+--
+-- Privilege
+create table if not exists acs.privilege
+(
+    id          bigserial    not null,
+    code        varchar(255) not null
+        constraint privilege_uk_code unique,
+    description text,
+    cid         bigint       not null,
+    ctime       timestamptz  not null,
+    mid         bigint,
+    mtime       timestamptz,
+    constraint privilege_pk primary key (id)
+);
+--
+-- Role
+create table if not exists acs.role
+(
+    id          bigserial   not null,
+    name        varchar(50) not null
+        constraint role_uk_name unique,
+    description text,
+    status      int         not null default 100,
+    cid         bigint      not null,
+    ctime       timestamptz not null,
+    mid         bigint,
+    mtime       timestamptz,
+    constraint role_pk primary key (id)
+);
+--
+-- Policy：为角色授予（effect = 1）或拒绝（effect = 2）匹配 pattern 的权限，pattern 支持 `*` 通配符
+create table if not exists acs.policy
+(
+    id          bigserial    not null,
+    role_id     bigint       not null,
+    pattern     varchar(255) not null,
+    effect      int          not null,
+    description text,
+    cid         bigint       not null,
+    ctime       timestamptz  not null,
+    mid         bigint,
+    mtime       timestamptz,
+    constraint policy_pk primary key (id),
+    constraint policy_fk_role foreign key (role_id) references acs.role (id) on delete cascade
+);
+create index if not exists policy_idx_role_id on acs.policy (role_id);
+--
+-- Role Privilege Relation
+create table if not exists acs.role_privilege
+(
+    role_id      bigint      not null,
+    privilege_id bigint      not null,
+    cid          bigint      not null,
+    ctime        timestamptz not null,
+    constraint role_privilege_pk primary key (role_id, privilege_id),
+    constraint role_privilege_fk_role foreign key (role_id) references acs.role (id) on delete cascade,
+    constraint role_privilege_fk_privilege foreign key (privilege_id) references acs.privilege (id) on delete cascade
+);
+--
+-- User Role Relation
+create table if not exists acs.user_role
+(
+    user_id bigint      not null,
+    role_id bigint      not null,
+    cid     bigint      not null,
+    ctime   timestamptz not null,
+    constraint user_role_pk primary key (user_id, role_id),
+    constraint user_role_fk_role foreign key (role_id) references acs.role (id) on delete cascade
+);
+create index if not exists user_role_idx_role_id on acs.user_role (role_id);
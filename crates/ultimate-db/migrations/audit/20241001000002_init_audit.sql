@@ -0,0 +1,20 @@
+-- 审计记录，见 `ultimate_db::audit`
+create schema if not exists audit;
+--
+-- Audit Log
+create table if not exists audit.audit_log
+(
+    id           bigserial    not null,
+    table_schema varchar(255) not null,
+    table_name   varchar(255) not null,
+    entity_id    varchar(255) not null,
+    operation    varchar(20)  not null,
+    actor_id     bigint       not null,
+    tenant_id    bigint,
+    request_id   varchar(255),
+    before_data  jsonb,
+    after_data   jsonb,
+    ctime        timestamptz  not null default now(),
+    constraint audit_log_pk primary key (id)
+);
+create index if not exists audit_log_idx_entity on audit.audit_log (table_schema, table_name, entity_id);
@@ -0,0 +1,38 @@
+-- 后台任务队列，见 `ultimate_db::job`
+create schema if not exists job;
+--
+-- Job
+create table if not exists job.job
+(
+    id           bigserial    not null,
+    queue        varchar(64)  not null default 'default',
+    job_type     varchar(255) not null,
+    payload      jsonb        not null,
+    -- 100: 待执行, 200: 执行中, 300: 已完成, 400: 死信
+    status       int          not null default 100,
+    attempts     int          not null default 0,
+    max_attempts int          not null default 10,
+    unique_key   varchar(255),
+    run_at       timestamptz  not null default now(),
+    locked_by    varchar(255),
+    locked_until timestamptz,
+    last_error   text,
+    tenant_id    bigint,
+    request_id   varchar(255),
+    cid          bigint       not null,
+    ctime        timestamptz  not null default now(),
+    finished_at  timestamptz,
+    constraint job_pk primary key (id)
+);
+create index if not exists job_idx_pending on job.job (queue, run_at) where status = 100;
+create index if not exists job_idx_running on job.job (locked_until) where status = 200;
+create unique index if not exists job_uidx_unique_key on job.job (job_type, unique_key) where status in (100, 200);
+--
+-- Cron Job 的下次执行时间，多个实例通过行锁保证每次只入队一次
+create table if not exists job.job_cron
+(
+    name        varchar(255) not null,
+    next_run_at timestamptz  not null,
+    mtime       timestamptz  not null default now(),
+    constraint job_cron_pk primary key (name)
+);
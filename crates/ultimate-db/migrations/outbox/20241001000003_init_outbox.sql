@@ -0,0 +1,23 @@
+-- 事务性 Outbox，见 `ultimate_db::outbox`
+create schema if not exists outbox;
+--
+-- Outbox Event
+create table if not exists outbox.outbox_event
+(
+    id              bigserial    not null,
+    topic           varchar(255) not null,
+    event_key       varchar(255),
+    payload         jsonb        not null,
+    tenant_id       bigint,
+    request_id      varchar(255),
+    -- 100: 待投递, 200: 已投递, 300: 死信
+    status          int          not null default 100,
+    attempts        int          not null default 0,
+    next_attempt_at timestamptz  not null default now(),
+    last_error      text,
+    cid             bigint       not null,
+    ctime           timestamptz  not null default now(),
+    dispatched_at   timestamptz,
+    constraint outbox_event_pk primary key (id)
+);
+create index if not exists outbox_event_idx_pending on outbox.outbox_event (next_attempt_at, id) where status = 100;
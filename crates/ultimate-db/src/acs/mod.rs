@@ -1,5 +1,53 @@
-//! TO BE IMPLEMENTED
-//!
 //! `acs` Access Control System based on PBAC (Privilege Based Access Control)
 //!
-//! (more to come)
+//! 权限（[Privilege]）绑定到角色（[Role]），角色再绑定到用户；策略（[Policy]）可按通配符为角色批量授予或拒绝权限。
+//! [AcsResolver] 根据 [Ctx] 计算用户的有效权限并缓存，[require_privilege] 可在 Web handler 或 BMC 函数中检查权限：
+//!
+//! ```rust,no_run
+//! use ultimate::ctx::Ctx;
+//! use ultimate_db::{acs, ModelManager};
+//!
+//! async fn delete_user(mm: &ModelManager, id: i64) -> ultimate_db::Result<()> {
+//!   acs::require_privilege(mm.ctx_ref()?, "user:delete").await?;
+//!   // ...
+//!   Ok(())
+//! }
+//!
+//! async fn init(mm: ModelManager) {
+//!   acs::init_acs(acs::AcsResolver::new(acs::PgAcsStore::new(mm))).unwrap();
+//! }
+//! ```
+use std::sync::{LazyLock, OnceLock};
+
+use ultimate::ctx::Ctx;
+
+use crate::migration::{self, Migrator};
+use crate::{Error, Result};
+
+mod model;
+mod resolver;
+mod store;
+
+pub use model::*;
+pub use resolver::*;
+pub use store::*;
+
+/// 创建 ACS 表结构的迁移
+pub static MIGRATOR: LazyLock<Migrator> = LazyLock::new(|| migration::builtin(sqlx::migrate!("./migrations/acs")));
+
+static ACS: OnceLock<AcsResolver> = OnceLock::new();
+
+/// 初始化全局权限解析器，应在程序启动时调用一次。
+pub fn init_acs(resolver: AcsResolver) -> Result<()> {
+  ACS.set(resolver).map_err(|_| Error::AcsAlreadyInitialized)
+}
+
+/// 获取全局权限解析器
+pub fn acs() -> Result<&'static AcsResolver> {
+  ACS.get().ok_or(Error::AcsNotInitialized)
+}
+
+/// 使用全局权限解析器检查权限，无权限时返回 [Error::Forbidden]
+pub async fn require_privilege(ctx: &Ctx, code: &str) -> Result<()> {
+  acs()?.require_privilege(ctx, code).await
+}
@@ -0,0 +1,163 @@
+use modql::field::Fields;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use ultimate_common::time::UtcDateTime;
+
+use crate::{base::DbBmc, DbRowType};
+
+/// ACS 相关表所在的 schema。ACS 表为所有租户共用
+pub const ACS_SCHEMA: &str = "acs";
+
+/// 权限。`code` 建议使用 `<resource>:<action>` 格式，如：`user:read`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, Fields)]
+pub struct Privilege {
+  pub id: i64,
+  pub code: String,
+  pub description: Option<String>,
+  pub cid: i64,
+  pub ctime: UtcDateTime,
+  pub mid: Option<i64>,
+  pub mtime: Option<UtcDateTime>,
+}
+impl DbRowType for Privilege {}
+
+#[derive(Debug, Clone, Deserialize, Fields)]
+pub struct PrivilegeForCreate {
+  pub code: String,
+  pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, Fields)]
+pub struct Role {
+  pub id: i64,
+  pub name: String,
+  pub description: Option<String>,
+  pub status: i32,
+  pub cid: i64,
+  pub ctime: UtcDateTime,
+  pub mid: Option<i64>,
+  pub mtime: Option<UtcDateTime>,
+}
+impl DbRowType for Role {}
+
+#[derive(Debug, Clone, Deserialize, Fields)]
+pub struct RoleForCreate {
+  pub name: String,
+  pub description: Option<String>,
+  pub status: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[repr(i32)]
+pub enum PolicyEffect {
+  Allow = 1,
+  Deny = 2,
+}
+
+impl From<PolicyEffect> for sea_query::Value {
+  fn from(value: PolicyEffect) -> Self {
+    sea_query::Value::Int(Some(value as i32))
+  }
+}
+
+impl sea_query::Nullable for PolicyEffect {
+  fn null() -> sea_query::Value {
+    sea_query::Value::Int(None)
+  }
+}
+
+/// 策略：为角色授予或拒绝匹配 `pattern` 的权限。
+///
+/// `pattern` 支持 `*` 通配符，如：`user:*`、`*:read`、`*`。拒绝优先于授予。
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, Fields)]
+pub struct Policy {
+  pub id: i64,
+  pub role_id: i64,
+  pub pattern: String,
+  pub effect: PolicyEffect,
+  pub description: Option<String>,
+  pub cid: i64,
+  pub ctime: UtcDateTime,
+  pub mid: Option<i64>,
+  pub mtime: Option<UtcDateTime>,
+}
+impl DbRowType for Policy {}
+
+#[derive(Debug, Clone, Deserialize, Fields)]
+pub struct PolicyForCreate {
+  pub role_id: i64,
+  pub pattern: String,
+  pub effect: PolicyEffect,
+  pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, Fields)]
+pub struct RolePrivilege {
+  pub role_id: i64,
+  pub privilege_id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, Fields)]
+pub struct UserRole {
+  pub user_id: i64,
+  pub role_id: i64,
+}
+
+pub struct PrivilegeBmc;
+impl DbBmc for PrivilegeBmc {
+  const SCHEMA: &'static str = ACS_SCHEMA;
+  const TABLE: &'static str = "privilege";
+
+  fn multi_tenant() -> bool {
+    false
+  }
+}
+
+pub struct RoleBmc;
+impl DbBmc for RoleBmc {
+  const SCHEMA: &'static str = ACS_SCHEMA;
+  const TABLE: &'static str = "role";
+
+  fn multi_tenant() -> bool {
+    false
+  }
+}
+
+pub struct PolicyBmc;
+impl DbBmc for PolicyBmc {
+  const SCHEMA: &'static str = ACS_SCHEMA;
+  const TABLE: &'static str = "policy";
+
+  fn multi_tenant() -> bool {
+    false
+  }
+}
+
+pub struct RolePrivilegeBmc;
+impl DbBmc for RolePrivilegeBmc {
+  const SCHEMA: &'static str = ACS_SCHEMA;
+  const TABLE: &'static str = "role_privilege";
+
+  fn multi_tenant() -> bool {
+    false
+  }
+
+  fn has_modification_timestamps() -> bool {
+    false
+  }
+}
+
+pub struct UserRoleBmc;
+impl DbBmc for UserRoleBmc {
+  const SCHEMA: &'static str = ACS_SCHEMA;
+  const TABLE: &'static str = "user_role";
+
+  fn multi_tenant() -> bool {
+    false
+  }
+
+  fn has_modification_timestamps() -> bool {
+    false
+  }
+}
@@ -0,0 +1,216 @@
+use std::{
+  collections::{HashMap, HashSet},
+  sync::{Arc, RwLock},
+  time::{Duration, Instant},
+};
+
+use ultimate::ctx::Ctx;
+
+use super::{AcsStore, PolicyEffect};
+use crate::{Error, Result};
+
+/// 默认的权限缓存时长
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// 用户的有效权限
+#[derive(Debug, Clone, Default)]
+pub struct PrivilegeSet {
+  codes: HashSet<String>,
+  allow_patterns: Vec<String>,
+  deny_patterns: Vec<String>,
+}
+
+impl PrivilegeSet {
+  /// 是否拥有权限。匹配拒绝策略时总是返回 false
+  pub fn contains(&self, code: &str) -> bool {
+    if self.deny_patterns.iter().any(|p| pattern_matches(p, code)) {
+      return false;
+    }
+    self.codes.contains(code) || self.allow_patterns.iter().any(|p| pattern_matches(p, code))
+  }
+
+  /// 通过角色权限绑定直接授予的权限编码
+  pub fn codes(&self) -> &HashSet<String> {
+    &self.codes
+  }
+}
+
+/// 权限解析器，计算 [Ctx] 的有效权限并按用户缓存。
+///
+/// 有效权限来自用户绑定的角色、`Ctx::ext_roles` 携带的角色及 `Ctx::ext_privileges` 携带的权限，
+/// 与用户绑定的角色一样，`Ctx::ext_roles` 中只有启用状态的角色才会授予权限。
+/// 修改用户的角色或权限后，应调用 [AcsResolver::invalidate] 清除缓存。
+pub struct AcsResolver {
+  store: Arc<dyn AcsStore>,
+  ttl: Duration,
+  cache: RwLock<HashMap<CacheKey, CacheEntry>>,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct CacheKey {
+  uid: i64,
+  ext_roles: Vec<i64>,
+  ext_privileges: Vec<i64>,
+}
+
+struct CacheEntry {
+  expires_at: Instant,
+  privileges: Arc<PrivilegeSet>,
+}
+
+impl AcsResolver {
+  pub fn new(store: impl AcsStore + 'static) -> Self {
+    Self { store: Arc::new(store), ttl: DEFAULT_CACHE_TTL, cache: RwLock::default() }
+  }
+
+  /// 设置缓存时长，为 0 时不缓存
+  pub fn with_ttl(mut self, ttl: Duration) -> Self {
+    self.ttl = ttl;
+    self
+  }
+
+  /// 计算有效权限
+  pub async fn resolve(&self, ctx: &Ctx) -> Result<Arc<PrivilegeSet>> {
+    let key =
+      CacheKey { uid: ctx.uid(), ext_roles: ctx.ext_roles().to_vec(), ext_privileges: ctx.ext_privileges().to_vec() };
+    if let Some(entry) = self.cache.read().unwrap().get(&key) {
+      if entry.expires_at > Instant::now() {
+        return Ok(entry.privileges.clone());
+      }
+    }
+
+    let privileges = Arc::new(self.load(&key).await?);
+    if !self.ttl.is_zero() {
+      let entry = CacheEntry { expires_at: Instant::now() + self.ttl, privileges: privileges.clone() };
+      self.cache.write().unwrap().insert(key, entry);
+    }
+    Ok(privileges)
+  }
+
+  pub async fn has_privilege(&self, ctx: &Ctx, code: &str) -> Result<bool> {
+    Ok(self.resolve(ctx).await?.contains(code))
+  }
+
+  /// 检查权限，无权限时返回 [Error::Forbidden]
+  pub async fn require_privilege(&self, ctx: &Ctx, code: &str) -> Result<()> {
+    if self.has_privilege(ctx, code).await? {
+      Ok(())
+    } else {
+      Err(Error::Forbidden { privilege: code.to_string() })
+    }
+  }
+
+  /// 清除用户的权限缓存
+  pub fn invalidate(&self, uid: i64) {
+    self.cache.write().unwrap().retain(|k, _| k.uid != uid);
+  }
+
+  pub fn invalidate_all(&self) {
+    self.cache.write().unwrap().clear();
+  }
+
+  async fn load(&self, key: &CacheKey) -> Result<PrivilegeSet> {
+    let mut role_ids = self.store.find_role_ids_of_user(key.uid).await?;
+    role_ids.extend(self.store.find_enabled_role_ids(&key.ext_roles).await?);
+    role_ids.sort_unstable();
+    role_ids.dedup();
+
+    let mut codes: HashSet<String> = self.store.find_privileges_of_roles(&role_ids).await?.into_iter().collect();
+    codes.extend(self.store.find_privileges_by_ids(&key.ext_privileges).await?);
+
+    let mut set = PrivilegeSet { codes, ..Default::default() };
+    for rule in self.store.find_policies_of_roles(&role_ids).await? {
+      match rule.effect {
+        PolicyEffect::Allow => set.allow_patterns.push(rule.pattern),
+        PolicyEffect::Deny => set.deny_patterns.push(rule.pattern),
+      }
+    }
+    Ok(set)
+  }
+}
+
+/// 匹配权限编码，`*` 匹配任意个字符
+fn pattern_matches(pattern: &str, code: &str) -> bool {
+  let mut parts = pattern.split('*');
+  let first = parts.next().unwrap_or_default();
+  let Some(mut rest) = code.strip_prefix(first) else {
+    return false;
+  };
+
+  let mut parts = parts.peekable();
+  while let Some(part) = parts.next() {
+    if parts.peek().is_none() {
+      return rest.ends_with(part);
+    }
+    match rest.find(part) {
+      Some(i) => rest = &rest[i + part.len()..],
+      None => return false,
+    }
+  }
+  rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+  use ultimate_common::time;
+
+  use super::*;
+  use crate::acs::MemoryAcsStore;
+
+  #[test]
+  fn test_pattern_matches() {
+    assert!(pattern_matches("*", "user:read"));
+    assert!(pattern_matches("user:*", "user:read"));
+    assert!(pattern_matches("*:read", "user:read"));
+    assert!(pattern_matches("user:read", "user:read"));
+    assert!(!pattern_matches("user:read", "user:readonly"));
+    assert!(!pattern_matches("order:*", "user:read"));
+    assert!(!pattern_matches("*:write", "user:read"));
+  }
+
+  #[tokio::test]
+  async fn test_resolver() -> anyhow::Result<()> {
+    let store = MemoryAcsStore::new();
+    store
+      .add_privilege(1, "user:read")
+      .add_privilege(2, "user:delete")
+      .bind_role_privilege(10, 1)
+      .bind_user_role(1000, 10)
+      .add_policy(10, "order:*", PolicyEffect::Allow)
+      .add_policy(10, "order:delete", PolicyEffect::Deny);
+    let resolver = AcsResolver::new(store);
+
+    let now = time::now_utc();
+    let ctx = Ctx::new(1000, now, now + time::Duration::minutes(5));
+    resolver.require_privilege(&ctx, "user:read").await?;
+    resolver.require_privilege(&ctx, "order:create").await?;
+    assert!(matches!(resolver.require_privilege(&ctx, "order:delete").await, Err(Error::Forbidden { .. })));
+    assert!(!resolver.has_privilege(&ctx, "user:delete").await?);
+
+    let guest = Ctx::new(2000, now, now + time::Duration::minutes(5));
+    assert!(!resolver.has_privilege(&guest, "user:read").await?);
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_resolver_skip_disabled_roles() -> anyhow::Result<()> {
+    let store = MemoryAcsStore::new();
+    store
+      .add_privilege(1, "user:read")
+      .add_privilege(2, "order:read")
+      .bind_role_privilege(10, 1)
+      .bind_role_privilege(20, 2)
+      .bind_user_role(1000, 10)
+      .disable_role(10)
+      .disable_role(20);
+    let resolver = AcsResolver::new(store).with_ttl(Duration::ZERO);
+
+    // 用户绑定的角色及 ext_roles 携带的角色被禁用时都不授予权限
+    let now = time::now_utc();
+    let ctx = Ctx::new(1000, now, now + time::Duration::minutes(5)).with_ext_roles(vec![20]);
+    let privileges = resolver.resolve(&ctx).await?;
+    assert!(!privileges.contains("user:read"));
+    assert!(!privileges.contains("order:read"));
+    Ok(())
+  }
+}
@@ -0,0 +1,247 @@
+use std::{
+  collections::{HashMap, HashSet},
+  sync::RwLock,
+};
+
+use async_trait::async_trait;
+use modql::SIden;
+use sea_query::{Expr, Query};
+use sea_query_binder::SqlxBinder;
+
+use super::{PolicyBmc, PolicyEffect, PrivilegeBmc, RoleBmc, RolePrivilegeBmc, UserRoleBmc};
+use crate::{
+  base::{CommonIden, DbBmc},
+  store::DbQueryBuilder,
+  ModelManager, Result,
+};
+
+/// 启用状态的角色，只有启用的角色才会授予权限
+pub const ROLE_STATUS_ENABLED: i32 = 100;
+
+/// 授予或拒绝权限的策略规则
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyRule {
+  pub pattern: String,
+  pub effect: PolicyEffect,
+}
+
+impl PolicyRule {
+  pub fn new(pattern: impl Into<String>, effect: PolicyEffect) -> Self {
+    Self { pattern: pattern.into(), effect }
+  }
+}
+
+/// 访问控制数据的存储后端
+#[async_trait]
+pub trait AcsStore: Send + Sync {
+  /// 用户绑定的（启用状态）角色 ID 列表
+  async fn find_role_ids_of_user(&self, uid: i64) -> Result<Vec<i64>>;
+
+  /// 过滤出 `role_ids` 中存在且为启用状态的角色 ID，用于校验 `Ctx::ext_roles` 携带的角色
+  async fn find_enabled_role_ids(&self, role_ids: &[i64]) -> Result<Vec<i64>>;
+
+  /// 角色绑定的权限编码列表
+  async fn find_privileges_of_roles(&self, role_ids: &[i64]) -> Result<Vec<String>>;
+
+  /// 按权限 ID 查询权限编码列表
+  async fn find_privileges_by_ids(&self, privilege_ids: &[i64]) -> Result<Vec<String>>;
+
+  /// 角色关联的策略规则列表
+  async fn find_policies_of_roles(&self, role_ids: &[i64]) -> Result<Vec<PolicyRule>>;
+}
+
+/// 基于 PostgreSQL 的存储后端，表结构见 [super::MIGRATOR]
+#[derive(Clone)]
+pub struct PgAcsStore {
+  mm: ModelManager,
+}
+
+impl PgAcsStore {
+  pub fn new(mm: ModelManager) -> Self {
+    Self { mm }
+  }
+}
+
+#[async_trait]
+impl AcsStore for PgAcsStore {
+  async fn find_role_ids_of_user(&self, uid: i64) -> Result<Vec<i64>> {
+    let ur = SIden(UserRoleBmc::TABLE);
+    let r = SIden(RoleBmc::TABLE);
+    let mut query = Query::select();
+    query
+      .column((ur, SIden("role_id")))
+      .from(UserRoleBmc::table_ref())
+      .inner_join(RoleBmc::table_ref(), Expr::col((r, CommonIden::Id)).equals((ur, SIden("role_id"))))
+      .and_where(Expr::col((ur, SIden("user_id"))).eq(uid))
+      .and_where(Expr::col((r, SIden("status"))).eq(ROLE_STATUS_ENABLED));
+
+    let (sql, values) = query.build_sqlx(DbQueryBuilder);
+    let rows = self.mm.dbx().fetch_all(sqlx::query_as_with::<_, (i64,), _>(&sql, values)).await?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+  }
+
+  async fn find_enabled_role_ids(&self, role_ids: &[i64]) -> Result<Vec<i64>> {
+    if role_ids.is_empty() {
+      return Ok(vec![]);
+    }
+    let mut query = Query::select();
+    query
+      .column(CommonIden::Id)
+      .from(RoleBmc::table_ref())
+      .and_where(Expr::col(CommonIden::Id).is_in(role_ids.iter().copied()))
+      .and_where(Expr::col(SIden("status")).eq(ROLE_STATUS_ENABLED));
+
+    let (sql, values) = query.build_sqlx(DbQueryBuilder);
+    let rows = self.mm.dbx().fetch_all(sqlx::query_as_with::<_, (i64,), _>(&sql, values)).await?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+  }
+
+  async fn find_privileges_of_roles(&self, role_ids: &[i64]) -> Result<Vec<String>> {
+    if role_ids.is_empty() {
+      return Ok(vec![]);
+    }
+    let rp = SIden(RolePrivilegeBmc::TABLE);
+    let p = SIden(PrivilegeBmc::TABLE);
+    let mut query = Query::select();
+    query
+      .distinct()
+      .column((p, SIden("code")))
+      .from(RolePrivilegeBmc::table_ref())
+      .inner_join(PrivilegeBmc::table_ref(), Expr::col((p, CommonIden::Id)).equals((rp, SIden("privilege_id"))))
+      .and_where(Expr::col((rp, SIden("role_id"))).is_in(role_ids.iter().copied()));
+
+    let (sql, values) = query.build_sqlx(DbQueryBuilder);
+    let rows = self.mm.dbx().fetch_all(sqlx::query_as_with::<_, (String,), _>(&sql, values)).await?;
+    Ok(rows.into_iter().map(|(code,)| code).collect())
+  }
+
+  async fn find_privileges_by_ids(&self, privilege_ids: &[i64]) -> Result<Vec<String>> {
+    if privilege_ids.is_empty() {
+      return Ok(vec![]);
+    }
+    let mut query = Query::select();
+    query
+      .column(SIden("code"))
+      .from(PrivilegeBmc::table_ref())
+      .and_where(Expr::col(CommonIden::Id).is_in(privilege_ids.iter().copied()));
+
+    let (sql, values) = query.build_sqlx(DbQueryBuilder);
+    let rows = self.mm.dbx().fetch_all(sqlx::query_as_with::<_, (String,), _>(&sql, values)).await?;
+    Ok(rows.into_iter().map(|(code,)| code).collect())
+  }
+
+  async fn find_policies_of_roles(&self, role_ids: &[i64]) -> Result<Vec<PolicyRule>> {
+    if role_ids.is_empty() {
+      return Ok(vec![]);
+    }
+    let mut query = Query::select();
+    query
+      .columns([SIden("pattern"), SIden("effect")])
+      .from(PolicyBmc::table_ref())
+      .and_where(Expr::col(SIden("role_id")).is_in(role_ids.iter().copied()));
+
+    let (sql, values) = query.build_sqlx(DbQueryBuilder);
+    let rows = self.mm.dbx().fetch_all(sqlx::query_as_with::<_, (String, PolicyEffect), _>(&sql, values)).await?;
+    Ok(rows.into_iter().map(|(pattern, effect)| PolicyRule { pattern, effect }).collect())
+  }
+}
+
+/// 基于内存的存储后端，用于测试或权限数据固定的场景
+///
+/// # Examples
+///
+/// ```rust
+/// use ultimate_db::acs::{MemoryAcsStore, PolicyEffect};
+///
+/// let store = MemoryAcsStore::new();
+/// store.add_privilege(1, "user:read").bind_role_privilege(10, 1).bind_user_role(1000, 10);
+/// store.add_policy(10, "order:*", PolicyEffect::Allow);
+/// ```
+#[derive(Debug, Default)]
+pub struct MemoryAcsStore {
+  inner: RwLock<MemoryAcsData>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryAcsData {
+  privileges: HashMap<i64, String>,
+  disabled_roles: HashSet<i64>,
+  user_roles: HashMap<i64, HashSet<i64>>,
+  role_privileges: HashMap<i64, HashSet<i64>>,
+  policies: HashMap<i64, Vec<PolicyRule>>,
+}
+
+impl MemoryAcsStore {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn add_privilege(&self, privilege_id: i64, code: impl Into<String>) -> &Self {
+    self.inner.write().unwrap().privileges.insert(privilege_id, code.into());
+    self
+  }
+
+  /// 禁用角色，禁用的角色不授予权限。未禁用的角色均视为启用状态
+  pub fn disable_role(&self, role_id: i64) -> &Self {
+    self.inner.write().unwrap().disabled_roles.insert(role_id);
+    self
+  }
+
+  pub fn enable_role(&self, role_id: i64) -> &Self {
+    self.inner.write().unwrap().disabled_roles.remove(&role_id);
+    self
+  }
+
+  pub fn bind_user_role(&self, uid: i64, role_id: i64) -> &Self {
+    self.inner.write().unwrap().user_roles.entry(uid).or_default().insert(role_id);
+    self
+  }
+
+  pub fn unbind_user_role(&self, uid: i64, role_id: i64) -> &Self {
+    if let Some(roles) = self.inner.write().unwrap().user_roles.get_mut(&uid) {
+      roles.remove(&role_id);
+    }
+    self
+  }
+
+  pub fn bind_role_privilege(&self, role_id: i64, privilege_id: i64) -> &Self {
+    self.inner.write().unwrap().role_privileges.entry(role_id).or_default().insert(privilege_id);
+    self
+  }
+
+  pub fn add_policy(&self, role_id: i64, pattern: impl Into<String>, effect: PolicyEffect) -> &Self {
+    self.inner.write().unwrap().policies.entry(role_id).or_default().push(PolicyRule::new(pattern, effect));
+    self
+  }
+}
+
+#[async_trait]
+impl AcsStore for MemoryAcsStore {
+  async fn find_role_ids_of_user(&self, uid: i64) -> Result<Vec<i64>> {
+    let data = self.inner.read().unwrap();
+    let roles = data.user_roles.get(&uid).into_iter().flatten();
+    Ok(roles.filter(|role_id| !data.disabled_roles.contains(role_id)).copied().collect())
+  }
+
+  async fn find_enabled_role_ids(&self, role_ids: &[i64]) -> Result<Vec<i64>> {
+    let data = self.inner.read().unwrap();
+    Ok(role_ids.iter().filter(|role_id| !data.disabled_roles.contains(role_id)).copied().collect())
+  }
+
+  async fn find_privileges_of_roles(&self, role_ids: &[i64]) -> Result<Vec<String>> {
+    let data = self.inner.read().unwrap();
+    let privilege_ids: HashSet<i64> =
+      role_ids.iter().filter_map(|role_id| data.role_privileges.get(role_id)).flatten().copied().collect();
+    Ok(privilege_ids.iter().filter_map(|id| data.privileges.get(id).cloned()).collect())
+  }
+
+  async fn find_privileges_by_ids(&self, privilege_ids: &[i64]) -> Result<Vec<String>> {
+    let data = self.inner.read().unwrap();
+    Ok(privilege_ids.iter().filter_map(|id| data.privileges.get(id).cloned()).collect())
+  }
+
+  async fn find_policies_of_roles(&self, role_ids: &[i64]) -> Result<Vec<PolicyRule>> {
+    let data = self.inner.read().unwrap();
+    Ok(role_ids.iter().filter_map(|role_id| data.policies.get(role_id)).flatten().cloned().collect())
+  }
+}
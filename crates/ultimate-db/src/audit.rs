@@ -0,0 +1,418 @@
+//! 审计及变更历史。
+//!
+//! [DbBmc::audit] 返回审计策略的表，`crud_fns` 在创建、更新、删除数据时于同一事务内写入审计记录：
+//! 表、数据 ID、操作、操作人（[Ctx::uid]）、请求 ID（[Ctx::request_id]）及修改前后的数据（更新时只记录变化的列）。
+//! [find_history] 按时间顺序查询数据的变更历史。审计表结构见 [MIGRATOR]。只支持 PostgreSQL 后端。
+//!
+//! ```rust,no_run
+//! use ultimate_db::{audit::AuditPolicy, base::DbBmc};
+//!
+//! pub struct InvoiceBmc;
+//! impl DbBmc for InvoiceBmc {
+//!   const TABLE: &'static str = "invoice";
+//!   const SCHEMA: &'static str = "finance";
+//!
+//!   fn audit() -> Option<AuditPolicy> {
+//!     Some(AuditPolicy::default().with_exclude_columns(&["attachment"]))
+//!   }
+//! }
+//! ```
+//!
+//! [Ctx::uid]: ultimate::ctx::Ctx::uid
+//! [Ctx::request_id]: ultimate::ctx::Ctx::request_id
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use modql::SIden;
+use sea_query::{
+  Alias, Condition, DeleteStatement, Expr, InsertStatement, IntoIden, LockType, Order, Query, ReturningClause,
+  SimpleExpr, TableRef, UpdateStatement,
+};
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::FromRow;
+use ultimate_common::time::UtcDateTime;
+
+use crate::base::{apply_scopes_with_deleted, CommonIden, DbBmc};
+use crate::migration::{self, Migrator};
+use crate::store::{backend, DbKind, DbQueryBuilder, DB_KIND};
+use crate::{Error, Id, ModelManager, Result};
+
+/// 创建审计表的迁移
+pub static MIGRATOR: LazyLock<Migrator> = LazyLock::new(|| migration::builtin(sqlx::migrate!("./migrations/audit")));
+
+/// 审计的操作类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+  Create,
+  Update,
+  Delete,
+  /// 恢复逻辑删除的数据
+  Restore,
+  /// `upsert`，冲突时更新已存在的数据。只记录修改后的数据
+  Upsert,
+}
+
+impl AuditOperation {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      AuditOperation::Create => "create",
+      AuditOperation::Update => "update",
+      AuditOperation::Delete => "delete",
+      AuditOperation::Restore => "restore",
+      AuditOperation::Upsert => "upsert",
+    }
+  }
+}
+
+/// 审计策略，由 [DbBmc::audit] 返回
+#[derive(Debug, Clone)]
+pub struct AuditPolicy {
+  schema: &'static str,
+  table: &'static str,
+  exclude_columns: &'static [&'static str],
+}
+
+impl Default for AuditPolicy {
+  fn default() -> Self {
+    Self { schema: "audit", table: "audit_log", exclude_columns: &[] }
+  }
+}
+
+impl AuditPolicy {
+  /// 写入审计记录的表，默认为 `audit.audit_log`
+  pub fn with_table(mut self, schema: &'static str, table: &'static str) -> Self {
+    self.schema = schema;
+    self.table = table;
+    self
+  }
+
+  /// 不记录的列，如密码等敏感数据
+  pub fn with_exclude_columns(mut self, exclude_columns: &'static [&'static str]) -> Self {
+    self.exclude_columns = exclude_columns;
+    self
+  }
+
+  pub fn qualified_table(&self) -> (&'static str, &'static str) {
+    (self.schema, self.table)
+  }
+
+  pub fn exclude_columns(&self) -> &'static [&'static str] {
+    self.exclude_columns
+  }
+
+  fn table_ref(&self) -> TableRef {
+    backend::table_ref(SIden(self.schema).into_iden(), SIden(self.table).into_iden())
+  }
+}
+
+/// 审计记录
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AuditRecord {
+  pub id: i64,
+  pub table_schema: String,
+  pub table_name: String,
+  pub entity_id: String,
+  pub operation: String,
+  pub actor_id: i64,
+  pub tenant_id: Option<i64>,
+  pub request_id: Option<String>,
+  /// 修改前的数据，创建时为 None。更新时只包含变化的列
+  pub before_data: Option<Value>,
+  /// 修改后的数据，删除时为 None。更新时只包含变化的列
+  pub after_data: Option<Value>,
+  pub ctime: UtcDateTime,
+}
+
+/// 查询数据的变更历史，按时间顺序排列。表未启用审计时返回 [Error::InvalidArgument]。
+///
+/// 数据须对当前 Ctx 可见（租户及 [DbBmc::data_scope]，已逻辑删除的数据仍可查询），否则返回 [Error::EntityNotFound]
+pub async fn find_history<MC>(mm: &ModelManager, id: Id) -> Result<Vec<AuditRecord>>
+where
+  MC: DbBmc,
+{
+  let policy = _require_audit::<MC>()?;
+  let ctx = mm.ctx_ref()?;
+
+  // -- Check the entity is visible
+  let mut query = Query::select();
+  query.expr(Expr::val(1)).from(mm.table_ref::<MC>()?).and_where(Expr::col(CommonIden::Id).eq(id.clone()));
+  apply_scopes_with_deleted::<MC, _>(mm, &mut query)?;
+  let (sql, values) = query.build_sqlx(DbQueryBuilder);
+  let sqlx_query = sqlx::query_as_with::<_, (i32,), _>(&sql, values);
+  if mm.dbx_of::<MC>()?.fetch_optional(sqlx_query).await?.is_none() {
+    return Err(Error::EntityNotFound { schema: MC::SCHEMA, entity: MC::TABLE, id });
+  }
+
+  // -- Build the query
+  let mut query = Query::select();
+  query
+    .from(policy.table_ref())
+    .columns(
+      [
+        "id",
+        "table_schema",
+        "table_name",
+        "entity_id",
+        "operation",
+        "actor_id",
+        "tenant_id",
+        "request_id",
+        "before_data",
+        "after_data",
+        "ctime",
+      ]
+      .map(Alias::new),
+    )
+    .and_where(Expr::col(Alias::new("table_schema")).eq(MC::SCHEMA))
+    .and_where(Expr::col(Alias::new("table_name")).eq(MC::TABLE))
+    .and_where(Expr::col(Alias::new("entity_id")).eq(id.to_string()))
+    .order_by(Alias::new("id"), Order::Asc);
+  if let Some(tenant_id) = mm.tenancy().tenant_of::<MC>(Some(ctx))? {
+    query.and_where(Expr::col(Alias::new("tenant_id")).eq(tenant_id));
+  }
+
+  // -- Execute the query
+  let (sql, values) = query.build_sqlx(DbQueryBuilder);
+  let sqlx_query = sqlx::query_as_with::<_, AuditRecord, _>(&sql, values);
+  let records = mm.dbx_of::<MC>()?.fetch_all(sqlx_query).await?;
+  Ok(records)
+}
+
+/// 可审计的写语句
+pub(crate) trait AuditStatement: SqlxBinder + Clone {
+  fn set_returning(&mut self, returning: ReturningClause);
+}
+
+impl AuditStatement for InsertStatement {
+  fn set_returning(&mut self, returning: ReturningClause) {
+    self.returning(returning);
+  }
+}
+
+impl AuditStatement for UpdateStatement {
+  fn set_returning(&mut self, returning: ReturningClause) {
+    self.returning(returning);
+  }
+}
+
+impl AuditStatement for DeleteStatement {
+  fn set_returning(&mut self, returning: ReturningClause) {
+    self.returning(returning);
+  }
+}
+
+type JsonObject = Map<String, Value>;
+
+/// 在事务内执行写语句并写入审计记录，返回受影响的数据的 ID（文本形式）。
+///
+/// `before` 为查询修改前数据的过滤条件，用于更新及逻辑删除；物理删除时语句返回的即为删除前的数据。
+pub(crate) async fn execute_audited<MC, S>(
+  mm: &ModelManager,
+  operation: AuditOperation,
+  mut query: S,
+  before: Option<Condition>,
+) -> Result<Vec<String>>
+where
+  MC: DbBmc,
+  S: AuditStatement,
+{
+  let policy = _require_audit::<MC>()?;
+  // 依赖 `RETURNING` 及 `to_jsonb`
+  if DB_KIND != DbKind::Postgres {
+    return Err(Error::UnsupportedByBackend { feature: "audit", backend: DB_KIND });
+  }
+
+  query.set_returning(Query::returning().exprs(_audit_exprs::<MC>()));
+  let (sql, values) = query.build_sqlx(DbQueryBuilder);
+  let before = match before {
+    Some(cond) => {
+      let mut query = Query::select();
+      query.from(mm.table_ref::<MC>()?).exprs(_audit_exprs::<MC>()).cond_where(cond).lock(LockType::Update);
+      apply_scopes_with_deleted::<MC, _>(mm, &mut query)?;
+      Some(query.build_sqlx(DbQueryBuilder))
+    }
+    None => None,
+  };
+
+  mm.transaction(|mm| {
+    let (sql, values) = (sql.clone(), values.clone());
+    let before = before.clone();
+    let policy = policy.clone();
+    async move {
+      let dbx = mm.dbx_of::<MC>()?;
+
+      let mut before_rows: HashMap<String, Value> = HashMap::new();
+      if let Some((sql, values)) = before {
+        let sqlx_query = sqlx::query_as_with::<_, (String, Value), _>(&sql, values);
+        before_rows = dbx.fetch_all(sqlx_query).await?.into_iter().collect();
+      }
+
+      let sqlx_query = sqlx::query_as_with::<_, (String, Value), _>(&sql, values);
+      let rows = dbx.fetch_all(sqlx_query).await?;
+      if rows.is_empty() {
+        return Ok(Vec::new());
+      }
+
+      // -- Write audit records
+      let ctx = mm.ctx_ref()?;
+      let mut query = Query::insert();
+      query.into_table(policy.table_ref()).columns(
+        [
+          "table_schema",
+          "table_name",
+          "entity_id",
+          "operation",
+          "actor_id",
+          "tenant_id",
+          "request_id",
+          "before_data",
+          "after_data",
+        ]
+        .map(Alias::new),
+      );
+      for (id, data) in rows.iter() {
+        let (before_data, after_data) = match operation {
+          AuditOperation::Create | AuditOperation::Upsert => (None, Some(data.clone())),
+          AuditOperation::Update | AuditOperation::Restore => (before_rows.remove(id), Some(data.clone())),
+          AuditOperation::Delete => (Some(before_rows.remove(id).unwrap_or_else(|| data.clone())), None),
+        };
+        let (before_data, after_data) = audit_diff(before_data, after_data, policy.exclude_columns);
+        query.values([
+          MC::SCHEMA.into(),
+          MC::TABLE.into(),
+          id.clone().into(),
+          operation.as_str().into(),
+          ctx.uid().into(),
+          ctx.tenant_id().into(),
+          ctx.request_id().map(ToString::to_string).into(),
+          _json_expr(before_data),
+          _json_expr(after_data),
+        ])?;
+      }
+      let (sql, values) = query.build_sqlx(DbQueryBuilder);
+      dbx.execute(sqlx::query_with(&sql, values)).await?;
+
+      Ok(rows.into_iter().map(|(id, _)| id).collect())
+    }
+  })
+  .await
+}
+
+/// 去除不记录的列；同时存在修改前后的数据时只保留变化的列
+fn audit_diff(
+  before: Option<Value>,
+  after: Option<Value>,
+  exclude_columns: &[&str],
+) -> (Option<JsonObject>, Option<JsonObject>) {
+  let strip = |v: Option<Value>| match v {
+    Some(Value::Object(mut map)) => {
+      map.retain(|k, _| !exclude_columns.contains(&k.as_str()));
+      Some(map)
+    }
+    _ => None,
+  };
+
+  match (strip(before), strip(after)) {
+    (Some(mut before), Some(mut after)) => {
+      let unchanged: Vec<String> =
+        after.iter().filter(|(k, v)| before.get(k.as_str()) == Some(v)).map(|(k, _)| k.clone()).collect();
+      for k in unchanged {
+        before.remove(&k);
+        after.remove(&k);
+      }
+      (Some(before), Some(after))
+    }
+    (before, after) => (before, after),
+  }
+}
+
+/// 返回 ID 及整行数据（JSON）的表达式
+fn _audit_exprs<MC: DbBmc>() -> [SimpleExpr; 2] {
+  [
+    Expr::cust_with_expr("CAST($1 AS TEXT)", Expr::col(CommonIden::Id)),
+    Expr::cust(format!(r#"to_jsonb("{}")"#, MC::TABLE.replace('"', "\"\""))),
+  ]
+}
+
+fn _json_expr(value: Option<JsonObject>) -> SimpleExpr {
+  match value {
+    Some(map) => Expr::cust_with_values("CAST($1 AS JSONB)", [Value::Object(map).to_string()]),
+    None => Expr::cust("NULL"),
+  }
+}
+
+fn _require_audit<MC: DbBmc>() -> Result<AuditPolicy> {
+  MC::audit()
+    .ok_or_else(|| Error::InvalidArgument { message: format!("'{}.{}' not enable audit", MC::SCHEMA, MC::TABLE) })
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  #[test]
+  fn test_audit_diff() {
+    let before = json!({"id": 1, "amount": 100, "note": "a", "secret": "x"});
+    let after = json!({"id": 1, "amount": 200, "note": "a", "secret": "y"});
+    let (before, after) = audit_diff(Some(before), Some(after), &["secret"]);
+    assert_eq!(before.map(Value::Object), Some(json!({"amount": 100})));
+    assert_eq!(after.map(Value::Object), Some(json!({"amount": 200})));
+
+    let (before, after) = audit_diff(None, Some(json!({"id": 1, "secret": "x"})), &["secret"]);
+    assert_eq!(before, None);
+    assert_eq!(after.map(Value::Object), Some(json!({"id": 1})));
+  }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod sqlite_tests {
+  use ultimate::{configuration::model::DbConf, ctx::Ctx};
+  use ultimate_common::time;
+
+  use super::*;
+  use crate::base::DataScope;
+
+  struct DictBmc;
+  impl DbBmc for DictBmc {
+    const TABLE: &'static str = "dict";
+
+    fn audit() -> Option<AuditPolicy> {
+      Some(AuditPolicy::default())
+    }
+
+    fn data_scope() -> DataScope {
+      DataScope::Owner
+    }
+  }
+
+  #[tokio::test]
+  async fn test_find_history_of_invisible_entity() -> anyhow::Result<()> {
+    let conf: DbConf = serde_json::from_value(serde_json::json!({ "enable": true, "url": "sqlite::memory:" }))?;
+    let mm = ModelManager::new(&conf).await?;
+    let script = "CREATE TABLE dict (id INTEGER PRIMARY KEY, owner_id INTEGER NOT NULL);
+      CREATE TABLE audit_log (id INTEGER PRIMARY KEY, table_schema TEXT NOT NULL, table_name TEXT NOT NULL,
+        entity_id TEXT NOT NULL, operation TEXT NOT NULL, actor_id INTEGER NOT NULL, tenant_id INTEGER,
+        request_id TEXT, before_data TEXT, after_data TEXT, ctime TEXT NOT NULL);
+      INSERT INTO dict (id, owner_id) VALUES (1, 100);
+      INSERT INTO audit_log (table_schema, table_name, entity_id, operation, actor_id, after_data, ctime)
+        VALUES ('public', 'dict', '1', 'create', 100, '{\"secret\": \"x\"}', '2024-01-01T00:00:00Z');";
+    sqlx::raw_sql(script).execute(mm.dbx().db()).await?;
+
+    let user = |uid| {
+      let now = time::now_utc();
+      mm.clone().with_ctx(Ctx::new(uid, now, now + time::Duration::minutes(30)))
+    };
+    let records = find_history::<DictBmc>(&user(100), 1.into()).await?;
+    assert_eq!(records.len(), 1);
+
+    // 不是数据的所有者时看不到数据，也看不到变更历史
+    let err = find_history::<DictBmc>(&user(200), 1.into()).await.unwrap_err();
+    assert!(matches!(err, Error::EntityNotFound { .. }), "{}", err);
+    Ok(())
+  }
+}
@@ -0,0 +1,465 @@
+//! 聚合查询。
+//!
+//! [aggregate]、[page_aggregate] 按 [GroupBy] 分组计算 [Aggregate]，结果映射为调用方定义的类型，
+//! 字段名为分组列及聚合表达式的别名。排序只能使用这些别名，未指定排序时按分组列升序。
+//!
+//! ```rust,no_run
+//! use chrono::NaiveDate;
+//! use modql::filter::FilterNode;
+//! use ultimate_api::v1::{PagePayload, Pagination};
+//! use ultimate_db::base::{self, Aggregate, AggregateCast, AggregateQuery, DateTrunc, DbBmc, GroupBy};
+//! use ultimate_db::ModelManager;
+//!
+//! struct OrderBmc;
+//! impl DbBmc for OrderBmc {
+//!   const TABLE: &'static str = "order";
+//! }
+//!
+//! #[derive(sqlx::FromRow)]
+//! struct DailySales {
+//!   day: NaiveDate,
+//!   count: i64,
+//!   amount: Option<i64>,
+//! }
+//!
+//! # async fn example(mm: &ModelManager, pagination: Pagination) -> ultimate_db::Result<PagePayload<DailySales>> {
+//! let query = AggregateQuery::new()
+//!   .with_group_by(GroupBy::date("ctime", DateTrunc::Day).with_alias("day"))
+//!   .with_aggregate(Aggregate::count())
+//!   .with_aggregate(Aggregate::sum("amount").with_alias("amount").with_cast(AggregateCast::Integer));
+//! base::page_aggregate::<OrderBmc, DailySales, _>(mm, Vec::<FilterNode>::new(), &query, pagination)
+//!   .await
+//! # }
+//! ```
+use modql::filter::{FilterGroups, ListOptions, OrderBy};
+use sea_query::{Alias, Asterisk, Condition, Expr, Func, Order, Query, SelectStatement, SimpleExpr, TableRef};
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use ultimate_api::v1::{Page, PagePayload, Pagination};
+
+use super::{apply_scopes, DbBmc};
+use crate::store::{backend, DbKind, DbQueryBuilder, DbRow, DB_KIND};
+use crate::{Error, ModelManager, Result};
+
+/// 日期截断的单位，结果为日期（`DATE`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateTrunc {
+  Day,
+
+  /// 所在周的周一
+  Week,
+
+  /// 所在月的第一天
+  Month,
+}
+
+impl DateTrunc {
+  /// PostgreSQL 截断 `timestamptz` 时使用会话的时区（`TimeZone`）。
+  ///
+  /// 常量参数使用字面量而不是绑定参数，以保证 `SELECT` 与 `GROUP BY` 中的表达式相同
+  fn expr(&self, column: SimpleExpr) -> SimpleExpr {
+    let func = |name: &str| Func::cust(Alias::new(name));
+    match (DB_KIND, self) {
+      (DbKind::Postgres, _) => {
+        let unit = match self {
+          DateTrunc::Day => "'day'",
+          DateTrunc::Week => "'week'",
+          DateTrunc::Month => "'month'",
+        };
+        Expr::expr(func("date_trunc").arg(Expr::cust(unit)).arg(column)).cast_as(Alias::new("DATE"))
+      }
+      (DbKind::MySql, DateTrunc::Day) => func("DATE").arg(column).into(),
+      (DbKind::MySql, DateTrunc::Week) => {
+        let monday = func("SUBDATE").arg(column.clone()).arg(func("WEEKDAY").arg(column));
+        func("DATE").arg(monday).into()
+      }
+      (DbKind::MySql, DateTrunc::Month) => {
+        func("DATE").arg(func("DATE_FORMAT").arg(column).arg(Expr::cust("'%Y-%m-01'"))).into()
+      }
+      (DbKind::Sqlite, DateTrunc::Day) => func("date").arg(column).into(),
+      (DbKind::Sqlite, DateTrunc::Week) => {
+        func("date").args([column, Expr::cust("'weekday 0'"), Expr::cust("'-6 days'")]).into()
+      }
+      (DbKind::Sqlite, DateTrunc::Month) => func("date").args([column, Expr::cust("'start of month'")]).into(),
+    }
+  }
+}
+
+/// 分组列
+#[derive(Debug, Clone)]
+pub struct GroupBy {
+  column: String,
+  trunc: Option<DateTrunc>,
+  alias: String,
+}
+
+impl GroupBy {
+  /// 按列的值分组，别名默认为列名
+  pub fn column(column: impl Into<String>) -> Self {
+    let column = column.into();
+    Self { alias: column.clone(), column, trunc: None }
+  }
+
+  /// 按截断后的日期分组，别名默认为列名
+  pub fn date(column: impl Into<String>, trunc: DateTrunc) -> Self {
+    Self { trunc: Some(trunc), ..Self::column(column) }
+  }
+
+  pub fn with_alias(mut self, alias: impl Into<String>) -> Self {
+    self.alias = alias.into();
+    self
+  }
+
+  pub fn alias(&self) -> &str {
+    &self.alias
+  }
+
+  fn expr(&self) -> SimpleExpr {
+    let column = Expr::col(Alias::new(&self.column)).into();
+    match self.trunc {
+      Some(trunc) => trunc.expr(column),
+      None => column,
+    }
+  }
+}
+
+/// 聚合函数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFn {
+  Count,
+  CountDistinct,
+  Sum,
+  Avg,
+  Min,
+  Max,
+}
+
+/// 聚合结果的类型转换，用于统一不同数据库的结果类型，如 PostgreSQL 对整数列 `SUM` 的结果为 `NUMERIC`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateCast {
+  /// 转换为 64 位整数，对应 `i64`
+  Integer,
+
+  /// 转换为双精度浮点数，对应 `f64`
+  Float,
+}
+
+/// 聚合表达式
+#[derive(Debug, Clone)]
+pub struct Aggregate {
+  func: AggregateFn,
+  column: Option<String>,
+  alias: String,
+  cast: Option<AggregateCast>,
+}
+
+impl Aggregate {
+  /// `COUNT(*)`，别名默认为 `count`
+  pub fn count() -> Self {
+    Self { func: AggregateFn::Count, column: None, alias: "count".to_string(), cast: None }
+  }
+
+  /// `COUNT(DISTINCT column)`，别名默认为 `count_distinct_<column>`
+  pub fn count_distinct(column: impl Into<String>) -> Self {
+    Self::new(AggregateFn::CountDistinct, column.into())
+  }
+
+  /// 别名默认为 `sum_<column>`
+  pub fn sum(column: impl Into<String>) -> Self {
+    Self::new(AggregateFn::Sum, column.into())
+  }
+
+  /// 结果默认转换为浮点数，别名默认为 `avg_<column>`
+  pub fn avg(column: impl Into<String>) -> Self {
+    Self::new(AggregateFn::Avg, column.into()).with_cast(AggregateCast::Float)
+  }
+
+  /// 别名默认为 `min_<column>`
+  pub fn min(column: impl Into<String>) -> Self {
+    Self::new(AggregateFn::Min, column.into())
+  }
+
+  /// 别名默认为 `max_<column>`
+  pub fn max(column: impl Into<String>) -> Self {
+    Self::new(AggregateFn::Max, column.into())
+  }
+
+  fn new(func: AggregateFn, column: String) -> Self {
+    let prefix = match func {
+      AggregateFn::Count => "count",
+      AggregateFn::CountDistinct => "count_distinct",
+      AggregateFn::Sum => "sum",
+      AggregateFn::Avg => "avg",
+      AggregateFn::Min => "min",
+      AggregateFn::Max => "max",
+    };
+    Self { func, alias: format!("{}_{}", prefix, column), column: Some(column), cast: None }
+  }
+
+  pub fn with_alias(mut self, alias: impl Into<String>) -> Self {
+    self.alias = alias.into();
+    self
+  }
+
+  pub fn with_cast(mut self, cast: AggregateCast) -> Self {
+    self.cast = Some(cast);
+    self
+  }
+
+  pub fn alias(&self) -> &str {
+    &self.alias
+  }
+
+  fn expr(&self) -> SimpleExpr {
+    let column = match &self.column {
+      Some(column) => Expr::col(Alias::new(column)),
+      None => Expr::col(Asterisk),
+    };
+    let expr: SimpleExpr = match self.func {
+      AggregateFn::Count => Func::count(column).into(),
+      AggregateFn::CountDistinct => Func::count_distinct(column).into(),
+      AggregateFn::Sum => Func::sum(column).into(),
+      AggregateFn::Avg => Func::avg(column).into(),
+      AggregateFn::Min => Func::min(column).into(),
+      AggregateFn::Max => Func::max(column).into(),
+    };
+    match self.cast {
+      Some(AggregateCast::Integer) => Expr::expr(expr).cast_as(Alias::new(backend::INTEGER_TYPE)),
+      Some(AggregateCast::Float) => Expr::expr(expr).cast_as(Alias::new(backend::FLOAT_TYPE)),
+      None => expr,
+    }
+  }
+}
+
+/// 聚合查询的分组列及聚合表达式
+#[derive(Debug, Clone, Default)]
+pub struct AggregateQuery {
+  group_bys: Vec<GroupBy>,
+  aggregates: Vec<Aggregate>,
+}
+
+impl AggregateQuery {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn with_group_by(mut self, group_by: GroupBy) -> Self {
+    self.group_bys.push(group_by);
+    self
+  }
+
+  pub fn with_aggregate(mut self, aggregate: Aggregate) -> Self {
+    self.aggregates.push(aggregate);
+    self
+  }
+
+  pub fn group_bys(&self) -> &[GroupBy] {
+    &self.group_bys
+  }
+
+  pub fn aggregates(&self) -> &[Aggregate] {
+    &self.aggregates
+  }
+
+  /// 生成查询语句，`list_options` 的排序列必须是分组列或聚合表达式的别名
+  fn select(&self, table_ref: TableRef, list_options: Option<ListOptions>) -> Result<SelectStatement> {
+    if self.aggregates.is_empty() {
+      return Err(Error::InvalidArgument { message: "Aggregate query requires at least one aggregate".to_string() });
+    }
+
+    let mut query = Query::select();
+    query.from(table_ref);
+    for group_by in &self.group_bys {
+      query.expr_as(group_by.expr(), Alias::new(&group_by.alias));
+      query.add_group_by([group_by.expr()]);
+    }
+    for aggregate in &self.aggregates {
+      query.expr_as(aggregate.expr(), Alias::new(&aggregate.alias));
+    }
+
+    let Some(mut list_options) = list_options else {
+      return Ok(query);
+    };
+    let order_bys = list_options.order_bys.take().map(|order_bys| order_bys.order_bys()).unwrap_or_default();
+    if order_bys.is_empty() {
+      for group_by in &self.group_bys {
+        query.order_by(Alias::new(&group_by.alias), Order::Asc);
+      }
+    }
+    for order_by in order_bys {
+      let (alias, order) = match order_by {
+        OrderBy::Asc(alias) => (alias, Order::Asc),
+        OrderBy::Desc(alias) => (alias, Order::Desc),
+      };
+      if !self.has_alias(&alias) {
+        return Err(Error::InvalidArgument { message: format!("Invalid aggregate sort column: '{}'", alias) });
+      }
+      query.order_by(Alias::new(alias), order);
+    }
+    list_options.apply_to_sea_query(&mut query);
+    Ok(query)
+  }
+
+  fn has_alias(&self, alias: &str) -> bool {
+    self.group_bys.iter().any(|g| g.alias == alias) || self.aggregates.iter().any(|a| a.alias == alias)
+  }
+}
+
+/// 聚合查询，每个分组返回一行。
+///
+/// 未设置 `limit` 时返回所有分组，不使用 [DbBmc::LIST_LIMIT_DEFAULT]；设置的 `limit` 不能超过 [DbBmc::LIST_LIMIT_MAX]
+pub async fn aggregate<MC, R, F>(
+  mm: &ModelManager,
+  filter: F,
+  query: &AggregateQuery,
+  list_options: Option<ListOptions>,
+) -> Result<Vec<R>>
+where
+  MC: DbBmc,
+  R: for<'r> FromRow<'r, DbRow> + Unpin + Send,
+  F: Into<FilterGroups>,
+{
+  if let Some(limit) = list_options.as_ref().and_then(|list_options| list_options.limit) {
+    if limit > MC::LIST_LIMIT_MAX {
+      return Err(Error::ListLimitOverMax { max: MC::LIST_LIMIT_MAX, actual: limit });
+    }
+  }
+  let select = _build_aggregate_query::<MC>(mm, filter.into(), query, Some(list_options.unwrap_or_default()))?;
+  let (sql, values) = select.build_sqlx(DbQueryBuilder);
+  let sqlx_query = sqlx::query_as_with::<_, R, _>(&sql, values);
+  let rows = mm.dbx_of::<MC>()?.fetch_all(sqlx_query).await?;
+  Ok(rows)
+}
+
+/// 分页的聚合查询，总数为分组数
+pub async fn page_aggregate<MC, R, F>(
+  mm: &ModelManager,
+  filter: F,
+  query: &AggregateQuery,
+  pagination: Pagination,
+) -> Result<PagePayload<R>>
+where
+  MC: DbBmc,
+  R: for<'r> FromRow<'r, DbRow> + Unpin + Send,
+  F: Into<FilterGroups>,
+{
+  let filters: FilterGroups = filter.into();
+
+  let groups = _build_aggregate_query::<MC>(mm, filters.clone(), query, None)?;
+  let mut count_query = Query::select();
+  count_query.expr(Expr::col(Asterisk).count()).from_subquery(groups, Alias::new("t"));
+  let (sql, values) = count_query.build_sqlx(DbQueryBuilder);
+  let sqlx_query = sqlx::query_as_with::<_, (i64,), _>(&sql, values);
+  let (total_size,) = mm.dbx_of::<MC>()?.fetch_one(sqlx_query).await.map_err(|_| Error::CountFail)?;
+
+  let items = aggregate::<MC, R, _>(mm, filters, query, Some((&pagination).into())).await?;
+  Ok(PagePayload::new(Page::new(&pagination, total_size), items))
+}
+
+fn _build_aggregate_query<MC>(
+  mm: &ModelManager,
+  filters: FilterGroups,
+  query: &AggregateQuery,
+  list_options: Option<ListOptions>,
+) -> Result<SelectStatement>
+where
+  MC: DbBmc,
+{
+  let mut select = query.select(mm.table_ref::<MC>()?, list_options)?;
+  let cond: Condition = filters.try_into()?;
+  select.cond_where(cond);
+  apply_scopes::<MC, _>(mm, &mut select)?;
+  Ok(select)
+}
+
+#[cfg(all(test, feature = "postgres"))]
+mod tests {
+  use modql::filter::OrderBys;
+  use modql::SIden;
+  use sea_query::{IntoIden, PostgresQueryBuilder};
+
+  use super::*;
+
+  #[test]
+  fn test_aggregate_query() -> Result<()> {
+    let query = AggregateQuery::new()
+      .with_group_by(GroupBy::date("ctime", DateTrunc::Month).with_alias("month"))
+      .with_group_by(GroupBy::column("status"))
+      .with_aggregate(Aggregate::count())
+      .with_aggregate(Aggregate::sum("amount").with_cast(AggregateCast::Integer))
+      .with_aggregate(Aggregate::avg("amount"));
+    let table_ref = TableRef::Table(SIden("order").into_iden());
+
+    let select = query.select(table_ref.clone(), None)?;
+    assert_eq!(
+      select.to_string(PostgresQueryBuilder),
+      r#"SELECT CAST(date_trunc('month', "ctime") AS DATE) AS "month", "status" AS "status", COUNT(*) AS "count", CAST(SUM("amount") AS BIGINT) AS "sum_amount", CAST(AVG("amount") AS DOUBLE PRECISION) AS "avg_amount" FROM "order" GROUP BY CAST(date_trunc('month', "ctime") AS DATE), "status""#
+    );
+
+    let list_options = ListOptions { limit: Some(10), offset: None, order_bys: Some(OrderBys::new(vec![])) };
+    let select = query.select(table_ref.clone(), Some(list_options))?;
+    assert!(select.to_string(PostgresQueryBuilder).ends_with(r#"ORDER BY "month" ASC, "status" ASC LIMIT 10"#));
+
+    let list_options = ListOptions { limit: None, offset: None, order_bys: Some(OrderBys::new(vec!["!count".into()])) };
+    let select = query.select(table_ref.clone(), Some(list_options))?;
+    assert!(select.to_string(PostgresQueryBuilder).ends_with(r#"ORDER BY "count" DESC"#));
+
+    let list_options = ListOptions { limit: None, offset: None, order_bys: Some(OrderBys::new(vec!["amount".into()])) };
+    assert!(matches!(query.select(table_ref, Some(list_options)), Err(Error::InvalidArgument { .. })));
+    Ok(())
+  }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod sqlite_tests {
+  use sqlx::FromRow;
+  use ultimate::configuration::model::DbConf;
+  use ultimate::ctx::Ctx;
+
+  use crate::base::{self, Aggregate, AggregateQuery, DateTrunc, DbBmc, GroupBy};
+  use crate::{Error, ModelManager};
+
+  struct LimitedDictBmc;
+  impl DbBmc for LimitedDictBmc {
+    const TABLE: &'static str = "dict";
+    const LIST_LIMIT_DEFAULT: i64 = 2;
+  }
+
+  #[derive(FromRow)]
+  struct WeekStats {
+    week: String,
+    count: i64,
+  }
+
+  #[tokio::test]
+  async fn test_aggregate_by_week_on_sqlite() -> anyhow::Result<()> {
+    let conf: DbConf = serde_json::from_value(serde_json::json!({ "enable": true, "url": "sqlite::memory:" }))?;
+    let mm = ModelManager::new(&conf).await?.with_ctx(Ctx::new_root());
+    mm.dbx().execute(sqlx::query("CREATE TABLE dict (id INTEGER PRIMARY KEY, ctime TEXT NOT NULL)")).await?;
+    let insert = "INSERT INTO dict (ctime) VALUES ('2024-01-03 10:00:00'), ('2024-01-07 23:00:00'), \
+      ('2024-01-08 00:00:00'), ('2024-02-15 08:00:00')";
+    mm.dbx().execute(sqlx::query(insert)).await?;
+
+    // 未设置 limit 时返回所有分组，不使用 LIST_LIMIT_DEFAULT
+    let query = AggregateQuery::new()
+      .with_group_by(GroupBy::date("ctime", DateTrunc::Week).with_alias("week"))
+      .with_aggregate(Aggregate::count());
+    let stats: Vec<WeekStats> =
+      base::aggregate::<LimitedDictBmc, _, _>(&mm, Vec::<modql::filter::FilterNode>::new(), &query, None).await?;
+    let weeks: Vec<_> = stats.iter().map(|s| (s.week.as_str(), s.count)).collect();
+    assert_eq!(weeks, vec![("2024-01-01", 2), ("2024-01-08", 1), ("2024-02-12", 1)]);
+
+    let list_options = modql::filter::ListOptions { limit: Some(5001), ..Default::default() };
+    let result = base::aggregate::<LimitedDictBmc, WeekStats, _>(
+      &mm,
+      Vec::<modql::filter::FilterNode>::new(),
+      &query,
+      Some(list_options),
+    )
+    .await;
+    assert!(matches!(result, Err(Error::ListLimitOverMax { .. })));
+    Ok(())
+  }
+}
@@ -0,0 +1,233 @@
+use sea_query::{Alias, DynIden, Expr, Func, IntoIden, OnConflict};
+use ultimate::configuration::model::TenancyMode;
+
+use super::{CommonIden, DbBmc, TimestampIden};
+use crate::store::{DbKind, DB_KIND};
+use crate::{Error, ModelManager, Result};
+
+/// `upsert`、`insert_ignore` 的冲突处理方式。
+///
+/// 未指定时使用 [DbBmc::conflict_columns] 和 [DbBmc::upsert_update_columns] 的声明。
+#[derive(Debug, Clone, Default)]
+pub struct ConflictSpec {
+  columns: Vec<String>,
+  update_columns: Option<Vec<String>>,
+}
+
+impl ConflictSpec {
+  /// 冲突列，必须存在对应的唯一约束或唯一索引
+  pub fn new<I, S>(columns: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    Self { columns: columns.into_iter().map(Into::into).collect(), update_columns: None }
+  }
+
+  /// 使用 [DbBmc] 声明的冲突列和更新列
+  pub fn of<MC: DbBmc>() -> Self {
+    Self {
+      columns: MC::conflict_columns().iter().map(|c| c.to_string()).collect(),
+      update_columns: MC::upsert_update_columns().map(|cols| cols.iter().map(|c| c.to_string()).collect()),
+    }
+  }
+
+  /// 冲突时更新的列，未设置时更新除冲突列和创建信息（id、owner_id、cid、ctime）外所有插入的列
+  pub fn with_update_columns<I, S>(mut self, columns: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    self.update_columns = Some(columns.into_iter().map(Into::into).collect());
+    self
+  }
+
+  pub fn columns(&self) -> &[String] {
+    &self.columns
+  }
+
+  pub fn update_columns(&self) -> Option<&[String]> {
+    self.update_columns.as_deref()
+  }
+
+  /// 生成 `ON CONFLICT ... DO NOTHING`。
+  ///
+  /// MySQL 不支持 `DO NOTHING`，生成 `ON DUPLICATE KEY UPDATE <第一个冲突列> = <第一个冲突列>`
+  pub(crate) fn to_do_nothing<MC: DbBmc>(&self) -> Result<OnConflict> {
+    let target = self.target_columns::<MC>()?;
+    let first = target[0].clone();
+    let mut on_conflict = OnConflict::columns(target);
+    if DB_KIND == DbKind::MySql {
+      on_conflict.do_nothing_on([first]);
+    } else {
+      on_conflict.do_nothing();
+    }
+    Ok(on_conflict)
+  }
+
+  /// 生成 `ON CONFLICT ... DO UPDATE`。
+  ///
+  /// 冲突时还会更新修改人和修改时间、乐观锁版本号加 1、恢复已逻辑删除的数据；
+  /// 超出 [DbBmc::data_scope] 及 `shared_table` 多租户模式下其它租户的数据不会被更新。
+  ///
+  /// MySQL 生成 `ON DUPLICATE KEY UPDATE`，并设置 `id = LAST_INSERT_ID(id)` 以取得被更新的数据的 ID；
+  /// MySQL 不支持更新条件，存在数据范围或租户条件时返回错误。
+  pub(crate) fn to_do_update<MC: DbBmc>(&self, mm: &ModelManager, insert_columns: &[DynIden]) -> Result<OnConflict> {
+    let target = self.target_columns::<MC>()?;
+    let update_columns: Vec<DynIden> = match &self.update_columns {
+      Some(cols) => cols.iter().map(|c| Alias::new(c).into_iden()).collect(),
+      None => {
+        let excluded = [
+          CommonIden::Id.into_iden(),
+          CommonIden::OwnerId.into_iden(),
+          CommonIden::TenantId.into_iden(),
+          CommonIden::OptimisticLock.into_iden(),
+          TimestampIden::Cid.into_iden(),
+          TimestampIden::Ctime.into_iden(),
+        ];
+        // 按列名比较，插入列与冲突列、排除列的 Iden 类型可能不同
+        let skipped: Vec<String> = target.iter().chain(excluded.iter()).map(|c| c.to_string()).collect();
+        insert_columns.iter().filter(|c| !skipped.contains(&c.to_string())).cloned().collect()
+      }
+    };
+    if update_columns.is_empty() {
+      return Err(Error::InvalidArgument {
+        message: format!("No columns to update on conflict for table '{}.{}'", MC::SCHEMA, MC::TABLE),
+      });
+    }
+
+    let mut on_conflict = OnConflict::columns(target);
+    on_conflict.update_columns(update_columns);
+
+    let ctx = mm.ctx_ref()?;
+    if MC::has_modification_timestamps() {
+      on_conflict.value(TimestampIden::Mid, ctx.uid()).value(TimestampIden::Mtime, *ctx.req_time());
+    }
+    if MC::has_optimistic_lock() {
+      on_conflict.value(CommonIden::OptimisticLock, Expr::col(CommonIden::OptimisticLock).add(1));
+    }
+    if MC::use_logical_deletion() {
+      let logical_deletion = MC::logical_deletion();
+      on_conflict.value(Alias::new(logical_deletion.column()), logical_deletion.restored_value());
+    }
+    if mm.tenancy().mode() == TenancyMode::SharedTable {
+      if let Some(tenant_id) = mm.tenancy().tenant_of::<MC>(Some(ctx))? {
+        if DB_KIND == DbKind::MySql {
+          return Err(Error::UnsupportedByBackend { feature: "upsert with shared_table tenancy", backend: DB_KIND });
+        }
+        on_conflict.action_and_where(Expr::col(CommonIden::TenantId).eq(tenant_id));
+      }
+    }
+    if !MC::bypass_data_scope(ctx) {
+      if let Some(cond) = MC::data_scope().to_condition(ctx) {
+        if DB_KIND == DbKind::MySql {
+          return Err(Error::UnsupportedByBackend { feature: "upsert with data scope", backend: DB_KIND });
+        }
+        on_conflict.action_cond_where(cond);
+      }
+    }
+    if DB_KIND == DbKind::MySql {
+      on_conflict.value(CommonIden::Id, Func::cust(Alias::new("LAST_INSERT_ID")).arg(Expr::col(CommonIden::Id)));
+    }
+    Ok(on_conflict)
+  }
+
+  fn target_columns<MC: DbBmc>(&self) -> Result<Vec<DynIden>> {
+    if self.columns.is_empty() {
+      return Err(Error::InvalidArgument {
+        message: format!("Conflict columns are required for table '{}.{}'", MC::SCHEMA, MC::TABLE),
+      });
+    }
+    Ok(self.columns.iter().map(|c| Alias::new(c).into_iden()).collect())
+  }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+  use modql::field::Fields;
+  use ultimate::{configuration::model::DbConf, ctx::Ctx};
+
+  use crate::base::{self, ConflictSpec, DbBmc};
+  use crate::{Error, ModelManager};
+
+  struct DictBmc;
+  impl DbBmc for DictBmc {
+    const TABLE: &'static str = "dict";
+  }
+
+  #[derive(Fields)]
+  struct DictForUpsertById {
+    id: i64,
+    value: String,
+  }
+
+  #[derive(Fields)]
+  struct DictForUpsert {
+    name: String,
+    value: Option<String>,
+    remark: Option<String>,
+  }
+
+  #[tokio::test]
+  async fn test_upsert_shared_table_on_sqlite() -> anyhow::Result<()> {
+    let conf: DbConf = serde_json::from_value(serde_json::json!({
+      "enable": true,
+      "url": "sqlite::memory:",
+      "tenancy": { "mode": "shared_table" },
+    }))?;
+    let mm = ModelManager::new(&conf).await?;
+    // 只有 id 主键，没有 (id, tenant_id) 唯一索引
+    let create_table = "CREATE TABLE dict (id INTEGER PRIMARY KEY, tenant_id INTEGER NOT NULL, value TEXT NOT NULL, \
+      cid INTEGER, ctime TEXT, mid INTEGER, mtime TEXT)";
+    mm.dbx().execute(sqlx::query(create_table)).await?;
+    let tenant = |tenant_id| mm.clone().with_ctx(Ctx::new_root().with_tenant_id(tenant_id));
+    let upsert = |value: &str| DictForUpsertById { id: 1, value: value.to_string() };
+
+    // 使用默认的冲突列 `id`
+    assert_eq!(base::upsert::<DictBmc, _>(&tenant(1), upsert("1")).await?, 1);
+    assert_eq!(base::upsert::<DictBmc, _>(&tenant(1), upsert("2")).await?, 1);
+    assert!(!base::insert_ignore::<DictBmc, _>(&tenant(1), upsert("3")).await?);
+
+    // 不更新其它租户的数据
+    let err = base::upsert::<DictBmc, _>(&tenant(2), upsert("4")).await.unwrap_err();
+    assert!(matches!(err, Error::NotFound { .. }), "{}", err);
+    let row: (i64, String) = sqlx::query_as("SELECT tenant_id, value FROM dict").fetch_one(mm.dbx().db()).await?;
+    assert_eq!(row, (1, "2".to_string()));
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_upsert_many_with_different_columns_on_sqlite() -> anyhow::Result<()> {
+    let conf: DbConf = serde_json::from_value(serde_json::json!({ "enable": true, "url": "sqlite::memory:" }))?;
+    let mm = ModelManager::new(&conf).await?.with_ctx(Ctx::new_root());
+    let create_table = "CREATE TABLE dict (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL UNIQUE, \
+      value TEXT, remark TEXT, cid INTEGER, ctime TEXT, mid INTEGER, mtime TEXT)";
+    mm.dbx().execute(sqlx::query(create_table)).await?;
+    mm.dbx()
+      .execute(sqlx::query("INSERT INTO dict (name, value, remark) VALUES ('a', '1', 'r1'), ('b', '2', 'r2')"))
+      .await?;
+
+    // 各行只更新自己设置的列，未设置的列保留原值
+    let upsert = |name: &str, value: Option<&str>, remark: Option<&str>| DictForUpsert {
+      name: name.to_string(),
+      value: value.map(ToString::to_string),
+      remark: remark.map(ToString::to_string),
+    };
+    let rows = vec![upsert("a", Some("10"), None), upsert("b", None, Some("r20")), upsert("c", Some("3"), None)];
+    let n = base::upsert_many_with::<DictBmc, _>(&mm, rows, ConflictSpec::new(["name"])).await?;
+    assert_eq!(n, 3);
+
+    let rows: Vec<(String, Option<String>, Option<String>)> =
+      sqlx::query_as("SELECT name, value, remark FROM dict ORDER BY name").fetch_all(mm.dbx().db()).await?;
+    let some = |s: &str| Some(s.to_string());
+    assert_eq!(
+      rows,
+      vec![
+        ("a".to_string(), some("10"), some("r1")),
+        ("b".to_string(), some("2"), some("r20")),
+        ("c".to_string(), some("3"), None),
+      ]
+    );
+    Ok(())
+  }
+}
@@ -1,13 +1,29 @@
+use std::future::Future;
+
+use async_stream::try_stream;
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
 use modql::field::{HasSeaFields, SeaField, SeaFields};
 use modql::filter::{FilterGroups, ListOptions};
-use sea_query::{Condition, Expr, PostgresQueryBuilder, Query, SelectStatement};
-use sea_query_binder::SqlxBinder;
-use sqlx::postgres::PgRow;
+use modql::SIden;
+use sea_query::{
+  Condition, DynIden, Expr, InsertStatement, Order, Query, SelectStatement, TableRef, UpdateStatement, Value,
+};
+use sea_query_binder::{SqlxBinder, SqlxValues};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use sqlx::FromRow;
-use sqlx::Row;
-use ultimate_api::v1::{Page, PagePayload, Pagination};
-
-use crate::base::{prep_fields_for_create, prep_fields_for_update, CommonIden, DbBmc};
+use tracing::debug;
+use ultimate_api::v1::{CursorPage, CursorPagePayload, CursorPagination, CursorTotal, Page, PagePayload, Pagination};
+
+use crate::audit::{execute_audited, AuditOperation, AuditStatement};
+use crate::base::cursor::{apply_keyset_columns, keyset_condition, keyset_sorts, Cursor, CursorRow};
+use crate::base::{
+  apply_scopes, apply_scopes_with_deleted, prep_fields_for_create, prep_fields_for_optimistic_lock,
+  prep_fields_for_update, prep_id_for_create, prep_tenant_for_create, CommonIden, ConflictSpec, DbBmc, LogicalDeletion,
+};
+use crate::cache;
+use crate::store::{DbKind, DbQueryBuilder, DbRow, DB_KIND};
 use crate::{Error, Result};
 use crate::{Id, ModelManager};
 
@@ -21,55 +37,39 @@ where
   // -- Extract fields (name / sea-query value expression)
   let mut fields = data.not_none_sea_fields();
   fields = prep_fields_for_create::<MC>(fields, ctx);
+  fields = prep_id_for_create::<MC>(fields)?;
+  fields = prep_tenant_for_create::<MC>(mm, fields)?;
 
   // -- Build query
   let (columns, sea_values) = fields.for_sea_insert();
   let mut query = Query::insert();
-  query
-    .into_table(MC::table_ref())
-    .columns(columns)
-    .values(sea_values)?
-    .returning(Query::returning().columns([CommonIden::Id]));
+  query.into_table(mm.table_ref::<MC>()?).columns(columns).values(sea_values)?;
 
   // -- Exec query
-  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-  let sqlx_query = sqlx::query_as_with::<_, (i64,), _>(&sql, values);
-  // NOTE: For now, we will use the _txn for all create.
-  //       We could have a with_txn as function argument if perf is an issue (it should not be)
-  let (id,) = mm.dbx().fetch_one(sqlx_query).await?;
-  Ok(id)
+  let ids = _execute_returning_ids::<MC>(mm, AuditOperation::Create, query).await?;
+  ids.into_iter().next().ok_or(Error::CountFail)
 }
 
+/// 批量创建，返回的 ID 与输入数据一一对应。需要自增主键ID
+///
+/// MySQL 根据 `LAST_INSERT_ID()` 及插入的记录数推算 ID，要求批量插入的自增 ID 连续分配
 pub async fn create_many<MC, E>(mm: &ModelManager, data: Vec<E>) -> Result<Vec<i64>>
 where
   MC: DbBmc,
   E: HasSeaFields,
 {
-  let ctx = mm.ctx_ref()?;
-  let mut ids = Vec::with_capacity(data.len());
-
-  // Prepare insert query
-  let mut query = Query::insert();
-
-  for item in data {
-    let mut fields = item.not_none_sea_fields();
-    fields = prep_fields_for_create::<MC>(fields, ctx);
-    let (columns, sea_values) = fields.for_sea_insert();
-
-    // Append values for each item
-    query.into_table(MC::table_ref()).columns(columns.clone()).values(sea_values)?;
+  if data.is_empty() {
+    return Ok(Vec::new());
   }
+  let n = data.len();
 
-  query.returning(Query::returning().columns([CommonIden::Id]));
-
-  // Execute query
-  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-  let sqlx_query = sqlx::query_as_with::<_, (i64,), _>(&sql, values);
+  // Prepare insert query
+  let (query, _) = _build_insert::<MC, _>(mm, data)?;
 
-  let rows = mm.dbx().fetch_all(sqlx_query).await?;
-  for row in rows {
-    let (id,): (i64,) = row;
-    ids.push(id);
+  // Execute query. PostgreSQL、SQLite 按 VALUES 的顺序返回插入的行
+  let ids = _execute_returning_ids::<MC>(mm, AuditOperation::Create, query).await?;
+  if ids.len() != n {
+    return Err(Error::CountFail);
   }
 
   Ok(ids)
@@ -85,18 +85,16 @@ where
   // -- Extract fields (name / sea-query value expression)
   let mut fields = data.not_none_sea_fields();
   fields = prep_fields_for_create::<MC>(fields, ctx);
+  fields = prep_id_for_create::<MC>(fields)?;
+  fields = prep_tenant_for_create::<MC>(mm, fields)?;
 
   // -- Build query
   let (columns, sea_values) = fields.for_sea_insert();
   let mut query = Query::insert();
-  query.into_table(MC::table_ref()).columns(columns).values(sea_values)?;
-  // .returning(Query::returning().columns([CommonIden::Id]));
+  query.into_table(mm.table_ref::<MC>()?).columns(columns).values(sea_values)?;
 
   // -- Exec query
-  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-  let sqlx_query = sqlx::query_with(&sql, values);
-
-  let count = mm.dbx().execute(sqlx_query).await?;
+  let count = _execute::<MC, _>(mm, AuditOperation::Create, query, None).await?;
   if count == 1 {
     Ok(())
   } else {
@@ -110,36 +108,134 @@ where
   MC: DbBmc,
   E: HasSeaFields,
 {
-  let ctx = mm.ctx_ref()?;
-
   // Prepare insert query
-  let mut query = Query::insert();
-
-  for item in data {
-    let mut fields = item.not_none_sea_fields();
-    fields = prep_fields_for_create::<MC>(fields, ctx);
-    let (columns, sea_values) = fields.for_sea_insert();
-
-    // Append values for each item
-    query.into_table(MC::table_ref()).columns(columns).values(sea_values)?;
+  let (query, columns) = _build_insert::<MC, _>(mm, data)?;
+  if columns.is_empty() {
+    return Ok(0);
   }
 
   // Execute query
-  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-  let sqlx_query = sqlx::query_with(&sql, values);
-  let rows = mm.dbx().execute(sqlx_query).await?;
+  let rows = _execute::<MC, _>(mm, AuditOperation::Create, query, None).await?;
   Ok(rows)
 }
 
+/// 插入数据，冲突（[DbBmc::conflict_columns]）时更新已存在的数据，返回插入或更新的数据的 ID。需要 `i64` 类型的主键ID
+///
+/// 超出 [DbBmc::data_scope] 的已存在数据不会被更新，此时返回 [Error::NotFound]。
+/// MySQL 不支持指定冲突列，任一唯一索引冲突时都会更新。
+pub async fn upsert<MC, E>(mm: &ModelManager, data: E) -> Result<i64>
+where
+  MC: DbBmc,
+  E: HasSeaFields,
+{
+  upsert_with::<MC, E>(mm, data, ConflictSpec::of::<MC>()).await
+}
+
+/// 同 [upsert]，使用指定的冲突处理方式
+pub async fn upsert_with<MC, E>(mm: &ModelManager, data: E, conflict: ConflictSpec) -> Result<i64>
+where
+  MC: DbBmc,
+  E: HasSeaFields,
+{
+  // -- Build query
+  let (mut query, columns) = _build_insert::<MC, _>(mm, [data])?;
+  query.on_conflict(conflict.to_do_update::<MC>(mm, &columns)?).returning(Query::returning().columns([CommonIden::Id]));
+
+  // -- Exec query
+  let (sql, _) = query.build_sqlx(DbQueryBuilder);
+  match _execute_returning_ids::<MC>(mm, AuditOperation::Upsert, query).await?.first() {
+    Some(id) => Ok(*id),
+    None => Err(Error::NotFound { schema: MC::SCHEMA, table: MC::TABLE, sql }),
+  }
+}
+
+/// 批量插入数据，冲突（[DbBmc::conflict_columns]）时更新已存在的数据，返回插入或更新的记录数。
+///
+/// 各行设置的列不同时按列分组，每组生成一条语句并在同一事务中执行，冲突时只更新该行设置的列
+pub async fn upsert_many<MC, E>(mm: &ModelManager, data: impl IntoIterator<Item = E>) -> Result<u64>
+where
+  MC: DbBmc,
+  E: HasSeaFields,
+{
+  upsert_many_with::<MC, E>(mm, data, ConflictSpec::of::<MC>()).await
+}
+
+/// 同 [upsert_many]，使用指定的冲突处理方式
+pub async fn upsert_many_with<MC, E>(
+  mm: &ModelManager,
+  data: impl IntoIterator<Item = E>,
+  conflict: ConflictSpec,
+) -> Result<u64>
+where
+  MC: DbBmc,
+  E: HasSeaFields,
+{
+  // -- Build queries, one statement per column set
+  let mut queries = Vec::new();
+  for rows in _group_rows_by_columns(_prep_insert_rows::<MC, _>(mm, data)?) {
+    let (mut query, columns) = _build_insert_of_rows::<MC>(mm, rows)?;
+    query.on_conflict(conflict.to_do_update::<MC>(mm, &columns)?);
+    queries.push(query);
+  }
+
+  // -- Exec queries
+  match queries.len() {
+    0 => Ok(0),
+    1 => _execute::<MC, _>(mm, AuditOperation::Upsert, queries.remove(0), None).await,
+    _ => {
+      mm.transaction(|mm| {
+        let queries = queries.clone();
+        async move {
+          let mut n = 0;
+          for query in queries {
+            n += _execute::<MC, _>(&mm, AuditOperation::Upsert, query, None).await?;
+          }
+          Ok(n)
+        }
+      })
+      .await
+    }
+  }
+}
+
+/// 插入数据，冲突（[DbBmc::conflict_columns]）时忽略。返回是否插入
+pub async fn insert_ignore<MC, E>(mm: &ModelManager, data: E) -> Result<bool>
+where
+  MC: DbBmc,
+  E: HasSeaFields,
+{
+  insert_ignore_with::<MC, E>(mm, data, ConflictSpec::of::<MC>()).await
+}
+
+/// 同 [insert_ignore]，使用指定的冲突列。
+///
+/// MySQL 生成 `ON DUPLICATE KEY UPDATE <第一个冲突列> = <第一个冲突列>`：任一唯一索引冲突时都会忽略。
+/// sqlx 以 `CLIENT_FOUND_ROWS` 连接 MySQL，冲突时影响行数同样为 1，MySQL 下返回值无法区分是否插入
+pub async fn insert_ignore_with<MC, E>(mm: &ModelManager, data: E, conflict: ConflictSpec) -> Result<bool>
+where
+  MC: DbBmc,
+  E: HasSeaFields,
+{
+  // -- Build query
+  let (mut query, _) = _build_insert::<MC, _>(mm, [data])?;
+  query.on_conflict(conflict.to_do_nothing::<MC>()?);
+
+  // -- Exec query
+  let n = _execute::<MC, _>(mm, AuditOperation::Create, query, None).await?;
+  Ok(n == 1)
+}
+
 pub async fn find_by_id<MC, E>(mm: &ModelManager, id: Id) -> Result<E>
 where
   MC: DbBmc,
-  E: for<'r> FromRow<'r, PgRow> + Unpin + Send,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
   E: HasSeaFields,
 {
   let filter: FilterGroups = match id {
     #[cfg(feature = "uuid")]
-    Id::Uuid(id) => crate::IdUuidFilter { id: Some(modql::filter::OpValString::Eq(id.to_string()).into()) }.into(),
+    Id::Uuid(id) if DB_KIND == DbKind::Postgres => {
+      crate::IdUuidFilter { id: Some(modql::filter::OpValString::Eq(id.to_string()).into()) }.into()
+    }
     _ => id.to_filter_node("id").into(),
   };
   find_unique::<MC, E, _>(mm, filter).await?.ok_or_else(|| Error::EntityNotFound {
@@ -152,31 +248,63 @@ where
 pub async fn find_unique<MC, E, F>(mm: &ModelManager, filter: F) -> Result<Option<E>>
 where
   MC: DbBmc,
-  E: for<'r> FromRow<'r, PgRow> + Unpin + Send,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
   E: HasSeaFields,
   F: Into<FilterGroups>,
 {
-  // -- Build the query
-  let mut query = Query::select();
-  query.from(MC::table_ref()).columns(E::sea_column_refs());
-
-  // condition from filter
-  let filters: FilterGroups = filter.into();
-  let cond: Condition = filters.try_into()?;
-  query.cond_where(cond);
+  let (sql, values) = _build_unique_query::<MC, E, F>(mm, filter)?;
 
   // -- Execute the query
-  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
   let sqlx_query = sqlx::query_as_with::<_, E, _>(&sql, values);
-  let entity = mm.dbx().fetch_optional(sqlx_query).await?;
+  let entity = mm.dbx_of::<MC>()?.fetch_optional(sqlx_query).await?;
 
   Ok(entity)
 }
 
+/// 同 [find_by_id]，启用缓存（[DbBmc::cache]）时优先从缓存读取
+pub async fn find_by_id_cached<MC, E>(mm: &ModelManager, id: Id) -> Result<E>
+where
+  MC: DbBmc,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
+  E: HasSeaFields + Serialize + DeserializeOwned,
+{
+  let filter: FilterGroups = match id {
+    #[cfg(feature = "uuid")]
+    Id::Uuid(id) if DB_KIND == DbKind::Postgres => {
+      crate::IdUuidFilter { id: Some(modql::filter::OpValString::Eq(id.to_string()).into()) }.into()
+    }
+    _ => id.to_filter_node("id").into(),
+  };
+  find_unique_cached::<MC, E, _>(mm, filter).await?.ok_or_else(|| Error::EntityNotFound {
+    schema: MC::SCHEMA,
+    entity: MC::TABLE,
+    id,
+  })
+}
+
+/// 同 [find_unique]，启用缓存（[DbBmc::cache]）时优先从缓存读取。查询结果为空时同样会被缓存
+pub async fn find_unique_cached<MC, E, F>(mm: &ModelManager, filter: F) -> Result<Option<E>>
+where
+  MC: DbBmc,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
+  E: HasSeaFields + Serialize + DeserializeOwned,
+  F: Into<FilterGroups>,
+{
+  let (sql, values) = _build_unique_query::<MC, E, F>(mm, filter)?;
+  let key = cache::query_key::<MC>(mm, &sql, &values)?;
+
+  cache::get_or_load::<MC, _, _, _>(mm, key, || async {
+    let sqlx_query = sqlx::query_as_with::<_, E, _>(&sql, values);
+    let entity = mm.dbx_of::<MC>()?.fetch_optional(sqlx_query).await?;
+    Ok(entity)
+  })
+  .await
+}
+
 pub async fn find_first<MC, E, F>(mm: &ModelManager, filter: F) -> Result<Option<E>>
 where
   MC: DbBmc,
-  E: for<'r> FromRow<'r, PgRow> + Unpin + Send,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
   E: HasSeaFields,
   F: Into<FilterGroups>,
 {
@@ -187,28 +315,29 @@ where
 pub async fn find_many<MC, E, F>(mm: &ModelManager, filter: F, list_options: Option<ListOptions>) -> Result<Vec<E>>
 where
   MC: DbBmc,
-  E: for<'r> FromRow<'r, PgRow> + Unpin + Send,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
   E: HasSeaFields,
   F: Into<FilterGroups>,
 {
   // -- Build the query
   let mut query = Query::select();
-  query.from(MC::table_ref()).columns(E::sea_column_refs());
+  query.from(mm.table_ref::<MC>()?).columns(E::sea_column_refs());
 
   // condition from filter
   let filters: FilterGroups = filter.into();
   let cond: Condition = filters.try_into()?;
   query.cond_where(cond);
+  apply_scopes::<MC, _>(mm, &mut query)?;
 
   // list options
   let list_options = compute_list_options::<MC>(list_options)?;
   list_options.apply_to_sea_query(&mut query);
 
   // -- Execute the query
-  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+  let (sql, values) = query.build_sqlx(DbQueryBuilder);
 
   let sqlx_query = sqlx::query_as_with::<_, E, _>(&sql, values);
-  let entities = mm.dbx().fetch_all(sqlx_query).await?;
+  let entities = mm.dbx_of::<MC>()?.fetch_all(sqlx_query).await?;
 
   Ok(entities)
 }
@@ -216,22 +345,23 @@ where
 pub async fn find_many_on<MC, E, F>(mm: &ModelManager, f: F) -> Result<Vec<E>>
 where
   MC: DbBmc,
-  E: for<'r> FromRow<'r, PgRow> + Unpin + Send,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
   E: HasSeaFields,
   F: FnOnce(&mut SelectStatement) -> Result<()>,
 {
   // -- Build the query
   let mut query = Query::select();
-  query.from(MC::table_ref()).columns(E::sea_column_refs());
+  query.from(mm.table_ref::<MC>()?).columns(E::sea_column_refs());
 
   // condition from filter and list options
   f(&mut query)?;
+  apply_scopes::<MC, _>(mm, &mut query)?;
 
   // -- Execute the query
-  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+  let (sql, values) = query.build_sqlx(DbQueryBuilder);
 
   let sqlx_query = sqlx::query_as_with::<_, E, _>(&sql, values);
-  let entities = mm.dbx().fetch_all(sqlx_query).await?;
+  let entities = mm.dbx_of::<MC>()?.fetch_all(sqlx_query).await?;
 
   Ok(entities)
 }
@@ -241,20 +371,18 @@ where
   MC: DbBmc,
   F: Into<FilterGroups>,
 {
-  let db = mm.dbx().db();
   // -- Build the query
-  let mut query = Query::select().from(MC::table_ref()).expr(Expr::col(sea_query::Asterisk).count()).to_owned();
+  let mut query = Query::select().from(mm.table_ref::<MC>()?).expr(Expr::col(sea_query::Asterisk).count()).to_owned();
 
   // condition from filter
   let filters: FilterGroups = filter.into();
   let cond: Condition = filters.try_into()?;
   query.cond_where(cond);
+  apply_scopes::<MC, _>(mm, &mut query)?;
 
-  let query_str = query.to_string(PostgresQueryBuilder);
-
-  let result = sqlx::query(&query_str).fetch_one(db).await.map_err(|_| Error::CountFail)?;
-
-  let count: i64 = result.try_get("count").map_err(|_| Error::CountFail)?;
+  let (sql, values) = query.build_sqlx(DbQueryBuilder);
+  let sqlx_query = sqlx::query_as_with::<_, (i64,), _>(&sql, values);
+  let (count,) = mm.dbx_of::<MC>()?.fetch_one(sqlx_query).await.map_err(|_| Error::CountFail)?;
   Ok(count)
 }
 
@@ -265,19 +393,20 @@ where
 {
   // -- Build the query
   let mut query = Query::select();
-  query.from(MC::table_ref());
+  query.from(mm.table_ref::<MC>()?);
   query.expr(Expr::col(sea_query::Asterisk).count());
 
   // -- condition from filter
   f(&mut query)?;
+  apply_scopes::<MC, _>(mm, &mut query)?;
 
   // -- Generate sql and values
-  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-  println!("sql: {}, values: {:?}", sql, values);
+  let (sql, values) = query.build_sqlx(DbQueryBuilder);
+  debug!("sql: {}, values: {:?}", sql, values);
   let sqlx_query = sqlx::query_as_with::<_, (i64,), _>(&sql, values);
 
   // -- Execute the query
-  let (count,) = mm.dbx().fetch_one(sqlx_query).await.map_err(|_| Error::CountFail)?;
+  let (count,) = mm.dbx_of::<MC>()?.fetch_one(sqlx_query).await.map_err(|_| Error::CountFail)?;
   Ok(count)
 }
 
@@ -285,7 +414,7 @@ pub async fn page<MC, E, F>(mm: &ModelManager, filter: F, pagination: Pagination
 where
   MC: DbBmc,
   F: Into<FilterGroups>,
-  E: for<'r> FromRow<'r, PgRow> + Unpin + Send,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
   E: HasSeaFields,
 {
   let filter: FilterGroups = filter.into();
@@ -295,6 +424,226 @@ where
   Ok(PagePayload::new(Page::new(&pagination, total_size), items))
 }
 
+/// 游标（键集）分页，查询代价不随页数增加。
+///
+/// 总是以 `id` 作为最后一个排序列，排序列的值为 NULL 时视为最大值（升序时排在最后）。排序列不是 `E` 的字段时返回 [Error::InvalidArgument]，
+/// 游标与请求的排序不一致时返回 [Error::InvalidCursor]。
+pub async fn page_by_cursor<MC, E, F>(
+  mm: &ModelManager,
+  filter: F,
+  pagination: CursorPagination,
+) -> Result<CursorPagePayload<E>>
+where
+  MC: DbBmc,
+  F: Into<FilterGroups>,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
+  E: HasSeaFields,
+{
+  let page_size = pagination.page_size();
+  if page_size > MC::LIST_LIMIT_MAX {
+    return Err(Error::ListLimitOverMax { max: MC::LIST_LIMIT_MAX, actual: page_size });
+  }
+  let sorts = keyset_sorts(&pagination.sort_bys, E::field_names())?;
+  let cursor = match pagination.cursor.as_deref().filter(|c| !c.is_empty()) {
+    Some(c) => Some(mm.cursor_codec().decode(c)?),
+    None => None,
+  };
+  if cursor.as_ref().is_some_and(|c| c.sorts != sorts) {
+    return Err(Error::InvalidCursor);
+  }
+  let forward = cursor.as_ref().map_or(true, |c| c.next);
+
+  // -- Build the query
+  let filters: FilterGroups = filter.into();
+  let cond: Condition = filters.clone().try_into()?;
+  let mut query = Query::select();
+  query.from(mm.table_ref::<MC>()?).columns(E::sea_column_refs()).cond_where(cond);
+  if let Some(cursor) = &cursor {
+    query.cond_where(keyset_condition(&sorts, &cursor.keys, forward)?);
+  }
+  apply_scopes::<MC, _>(mm, &mut query)?;
+  apply_keyset_columns(&mut query, &sorts, forward);
+  query.limit(page_size as u64 + 1);
+
+  // -- Execute the query
+  let (sql, values) = query.build_sqlx(DbQueryBuilder);
+  let sqlx_query = sqlx::query_as_with::<_, CursorRow<E>, _>(&sql, values);
+  let mut rows = mm.dbx_of::<MC>()?.fetch_all(sqlx_query).await?;
+
+  let has_more = rows.len() as i64 > page_size;
+  rows.truncate(page_size as usize);
+  if !forward {
+    rows.reverse();
+  }
+  let (has_next, has_prev) = if forward { (has_more, cursor.is_some()) } else { (true, has_more) };
+  let encode = |row: Option<&CursorRow<E>>, next: bool| match row {
+    Some(row) => mm.cursor_codec().encode(&Cursor { next, sorts: sorts.clone(), keys: row.keys.clone() }).map(Some),
+    None => Ok(None),
+  };
+  let next_cursor = if has_next { encode(rows.last(), true)? } else { None };
+  let prev_cursor = if has_prev { encode(rows.first(), false)? } else { None };
+
+  let total_size = match pagination.total() {
+    CursorTotal::NONE => None,
+    CursorTotal::EXACT => Some(count::<MC, _>(mm, filters).await?),
+    CursorTotal::APPROXIMATE => _approximate_count::<MC>(mm).await?,
+  };
+
+  let page = CursorPage { page_size, next_cursor, prev_cursor, total_size };
+  Ok(CursorPagePayload::new(page, rows.into_iter().map(|row| row.entity).collect()))
+}
+
+/// 根据统计信息（PostgreSQL 为 `pg_class`，MySQL 为 `information_schema.TABLES`）估算表的记录数，
+/// 未统计或 SQLite 时返回 None
+async fn _approximate_count<MC>(mm: &ModelManager) -> Result<Option<i64>>
+where
+  MC: DbBmc,
+{
+  let (schema, table) = match mm.table_ref::<MC>()? {
+    TableRef::SchemaTable(schema, table) => (schema.to_string(), table.to_string()),
+    _ => (MC::SCHEMA.to_string(), MC::TABLE.to_string()),
+  };
+  let sqlx_query = match DB_KIND {
+    DbKind::Postgres => {
+      sqlx::query_as::<_, (Option<i64>,)>("SELECT reltuples::bigint FROM pg_class WHERE oid = to_regclass($1)")
+        .bind(format!(r#""{}"."{}""#, schema, table))
+    }
+    DbKind::MySql => sqlx::query_as::<_, (Option<i64>,)>(
+      "SELECT CAST(TABLE_ROWS AS SIGNED) FROM information_schema.TABLES WHERE TABLE_SCHEMA = ? AND TABLE_NAME = ?",
+    )
+    .bind(schema)
+    .bind(table),
+    DbKind::Sqlite => return Ok(None),
+  };
+  let total = mm.dbx_of::<MC>()?.fetch_optional(sqlx_query).await?;
+  Ok(total.and_then(|(n,)| n).filter(|n| *n >= 0))
+}
+
+/// 查询结果流，见 [find_stream]、[find_cursor]
+pub type EntityStream<E> = BoxStream<'static, Result<E>>;
+
+/// 以流的方式查询数据，用于导出等大结果集场景，内存占用与结果集大小无关。
+///
+/// 不受 [DbBmc::LIST_LIMIT_MAX] 限制，`list_options` 未设置 `limit` 时返回所有数据。
+/// 存在事务时在事务内查询，迭代期间不能在同一事务内执行其它查询。
+pub fn find_stream<MC, E, F>(mm: &ModelManager, filter: F, list_options: Option<ListOptions>) -> Result<EntityStream<E>>
+where
+  MC: DbBmc,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send + 'static,
+  E: HasSeaFields,
+  F: Into<FilterGroups>,
+{
+  let (sql, values) = _build_stream_query::<MC, E, F>(mm, filter, list_options)?;
+  let dbx = mm.dbx_of::<MC>()?.clone();
+  Ok(Box::pin(try_stream! {
+    let sqlx_query = sqlx::query_as_with::<_, E, _>(&sql, values);
+    let mut rows = dbx.fetch_stream(sqlx_query);
+    while let Some(entity) = rows.try_next().await? {
+      yield entity;
+    }
+  }))
+}
+
+/// 同 [find_stream]，使用服务端游标每次读取 `batch_size` 行
+pub fn find_cursor<MC, E, F>(
+  mm: &ModelManager,
+  filter: F,
+  list_options: Option<ListOptions>,
+  batch_size: u32,
+) -> Result<EntityStream<E>>
+where
+  MC: DbBmc,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send + 'static,
+  E: HasSeaFields,
+  F: Into<FilterGroups>,
+{
+  let (sql, values) = _build_stream_query::<MC, E, F>(mm, filter, list_options)?;
+  let rows = mm.dbx_of::<MC>()?.fetch_cursor(sql, values, batch_size);
+  Ok(Box::pin(rows.map_err(Error::from)))
+}
+
+/// 按 `id` 升序分批（键集分页）处理匹配过滤条件的数据，`key` 返回数据的 `id`。返回处理的记录数
+///
+/// 与 `offset` 分页不同，每批查询的代价不随已处理的数据量增加，且处理过程中的插入、删除不会导致遗漏或重复。
+pub async fn for_each_batch<MC, E, F, K, V, H, Fut>(
+  mm: &ModelManager,
+  filter: F,
+  batch_size: u64,
+  key: K,
+  handler: H,
+) -> Result<u64>
+where
+  MC: DbBmc,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
+  E: HasSeaFields,
+  F: Into<FilterGroups>,
+  K: Fn(&E) -> V,
+  V: Into<Value>,
+  H: FnMut(Vec<E>) -> Fut,
+  Fut: Future<Output = Result<()>>,
+{
+  for_each_batch_by::<MC, E, F, K, V, H, Fut>(mm, filter, "id", batch_size, key, handler).await
+}
+
+/// 同 [for_each_batch]，按 `column` 列升序分批，`key` 返回数据在该列的值。`column` 的值必须唯一且非空
+pub async fn for_each_batch_by<MC, E, F, K, V, H, Fut>(
+  mm: &ModelManager,
+  filter: F,
+  column: &'static str,
+  batch_size: u64,
+  key: K,
+  mut handler: H,
+) -> Result<u64>
+where
+  MC: DbBmc,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
+  E: HasSeaFields,
+  F: Into<FilterGroups>,
+  K: Fn(&E) -> V,
+  V: Into<Value>,
+  H: FnMut(Vec<E>) -> Fut,
+  Fut: Future<Output = Result<()>>,
+{
+  let batch_size = batch_size.max(1);
+  let filters: FilterGroups = filter.into();
+  let cond: Condition = filters.try_into()?;
+
+  let mut last: Option<Value> = None;
+  let mut total = 0;
+  loop {
+    // -- Build the query
+    let mut query = Query::select();
+    query
+      .from(mm.table_ref::<MC>()?)
+      .columns(E::sea_column_refs())
+      .cond_where(cond.clone())
+      .order_by(SIden(column), Order::Asc)
+      .limit(batch_size);
+    if let Some(last) = last.take() {
+      query.and_where(Expr::col(SIden(column)).gt(last));
+    }
+    apply_scopes::<MC, _>(mm, &mut query)?;
+
+    // -- Execute the query
+    let (sql, values) = query.build_sqlx(DbQueryBuilder);
+    let sqlx_query = sqlx::query_as_with::<_, E, _>(&sql, values);
+    let entities = mm.dbx_of::<MC>()?.fetch_all(sqlx_query).await?;
+
+    let Some(entity) = entities.last() else {
+      break;
+    };
+    last = Some(key(entity).into());
+    let n = entities.len() as u64;
+    total += n;
+    handler(entities).await?;
+    if n < batch_size {
+      break;
+    }
+  }
+
+  Ok(total)
+}
+
 pub async fn update_by_id<MC, E>(mm: &ModelManager, id: Id, data: E) -> Result<()>
 where
   MC: DbBmc,
@@ -308,21 +657,35 @@ where
     fields = prep_fields_for_update::<MC>(fields, ctx);
   }
 
+  let (fields, version) = prep_fields_for_optimistic_lock::<MC>(fields)?;
+
   // -- Build query
-  let fields = fields.for_sea_update();
   let mut query = Query::update();
-  query.table(MC::table_ref()).values(fields).and_where(Expr::col(CommonIden::Id).eq(id.clone()));
+  query.table(mm.table_ref::<MC>()?).values(fields).and_where(Expr::col(CommonIden::Id).eq(id.clone()));
+  if let Some(version) = version.clone() {
+    query.and_where(Expr::col(CommonIden::OptimisticLock).eq(version));
+  }
+  apply_scopes::<MC, _>(mm, &mut query)?;
 
   // -- Execute query
-  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-  let sqlx_query = sqlx::query_with(&sql, values);
-  let count = mm.dbx().execute(sqlx_query).await?;
+  let before = Condition::all().add(Expr::col(CommonIden::Id).eq(id.clone()));
+  let count = _execute::<MC, _>(mm, AuditOperation::Update, query, Some(before)).await?;
 
   // -- Check result
+  if count == 0 && version.is_some() {
+    // 数据存在但版本号不一致时为乐观锁冲突。从主库检查，避免副本复制延迟将冲突误报为不存在
+    let filter: FilterGroups = id.to_filter_node("id").into();
+    if crate::base::count::<MC, _>(&mm.read_primary(), filter).await? > 0 {
+      return Err(Error::OptimisticLockConflict { schema: MC::SCHEMA, table: MC::TABLE });
+    }
+  }
   _check_result::<MC>(count, id)
 }
 
-/// 根据过滤条件更新，返回更新的记录数
+/// 根据过滤条件更新，返回更新的记录数。
+///
+/// `data` 带有乐观锁版本号时，存在匹配的记录但版本号都不一致返回 [Error::OptimisticLockConflict]，
+/// 没有匹配的记录时返回 0
 pub async fn update<MC, E, F>(mm: &ModelManager, filter: F, data: E) -> Result<u64>
 where
   MC: DbBmc,
@@ -337,94 +700,366 @@ where
     fields = prep_fields_for_update::<MC>(fields, ctx);
   }
 
+  let (fields, version) = prep_fields_for_optimistic_lock::<MC>(fields)?;
+
   // -- Build query
-  let fields = fields.for_sea_update();
   let mut query = Query::update();
-  query.table(MC::table_ref()).values(fields);
+  query.table(mm.table_ref::<MC>()?).values(fields);
   let filters: FilterGroups = filter.into();
   let cond: Condition = filters.try_into()?;
-  query.cond_where(cond);
+  query.cond_where(cond.clone());
+  if let Some(version) = version.clone() {
+    query.and_where(Expr::col(CommonIden::OptimisticLock).eq(version));
+  }
+  apply_scopes::<MC, _>(mm, &mut query)?;
 
   // -- Execute query
-  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-  let sqlx_query = sqlx::query_with(&sql, values);
-  let count = mm.dbx().execute(sqlx_query).await?;
-
+  let count = _execute::<MC, _>(mm, AuditOperation::Update, query, Some(cond.clone())).await?;
+
+  if count == 0 && version.is_some() {
+    // 存在匹配的数据但版本号不一致时为乐观锁冲突，没有匹配的数据时返回 0。从主库检查，避免副本复制延迟
+    let matched = count_on::<MC, _>(&mm.read_primary(), |q| {
+      q.cond_where(cond);
+      Ok(())
+    })
+    .await?;
+    if matched > 0 {
+      return Err(Error::OptimisticLockConflict { schema: MC::SCHEMA, table: MC::TABLE });
+    }
+  }
   Ok(count)
 }
 
 pub async fn delete_by_id<MC>(mm: &ModelManager, id: Id) -> Result<()>
+where
+  MC: DbBmc,
+{
+  // -- Build & execute query
+  let cond = Condition::all().add(Expr::col(CommonIden::Id).eq(id.clone()));
+  let count = _delete::<MC>(mm, cond).await?;
+
+  _check_result::<MC>(count, id)
+}
+
+pub async fn delete_by_ids<MC>(mm: &ModelManager, ids: Vec<Id>) -> Result<u64>
+where
+  MC: DbBmc,
+{
+  if ids.is_empty() {
+    return Ok(0);
+  }
+
+  // -- Build & execute query
+  let cond = Condition::all().add(Expr::col(CommonIden::Id).is_in(ids));
+  let n = _delete::<MC>(mm, cond).await?;
+
+  Ok(n)
+}
+
+pub async fn delete<MC, E, F>(mm: &ModelManager, filter: F) -> Result<u64>
+where
+  MC: DbBmc,
+  F: Into<FilterGroups>,
+{
+  let filters: FilterGroups = filter.into();
+  let cond: Condition = filters.try_into()?;
+
+  // -- Build & execute query
+  let n = _delete::<MC>(mm, cond).await?;
+
+  Ok(n)
+}
+
+/// 查询数据，包括已逻辑删除的数据
+pub async fn find_with_deleted<MC, E, F>(
+  mm: &ModelManager,
+  filter: F,
+  list_options: Option<ListOptions>,
+) -> Result<Vec<E>>
+where
+  MC: DbBmc,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
+  E: HasSeaFields,
+  F: Into<FilterGroups>,
+{
+  // -- Build the query
+  let mut query = Query::select();
+  query.from(mm.table_ref::<MC>()?).columns(E::sea_column_refs());
+
+  // condition from filter
+  let filters: FilterGroups = filter.into();
+  let cond: Condition = filters.try_into()?;
+  query.cond_where(cond);
+  apply_scopes_with_deleted::<MC, _>(mm, &mut query)?;
+
+  // list options
+  let list_options = compute_list_options::<MC>(list_options)?;
+  list_options.apply_to_sea_query(&mut query);
+
+  // -- Execute the query
+  let (sql, values) = query.build_sqlx(DbQueryBuilder);
+  let sqlx_query = sqlx::query_as_with::<_, E, _>(&sql, values);
+  let entities = mm.dbx_of::<MC>()?.fetch_all(sqlx_query).await?;
+
+  Ok(entities)
+}
+
+/// 恢复已逻辑删除的数据
+pub async fn restore_by_id<MC>(mm: &ModelManager, id: Id) -> Result<()>
 where
   MC: DbBmc,
 {
   let ctx = mm.ctx_ref()?;
+  let logical_deletion = _require_logical_deletion::<MC>()?;
 
-  // -- Build query
-  let (sql, values) = if MC::use_logical_deletion() {
-    // -- Prep Fields
-    let mut fields = SeaFields::new(vec![SeaField::new(CommonIden::LogiscalDeletion, true)]);
-    if MC::has_modification_timestamps() {
-      fields = prep_fields_for_update::<MC>(fields, ctx);
-    }
+  // -- Prep Fields
+  let mut fields =
+    SeaFields::new(vec![SeaField::new(SIden(logical_deletion.column()), logical_deletion.restored_value())]);
+  if MC::has_modification_timestamps() {
+    fields = prep_fields_for_update::<MC>(fields, ctx);
+  }
 
-    let fields = fields.for_sea_update();
-    Query::update()
-      .table(MC::table_ref())
-      .values(fields)
-      .and_where(Expr::col(CommonIden::Id).eq(id.clone()))
-      .build_sqlx(PostgresQueryBuilder)
-  } else {
-    Query::delete()
-      .from_table(MC::table_ref())
-      .and_where(Expr::col(CommonIden::Id).eq(id.clone()))
-      .build_sqlx(PostgresQueryBuilder)
-  };
+  // -- Build query
+  let mut query = Query::update();
+  query
+    .table(mm.table_ref::<MC>()?)
+    .values(fields.for_sea_update())
+    .and_where(Expr::col(CommonIden::Id).eq(id.clone()))
+    .cond_where(logical_deletion.deleted_condition());
+  apply_scopes_with_deleted::<MC, _>(mm, &mut query)?;
 
   // -- Execute query
-  // let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-  let sqlx_query = sqlx::query_with(&sql, values);
-  let count = mm.dbx().execute(sqlx_query).await?;
+  let before = Condition::all().add(Expr::col(CommonIden::Id).eq(id.clone()));
+  let count = _execute::<MC, _>(mm, AuditOperation::Restore, query, Some(before)).await?;
 
   _check_result::<MC>(count, id)
 }
 
-pub async fn delete_by_ids<MC>(mm: &ModelManager, ids: Vec<Id>) -> Result<u64>
+/// 物理删除匹配过滤条件且已逻辑删除的数据，返回删除的记录数
+pub async fn purge<MC, F>(mm: &ModelManager, filter: F) -> Result<u64>
 where
   MC: DbBmc,
+  F: Into<FilterGroups>,
 {
-  if ids.is_empty() {
-    return Ok(0);
-  }
+  let logical_deletion = _require_logical_deletion::<MC>()?;
 
   // -- Build query
+  let filters: FilterGroups = filter.into();
+  let cond: Condition = filters.try_into()?;
   let mut query = Query::delete();
-  query.from_table(MC::table_ref()).and_where(Expr::col(CommonIden::Id).is_in(ids));
+  query.from_table(mm.table_ref::<MC>()?).cond_where(cond).cond_where(logical_deletion.deleted_condition());
+  apply_scopes_with_deleted::<MC, _>(mm, &mut query)?;
 
   // -- Execute query
-  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-  let sqlx_query = sqlx::query_with(&sql, values);
-  let n = mm.dbx().execute(sqlx_query).await?;
+  let n = _execute::<MC, _>(mm, AuditOperation::Delete, query, None).await?;
 
   Ok(n)
 }
 
-pub async fn delete<MC, E, F>(mm: &ModelManager, filter: F) -> Result<u64>
+/// 删除匹配条件的数据，使用逻辑删除时设置逻辑删除列。返回删除的记录数
+async fn _delete<MC>(mm: &ModelManager, cond: Condition) -> Result<u64>
+where
+  MC: DbBmc,
+{
+  if MC::use_logical_deletion() {
+    let mut query = _logical_deletion_update::<MC>(mm)?;
+    query.cond_where(cond.clone());
+    apply_scopes::<MC, _>(mm, &mut query)?;
+    _execute::<MC, _>(mm, AuditOperation::Delete, query, Some(cond)).await
+  } else {
+    let mut query = Query::delete();
+    query.from_table(mm.table_ref::<MC>()?).cond_where(cond);
+    apply_scopes::<MC, _>(mm, &mut query)?;
+    _execute::<MC, _>(mm, AuditOperation::Delete, query, None).await
+  }
+}
+
+/// 执行写语句，返回受影响的记录数。
+///
+/// 启用审计（[DbBmc::audit]）时在同一事务内写入审计记录，`before` 为查询修改前数据的过滤条件；
+/// 启用缓存（[DbBmc::cache]）且有数据被修改时使表的缓存失效
+async fn _execute<MC, S>(
+  mm: &ModelManager,
+  operation: AuditOperation,
+  query: S,
+  before: Option<Condition>,
+) -> Result<u64>
+where
+  MC: DbBmc,
+  S: AuditStatement,
+{
+  let n = if MC::audit().is_some() {
+    execute_audited::<MC, _>(mm, operation, query, before).await?.len() as u64
+  } else {
+    let (sql, values) = query.build_sqlx(DbQueryBuilder);
+    let sqlx_query = sqlx::query_with(&sql, values);
+    mm.dbx_of::<MC>()?.execute(sqlx_query).await?
+  };
+
+  _invalidate_cache::<MC>(mm, n).await?;
+  Ok(n)
+}
+
+/// 执行插入语句，返回插入（或更新）的数据的 ID。启用审计、缓存时同 [_execute]。
+///
+/// MySQL 不支持 `RETURNING`，根据 `LAST_INSERT_ID()` 及插入的记录数推算 ID：批量插入时自增 ID 连续分配，
+/// `upsert` 时通过 `id = LAST_INSERT_ID(id)` 取得被更新的数据的 ID
+async fn _execute_returning_ids<MC>(
+  mm: &ModelManager,
+  operation: AuditOperation,
+  query: InsertStatement,
+) -> Result<Vec<i64>>
+where
+  MC: DbBmc,
+{
+  let ids = if MC::audit().is_some() {
+    let ids = execute_audited::<MC, _>(mm, operation, query, None).await?;
+    ids.iter().map(|id| id.parse().map_err(|_| Error::CountFail)).collect::<Result<Vec<i64>>>()?
+  } else if DB_KIND.supports_returning() {
+    let mut query = query;
+    query.returning(Query::returning().columns([CommonIden::Id]));
+    let (sql, values) = query.build_sqlx(DbQueryBuilder);
+    let sqlx_query = sqlx::query_as_with::<_, (i64,), _>(&sql, values);
+    let rows = mm.dbx_of::<MC>()?.fetch_all(sqlx_query).await?;
+    rows.into_iter().map(|(id,)| id).collect()
+  } else {
+    let (sql, values) = query.build_sqlx(DbQueryBuilder);
+    let (n, first_id) = mm.dbx_of::<MC>()?.execute_insert(sqlx::query_with(&sql, values)).await?;
+    match first_id {
+      Some(id) if operation == AuditOperation::Upsert && n > 0 => vec![id],
+      Some(id) => (id..id + n as i64).collect(),
+      None => Vec::new(),
+    }
+  };
+
+  _invalidate_cache::<MC>(mm, ids.len() as u64).await?;
+  Ok(ids)
+}
+
+/// 启用缓存（[DbBmc::cache]）且有数据被修改时使表的缓存失效
+async fn _invalidate_cache<MC>(mm: &ModelManager, affected: u64) -> Result<()>
+where
+  MC: DbBmc,
+{
+  if affected > 0 && MC::cache().is_some() {
+    cache::invalidate::<MC>(mm).await?;
+  }
+  Ok(())
+}
+
+/// 构建插入语句，返回插入的列。
+///
+/// 各条数据的列取并集，某条数据未设置的列使用 `DEFAULT`，以保证每行的值与列对应。
+/// SQLite 不支持 `DEFAULT`，各条数据需设置相同的列。
+fn _build_insert<MC, E>(mm: &ModelManager, data: impl IntoIterator<Item = E>) -> Result<(InsertStatement, Vec<DynIden>)>
 where
   MC: DbBmc,
+  E: HasSeaFields,
+{
+  _build_insert_of_rows::<MC>(mm, _prep_insert_rows::<MC, _>(mm, data)?)
+}
+
+fn _prep_insert_rows<MC, E>(mm: &ModelManager, data: impl IntoIterator<Item = E>) -> Result<Vec<Vec<SeaField>>>
+where
+  MC: DbBmc,
+  E: HasSeaFields,
+{
+  let ctx = mm.ctx_ref()?;
+  data
+    .into_iter()
+    .map(|item| {
+      let mut fields = item.not_none_sea_fields();
+      fields = prep_fields_for_create::<MC>(fields, ctx);
+      fields = prep_id_for_create::<MC>(fields)?;
+      fields = prep_tenant_for_create::<MC>(mm, fields)?;
+      Ok(fields.into_vec())
+    })
+    .collect()
+}
+
+/// 按设置的列（不计顺序）分组，保持各组首次出现的顺序
+fn _group_rows_by_columns(rows: Vec<Vec<SeaField>>) -> Vec<Vec<Vec<SeaField>>> {
+  let mut groups: Vec<(Vec<String>, Vec<Vec<SeaField>>)> = Vec::new();
+  for fields in rows {
+    let mut key: Vec<String> = fields.iter().map(|f| f.iden.to_string()).collect();
+    key.sort_unstable();
+    match groups.iter_mut().find(|(k, _)| *k == key) {
+      Some((_, rows)) => rows.push(fields),
+      None => groups.push((key, vec![fields])),
+    }
+  }
+  groups.into_iter().map(|(_, rows)| rows).collect()
+}
+
+fn _build_insert_of_rows<MC>(mm: &ModelManager, rows: Vec<Vec<SeaField>>) -> Result<(InsertStatement, Vec<DynIden>)>
+where
+  MC: DbBmc,
+{
+  let mut columns: Vec<DynIden> = Vec::new();
+  for f in rows.iter().flatten() {
+    if !columns.iter().any(|c| c.to_string() == f.iden.to_string()) {
+      columns.push(f.iden.clone());
+    }
+  }
+
+  let mut query = Query::insert();
+  query.into_table(mm.table_ref::<MC>()?).columns(columns.clone());
+  for mut fields in rows {
+    if DB_KIND == DbKind::Sqlite && fields.len() != columns.len() {
+      return Err(Error::InvalidArgument {
+        message: format!("SQLite requires all rows to set the same columns, table: '{}'", MC::TABLE),
+      });
+    }
+    let values = columns.iter().map(|col| match fields.iter().position(|f| f.iden.to_string() == col.to_string()) {
+      Some(i) => fields.swap_remove(i).value,
+      None => Expr::cust("DEFAULT"),
+    });
+    query.values(values.collect::<Vec<_>>())?;
+  }
+  Ok((query, columns))
+}
+
+fn _build_unique_query<MC, E, F>(mm: &ModelManager, filter: F) -> Result<(String, SqlxValues)>
+where
+  MC: DbBmc,
+  E: HasSeaFields,
   F: Into<FilterGroups>,
 {
-  let mut query = Query::delete();
-  query.from_table(MC::table_ref());
+  let mut query = Query::select();
+  query.from(mm.table_ref::<MC>()?).columns(E::sea_column_refs());
+
+  // condition from filter
   let filters: FilterGroups = filter.into();
   let cond: Condition = filters.try_into()?;
   query.cond_where(cond);
+  apply_scopes::<MC, _>(mm, &mut query)?;
 
-  // -- Execute query
-  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-  let sqlx_query = sqlx::query_with(&sql, values);
-  let n = mm.dbx().execute(sqlx_query).await?;
+  Ok(query.build_sqlx(DbQueryBuilder))
+}
 
-  Ok(n)
+fn _build_stream_query<MC, E, F>(
+  mm: &ModelManager,
+  filter: F,
+  list_options: Option<ListOptions>,
+) -> Result<(String, SqlxValues)>
+where
+  MC: DbBmc,
+  E: HasSeaFields,
+  F: Into<FilterGroups>,
+{
+  let mut query = Query::select();
+  query.from(mm.table_ref::<MC>()?).columns(E::sea_column_refs());
+
+  let filters: FilterGroups = filter.into();
+  let cond: Condition = filters.try_into()?;
+  query.cond_where(cond);
+  apply_scopes::<MC, _>(mm, &mut query)?;
+
+  if let Some(list_options) = list_options {
+    list_options.apply_to_sea_query(&mut query);
+  }
+
+  Ok(query.build_sqlx(DbQueryBuilder))
 }
 
 pub fn compute_list_options<MC>(list_options: Option<ListOptions>) -> Result<ListOptions>
@@ -460,3 +1095,157 @@ where
     Ok(())
   }
 }
+
+/// 设置逻辑删除列的 update 语句
+fn _logical_deletion_update<MC>(mm: &ModelManager) -> Result<UpdateStatement>
+where
+  MC: DbBmc,
+{
+  let ctx = mm.ctx_ref()?;
+  let logical_deletion = MC::logical_deletion();
+
+  // -- Prep Fields
+  let mut fields =
+    SeaFields::new(vec![SeaField::new(SIden(logical_deletion.column()), logical_deletion.deleted_value(ctx))]);
+  if MC::has_modification_timestamps() {
+    fields = prep_fields_for_update::<MC>(fields, ctx);
+  }
+
+  let mut query = Query::update();
+  query.table(mm.table_ref::<MC>()?).values(fields.for_sea_update());
+  Ok(query)
+}
+
+fn _require_logical_deletion<MC>() -> Result<LogicalDeletion>
+where
+  MC: DbBmc,
+{
+  if MC::use_logical_deletion() {
+    Ok(MC::logical_deletion())
+  } else {
+    Err(Error::InvalidArgument { message: format!("'{}.{}' not use logical deletion", MC::SCHEMA, MC::TABLE) })
+  }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+  use modql::field::Fields;
+  use sqlx::FromRow;
+  use ultimate::configuration::model::DbConf;
+  use ultimate::ctx::Ctx;
+
+  use crate::base::{self, Aggregate, AggregateQuery, ConflictSpec, DateTrunc, DbBmc, GroupBy};
+  use crate::{Error, ModelManager};
+
+  struct DictBmc;
+  impl DbBmc for DictBmc {
+    const TABLE: &'static str = "dict";
+  }
+
+  struct VersionedDictBmc;
+  impl DbBmc for VersionedDictBmc {
+    const TABLE: &'static str = "dict";
+
+    fn has_optimistic_lock() -> bool {
+      true
+    }
+  }
+
+  #[derive(Fields)]
+  struct DictForVersionedUpdate {
+    value: String,
+    optimistic_lock: i32,
+  }
+
+  #[derive(Debug, FromRow, Fields)]
+  struct Dict {
+    id: i64,
+    name: String,
+    value: String,
+  }
+
+  #[derive(FromRow)]
+  struct DictStats {
+    month: String,
+    count: i64,
+    max_id: Option<i64>,
+  }
+
+  #[derive(Fields)]
+  struct DictForCreate {
+    name: String,
+    value: String,
+  }
+
+  fn dict(name: &str, value: &str) -> DictForCreate {
+    DictForCreate { name: name.to_string(), value: value.to_string() }
+  }
+
+  #[tokio::test]
+  async fn test_crud_on_sqlite() -> anyhow::Result<()> {
+    let conf: DbConf = serde_json::from_value(serde_json::json!({ "enable": true, "url": "sqlite::memory:" }))?;
+    let mm = ModelManager::new(&conf).await?.with_ctx(Ctx::new_root());
+    let create_table = "CREATE TABLE dict (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL UNIQUE, \
+      value TEXT NOT NULL, cid INTEGER, ctime TEXT, mid INTEGER, mtime TEXT)";
+    mm.dbx().execute(sqlx::query(create_table)).await?;
+
+    let id = base::create::<DictBmc, _>(&mm, dict("a", "1")).await?;
+    let ids = base::create_many::<DictBmc, _>(&mm, vec![dict("b", "2"), dict("c", "3")]).await?;
+    assert_eq!(ids, vec![id + 1, id + 2]);
+
+    let conflict = ConflictSpec::new(["name"]);
+    assert_eq!(base::upsert_with::<DictBmc, _>(&mm, dict("a", "10"), conflict.clone()).await?, id);
+    assert!(!base::insert_ignore_with::<DictBmc, _>(&mm, dict("a", "11"), conflict).await?);
+    let entity: Dict = base::find_by_id::<DictBmc, _>(&mm, id.into()).await?;
+    assert_eq!((entity.name.as_str(), entity.value.as_str()), ("a", "10"));
+
+    let err = base::create::<DictBmc, _>(&mm, dict("b", "4")).await.unwrap_err();
+    let err = err.resolve_unique_violation(None::<fn(&str, &str) -> Option<Error>>);
+    assert!(matches!(err, Error::UniqueViolation { .. }));
+
+    let result: crate::Result<()> = mm
+      .transaction(|mm| async move {
+        base::delete_by_id::<DictBmc>(&mm, id.into()).await?;
+        Err(Error::InvalidArgument { message: "rollback".to_string() })
+      })
+      .await;
+    assert!(result.is_err());
+    assert_eq!(base::count_on::<DictBmc, _>(&mm, |_| Ok(())).await?, 3);
+
+    let query = AggregateQuery::new()
+      .with_group_by(GroupBy::date("ctime", DateTrunc::Month).with_alias("month"))
+      .with_aggregate(Aggregate::count())
+      .with_aggregate(Aggregate::max("id"));
+    let stats: Vec<DictStats> =
+      base::aggregate::<DictBmc, _, _>(&mm, Vec::<modql::filter::FilterNode>::new(), &query, None).await?;
+    assert_eq!(stats.len(), 1);
+    assert!(stats[0].month.ends_with("-01"));
+    assert_eq!((stats[0].count, stats[0].max_id), (3, Some(id + 2)));
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_update_optimistic_lock_on_sqlite() -> anyhow::Result<()> {
+    let conf: DbConf = serde_json::from_value(serde_json::json!({ "enable": true, "url": "sqlite::memory:" }))?;
+    let mm = ModelManager::new(&conf).await?.with_ctx(Ctx::new_root());
+    let create_table = "CREATE TABLE dict (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL UNIQUE, \
+      value TEXT NOT NULL, optimistic_lock INTEGER NOT NULL DEFAULT 0, cid INTEGER, ctime TEXT, mid INTEGER, mtime TEXT)";
+    mm.dbx().execute(sqlx::query(create_table)).await?;
+    let id = base::create::<DictBmc, _>(&mm, dict("a", "1")).await?;
+
+    let update = |value: &str, optimistic_lock| DictForVersionedUpdate { value: value.to_string(), optimistic_lock };
+    let by_name = |name: &str| vec![modql::filter::FilterNode::from(("name", name))];
+    assert_eq!(base::update::<VersionedDictBmc, _, _>(&mm, by_name("a"), update("2", 0)).await?, 1);
+
+    // 版本号不一致时为乐观锁冲突，没有匹配的数据时返回 0
+    let err = base::update::<VersionedDictBmc, _, _>(&mm, by_name("a"), update("3", 0)).await.unwrap_err();
+    assert!(matches!(err, Error::OptimisticLockConflict { .. }), "{}", err);
+    assert_eq!(base::update::<VersionedDictBmc, _, _>(&mm, by_name("b"), update("3", 1)).await?, 0);
+
+    let err = base::update_by_id::<VersionedDictBmc, _>(&mm, id.into(), update("3", 0)).await.unwrap_err();
+    assert!(matches!(err, Error::OptimisticLockConflict { .. }), "{}", err);
+    let err = base::update_by_id::<VersionedDictBmc, _>(&mm, (id + 1).into(), update("3", 1)).await.unwrap_err();
+    assert!(matches!(err, Error::EntityNotFound { .. }), "{}", err);
+    Ok(())
+  }
+}
@@ -0,0 +1,328 @@
+//! 游标（键集）分页。
+//!
+//! 游标记录上一页首行或末行的排序列的值，下一次查询使用 `(sort_columns) > (cursor_values)` 条件定位，
+//! 查询代价不随页数增加。排序总是以主键 `id` 作为最后一个排序列，以保证顺序稳定。
+//! 游标使用 AES-256-GCM 加密，客户端无法读取、伪造或篡改；排序列只能是实体的字段。
+use std::sync::{Arc, OnceLock};
+
+use sea_query::{Alias, Condition, Expr, NullOrdering, Order, SelectStatement, SimpleExpr};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row};
+use tracing::warn;
+use ultimate_api::v1::{SortBy, SortDirection};
+use ultimate_common::{crypto::Keyring, digest, string};
+
+use crate::store::{backend, DbRow};
+use crate::{Error, Result};
+
+const KEY_COLUMN_PREFIX: &str = "__cursor_k";
+const TYPE_COLUMN_PREFIX: &str = "__cursor_t";
+
+/// 游标密钥的 ID，不包含在游标中
+const CURSOR_KEY_ID: &str = "c";
+
+static DEFAULT_KEYRING: OnceLock<Keyring> = OnceLock::new();
+
+/// 游标的编码及加密
+#[derive(Clone, Default)]
+pub struct CursorCodec {
+  keyring: Option<Arc<Keyring>>,
+}
+
+impl CursorCodec {
+  /// 加密密钥由 `secret` 派生，`secret` 为 None 时使用进程内随机生成的密钥
+  pub fn new(secret: Option<&str>) -> Self {
+    Self { keyring: secret.map(|s| Arc::new(cursor_keyring(s.as_bytes()))) }
+  }
+
+  fn keyring(&self) -> &Keyring {
+    match &self.keyring {
+      Some(keyring) => keyring,
+      None => DEFAULT_KEYRING.get_or_init(|| {
+        warn!("`ultimate.db.cursor_secret` is not set, cursors are only valid in the current process");
+        cursor_keyring(string::random_string(32).as_bytes())
+      }),
+    }
+  }
+
+  pub(crate) fn encode(&self, cursor: &Cursor) -> Result<String> {
+    let ciphertext = self.keyring().encrypt(&serde_json::to_vec(cursor)?).map_err(|_| Error::InvalidCursor)?;
+    let (_, payload) = ciphertext.split_once(':').ok_or(Error::InvalidCursor)?;
+    Ok(payload.to_string())
+  }
+
+  pub(crate) fn decode(&self, s: &str) -> Result<Cursor> {
+    let payload = self.keyring().decrypt(&format!("{}:{}", CURSOR_KEY_ID, s)).map_err(|_| Error::InvalidCursor)?;
+    serde_json::from_slice(&payload).map_err(|_| Error::InvalidCursor)
+  }
+}
+
+/// 使用 `secret` 的 SHA-256 摘要作为 AES-256 密钥
+fn cursor_keyring(secret: &[u8]) -> Keyring {
+  Keyring::new(CURSOR_KEY_ID, &digest::sha256(secret)).expect("SHA-256 digest is a valid AES-256 key")
+}
+
+/// 排序列及是否升序
+pub(crate) type KeysetSort = (String, bool);
+
+/// 排序列的值（文本形式）及类型
+pub(crate) type KeysetValue = (Option<String>, String);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Cursor {
+  /// true: 向后翻页，false: 向前翻页
+  #[serde(rename = "n")]
+  pub next: bool,
+
+  /// 生成游标时的排序，用于校验游标与请求一致
+  #[serde(rename = "s")]
+  pub sorts: Vec<KeysetSort>,
+
+  #[serde(rename = "k")]
+  pub keys: Vec<KeysetValue>,
+}
+
+/// 请求的排序加上主键 `id`。排序列必须是 `columns`（实体的字段）之一或 `id`，否则返回 [Error::InvalidArgument]
+pub(crate) fn keyset_sorts(sort_bys: &[SortBy], columns: &[&str]) -> Result<Vec<KeysetSort>> {
+  let mut sorts = Vec::with_capacity(sort_bys.len() + 1);
+  for s in sort_bys {
+    if s.f != "id" && !columns.contains(&s.f.as_str()) {
+      return Err(Error::InvalidArgument { message: format!("Unsupported sort column '{}'", s.f) });
+    }
+    sorts.push((s.f.clone(), !matches!(s.d.try_into().unwrap_or_default(), SortDirection::DESC)));
+  }
+  if !sorts.iter().any(|(col, _)| col == "id") {
+    sorts.push(("id".to_string(), true));
+  }
+  Ok(sorts)
+}
+
+/// 加入排序、排序列的值及类型，`forward` 为 false 时反向排序。NULL 视为最大值：升序时排在最后，降序时排在最前
+pub(crate) fn apply_keyset_columns(query: &mut SelectStatement, sorts: &[KeysetSort], forward: bool) {
+  for (i, (col, asc)) in sorts.iter().enumerate() {
+    let (order, nulls) =
+      if *asc == forward { (Order::Asc, NullOrdering::Last) } else { (Order::Desc, NullOrdering::First) };
+    let key = Expr::col(Alias::new(col)).cast_as(Alias::new(backend::TEXT_TYPE));
+    query
+      .expr_as(key, Alias::new(key_column(i)))
+      .expr_as(backend::type_of_expr(Expr::col(Alias::new(col)).into()), Alias::new(type_column(i)))
+      .order_by_with_nulls(Alias::new(col), order, nulls);
+  }
+}
+
+/// 位于游标之后（`forward` 为 true）或之前的数据的过滤条件，NULL 的顺序与 [apply_keyset_columns] 一致
+pub(crate) fn keyset_condition(sorts: &[KeysetSort], keys: &[KeysetValue], forward: bool) -> Result<Condition> {
+  if sorts.len() != keys.len() {
+    return Err(Error::InvalidCursor);
+  }
+
+  let mut cond = Condition::any();
+  for (i, ((col, asc), key)) in sorts.iter().zip(keys).enumerate() {
+    let col = Expr::col(Alias::new(col));
+    let after = match (&key.0, *asc == forward) {
+      // NULL 之后不再有更大的值
+      (None, true) => continue,
+      (None, false) => col.is_not_null(),
+      (Some(_), true) => col.clone().gt(key_value(key)?).or(col.is_null()),
+      (Some(_), false) => col.lt(key_value(key)?),
+    };
+
+    let mut c = Condition::all();
+    for ((prev_col, _), prev_key) in sorts[..i].iter().zip(&keys[..i]) {
+      let prev_col = Expr::col(Alias::new(prev_col));
+      c = c.add(match prev_key.0 {
+        Some(_) => prev_col.eq(key_value(prev_key)?),
+        None => prev_col.is_null(),
+      });
+    }
+    cond = cond.add(c.add(after));
+  }
+  Ok(cond)
+}
+
+/// 查询结果及其排序列的值
+pub(crate) struct CursorRow<E> {
+  pub entity: E,
+  pub keys: Vec<KeysetValue>,
+}
+
+impl<'r, E> FromRow<'r, DbRow> for CursorRow<E>
+where
+  E: FromRow<'r, DbRow>,
+{
+  fn from_row(row: &'r DbRow) -> sqlx::Result<Self> {
+    let entity = E::from_row(row)?;
+    let mut keys = Vec::new();
+    for i in 0.. {
+      let key_column = key_column(i);
+      if row.try_column(key_column.as_str()).is_err() {
+        break;
+      }
+      keys.push((row.try_get(key_column.as_str())?, row.try_get(type_column(i).as_str())?));
+    }
+    Ok(Self { entity, keys })
+  }
+}
+
+fn key_column(i: usize) -> String {
+  format!("{KEY_COLUMN_PREFIX}{i}")
+}
+
+fn type_column(i: usize) -> String {
+  format!("{TYPE_COLUMN_PREFIX}{i}")
+}
+
+/// 将游标中的文本值转换回原类型
+fn key_value((value, ty): &KeysetValue) -> Result<SimpleExpr> {
+  let valid_type = !ty.is_empty()
+    && ty
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | ' ' | '"' | '.' | '[' | ']' | '(' | ')' | ','));
+  if !valid_type {
+    return Err(Error::InvalidCursor);
+  }
+  Ok(Expr::val(value.clone()).cast_as(Alias::new(ty)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_cursor_codec() -> anyhow::Result<()> {
+    let codec = CursorCodec::new(Some("secret"));
+    let cursor = Cursor {
+      next: true,
+      sorts: vec![("ctime".to_string(), false), ("id".to_string(), true)],
+      keys: vec![(Some("2024-10-01 08:00:00+00".to_string()), "timestamp with time zone".to_string())],
+    };
+    let s = codec.encode(&cursor)?;
+    assert_eq!(codec.decode(&s)?, cursor);
+
+    // 游标内容是加密的，不包含排序列及其值
+    assert!(!s.contains(':') && !s.contains('.'));
+    let plain = String::from_utf8_lossy(&digest::b64u_decode(&s)?).to_string();
+    assert!(!plain.contains("ctime") && !plain.contains("2024"));
+
+    assert!(matches!(CursorCodec::new(Some("other")).decode(&s), Err(Error::InvalidCursor)));
+    let tampered = format!("{}{}", &s[..s.len() - 1], if s.ends_with('A') { "B" } else { "A" });
+    assert!(matches!(codec.decode(&tampered), Err(Error::InvalidCursor)));
+    Ok(())
+  }
+
+  #[test]
+  fn test_keyset_sorts() -> anyhow::Result<()> {
+    let sort_by = |f: &str| SortBy { f: f.to_string(), d: SortDirection::DESC as i32 };
+    let sorts = keyset_sorts(&[sort_by("name")], &["id", "name"])?;
+    assert_eq!(sorts, vec![("name".to_string(), false), ("id".to_string(), true)]);
+    assert_eq!(keyset_sorts(&[sort_by("id")], &["name"])?, vec![("id".to_string(), false)]);
+
+    let err = keyset_sorts(&[sort_by("name) OR (1=1")], &["id", "name"]).unwrap_err();
+    assert!(matches!(err, Error::InvalidArgument { .. }), "{}", err);
+    Ok(())
+  }
+
+  #[cfg(feature = "postgres")]
+  #[test]
+  fn test_keyset_condition() -> anyhow::Result<()> {
+    use sea_query::{PostgresQueryBuilder, Query};
+
+    let sorts = keyset_sorts(&[SortBy { f: "name".to_string(), d: SortDirection::DESC as i32 }], &["name"])?;
+    let keys = vec![(Some("Tom".to_string()), "text".to_string()), (Some("9".to_string()), "bigint".to_string())];
+
+    let mut query = Query::select();
+    query.column(Alias::new("name")).from(Alias::new("t")).cond_where(keyset_condition(&sorts, &keys, true)?);
+    apply_keyset_columns(&mut query, &sorts, true);
+    assert_eq!(
+      query.to_string(PostgresQueryBuilder),
+      r#"SELECT "name", CAST("name" AS TEXT) AS "__cursor_k0", pg_typeof("name")::text AS "__cursor_t0", CAST("id" AS TEXT) AS "__cursor_k1", pg_typeof("id")::text AS "__cursor_t1" FROM "t" WHERE "name" < CAST('Tom' AS text) OR ("name" = CAST('Tom' AS text) AND ("id" > CAST('9' AS bigint) OR "id" IS NULL)) ORDER BY "name" DESC NULLS FIRST, "id" ASC NULLS LAST"#
+    );
+
+    // 降序时 NULL 排在最前，其后是所有非空值
+    let keys = vec![(None, "text".to_string()), (Some("9".to_string()), "bigint".to_string())];
+    let cond = keyset_condition(&sorts, &keys, true)?;
+    assert_eq!(
+      Query::select().column(Alias::new("name")).from(Alias::new("t")).cond_where(cond).to_string(PostgresQueryBuilder),
+      r#"SELECT "name" FROM "t" WHERE "name" IS NOT NULL OR ("name" IS NULL AND ("id" > CAST('9' AS bigint) OR "id" IS NULL))"#
+    );
+
+    assert!(matches!(keyset_condition(&sorts, &keys[..1], true), Err(Error::InvalidCursor)));
+    Ok(())
+  }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod sqlite_tests {
+  use modql::field::Fields;
+  use sqlx::FromRow;
+  use ultimate::configuration::model::DbConf;
+  use ultimate::ctx::Ctx;
+  use ultimate_api::v1::{CursorPagination, SortBy, SortDirection};
+
+  use crate::base::{self, DbBmc};
+  use crate::ModelManager;
+
+  struct DictBmc;
+  impl DbBmc for DictBmc {
+    const TABLE: &'static str = "dict";
+  }
+
+  #[derive(Debug, FromRow, Fields)]
+  struct DictWithRemark {
+    id: i64,
+    remark: Option<String>,
+  }
+
+  #[tokio::test]
+  async fn test_page_by_cursor_with_nulls_on_sqlite() -> anyhow::Result<()> {
+    let conf: DbConf = serde_json::from_value(serde_json::json!({ "enable": true, "url": "sqlite::memory:" }))?;
+    let mm = ModelManager::new(&conf).await?.with_ctx(Ctx::new_root());
+    let create_table = "CREATE TABLE dict (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL UNIQUE, \
+      value TEXT, remark TEXT, cid INTEGER, ctime TEXT, mid INTEGER, mtime TEXT)";
+    mm.dbx().execute(sqlx::query(create_table)).await?;
+    let insert =
+      "INSERT INTO dict (name, remark) VALUES ('a', 'r2'), ('b', NULL), ('c', 'r1'), ('d', NULL), ('e', 'r2')";
+    mm.dbx().execute(sqlx::query(insert)).await?;
+
+    for d in [SortDirection::ASC, SortDirection::DESC] {
+      let mut pagination = CursorPagination {
+        page_size: 2,
+        sort_bys: vec![SortBy { f: "remark".to_string(), d: d as i32 }],
+        ..Default::default()
+      };
+      let mut pages = Vec::new();
+      loop {
+        let payload = base::page_by_cursor::<DictBmc, DictWithRemark, _>(
+          &mm,
+          Vec::<modql::filter::FilterNode>::new(),
+          pagination.clone(),
+        )
+        .await?;
+        pages.push((pagination.cursor.clone(), payload.page.prev_cursor.clone(), payload.items));
+        match payload.page.next_cursor {
+          Some(cursor) => pagination.cursor = Some(cursor),
+          None => break,
+        }
+      }
+
+      // NULL 视为最大值，每行都只出现一次
+      let ids: Vec<i64> = pages.iter().flat_map(|(.., items)| items.iter().map(|e| e.id)).collect();
+      let expected = if d == SortDirection::ASC { vec![3, 1, 5, 2, 4] } else { vec![2, 4, 1, 5, 3] };
+      assert_eq!(ids, expected, "{:?}", d);
+
+      // 从最后一页向前翻页得到上一页
+      let (_, prev_cursor, _) = pages.last().unwrap();
+      pagination.cursor = prev_cursor.clone();
+      let payload = base::page_by_cursor::<DictBmc, DictWithRemark, _>(
+        &mm,
+        Vec::<modql::filter::FilterNode>::new(),
+        pagination.clone(),
+      )
+      .await?;
+      let prev_ids: Vec<i64> = payload.items.iter().map(|e| e.id).collect();
+      let page_ids: Vec<i64> = pages[pages.len() - 2].2.iter().map(|e| e.id).collect();
+      assert_eq!(prev_ids, page_ids, "{:?}", d);
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,70 @@
+use sea_query::{Condition, Expr};
+use ultimate::ctx::Ctx;
+
+use super::CommonIden;
+
+/// 行级数据范围，由 [super::DbBmc::data_scope] 声明。
+///
+/// `crud_fns` 会将数据范围条件加入所有 select、update、delete 语句，超出范围的数据视为不存在。
+#[derive(Clone, Copy, Default)]
+pub enum DataScope {
+  /// 不限制
+  #[default]
+  All,
+
+  /// 只能访问自己的数据：`owner_id = ctx.uid()`
+  Owner,
+
+  /// 只能访问所属组织的数据：`org_id IN ctx.ext_orgs()`
+  Org,
+
+  /// 只能访问所属租户的数据：`tenant_id = ctx.tenant_id()`，`Ctx` 未设置租户时不能访问任何数据
+  Tenant,
+
+  /// 自定义过滤条件
+  Custom(fn(&Ctx) -> Condition),
+}
+
+impl DataScope {
+  /// 生成过滤条件，不限制时返回 None
+  pub fn to_condition(&self, ctx: &Ctx) -> Option<Condition> {
+    let cond = match self {
+      DataScope::All => return None,
+      DataScope::Owner => Condition::all().add(Expr::col(CommonIden::OwnerId).eq(ctx.uid())),
+      DataScope::Org => Condition::all().add(Expr::col(CommonIden::OrgId).is_in(ctx.ext_orgs().iter().copied())),
+      DataScope::Tenant => match ctx.tenant_id() {
+        Some(tenant_id) => Condition::all().add(Expr::col(CommonIden::TenantId).eq(tenant_id)),
+        None => Condition::all().add(Expr::cust("FALSE")),
+      },
+      DataScope::Custom(f) => f(ctx),
+    };
+    Some(cond)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use sea_query::{PostgresQueryBuilder, Query};
+
+  use super::*;
+
+  fn to_sql(scope: DataScope, ctx: &Ctx) -> String {
+    let mut query = Query::select();
+    query.column(CommonIden::Id).from(CommonIden::Id);
+    if let Some(cond) = scope.to_condition(ctx) {
+      query.cond_where(cond);
+    }
+    query.to_string(PostgresQueryBuilder)
+  }
+
+  #[test]
+  fn test_data_scope() {
+    let ctx = Ctx::new_root();
+    assert_eq!(to_sql(DataScope::All, &ctx), r#"SELECT "id" FROM "id""#);
+    assert_eq!(to_sql(DataScope::Owner, &ctx), r#"SELECT "id" FROM "id" WHERE "owner_id" = 0"#);
+    assert_eq!(to_sql(DataScope::Tenant, &ctx), r#"SELECT "id" FROM "id" WHERE FALSE"#);
+    let ctx = ctx.with_ext_orgs(vec![3, 5]).with_tenant_id(9);
+    assert_eq!(to_sql(DataScope::Org, &ctx), r#"SELECT "id" FROM "id" WHERE "org_id" IN (3, 5)"#);
+    assert_eq!(to_sql(DataScope::Tenant, &ctx), r#"SELECT "id" FROM "id" WHERE "tenant_id" = 9"#);
+  }
+}
@@ -1,6 +1,14 @@
 use modql::SIden;
 use sea_query::{IntoIden, TableRef};
 
+use ultimate::ctx::Ctx;
+
+use super::{DataScope, LogicalDeletion, Relation, SearchPolicy};
+use crate::audit::AuditPolicy;
+use crate::cache::CachePolicy;
+use crate::store::backend;
+use crate::DbIdGenerator;
+
 /// The DbBmc trait must be implemented for the Bmc struct of an entity.
 /// It specifies meta information such as the table name,
 /// whether the table has timestamp columns (cid, ctime, mid, mtime), and more as the
@@ -15,7 +23,7 @@ pub trait DbBmc {
   const LIST_LIMIT_MAX: i64 = super::LIST_LIMIT_MAX;
 
   fn table_ref() -> TableRef {
-    TableRef::SchemaTable(SIden(Self::SCHEMA).into_iden(), SIden(Self::TABLE).into_iden())
+    backend::table_ref(SIden(Self::SCHEMA).into_iden(), SIden(Self::TABLE).into_iden())
   }
 
   fn qualified_table() -> (&'static str, &'static str) {
@@ -35,13 +43,20 @@ pub trait DbBmc {
     true
   }
 
-  /// 是否使用逻辑删除
+  /// 是否使用逻辑删除。启用后 `crud_fns` 的查询、更新只作用于未删除的数据，删除改为设置逻辑删除列
   ///
   /// default: false
   fn use_logical_deletion() -> bool {
     false
   }
 
+  /// 逻辑删除列的名称和类型
+  ///
+  /// default: LogicalDeletion::Flag("logical_deletion")
+  fn logical_deletion() -> LogicalDeletion {
+    LogicalDeletion::default()
+  }
+
   /// Specifies if the entity table managed by this BMC
   /// has an `owner_id` column that needs to be set on create (by default ctx.user_id).
   ///
@@ -50,12 +65,87 @@ pub trait DbBmc {
     false
   }
 
-  /// 乐观锁
+  /// 行级数据范围，`crud_fns` 会据此过滤所有 select、update、delete 语句
+  ///
+  /// default: DataScope::All
+  fn data_scope() -> DataScope {
+    DataScope::All
+  }
+
+  /// 是否跳过数据范围过滤
+  ///
+  /// default: 根用户和超级管理员跳过
+  fn bypass_data_scope(ctx: &Ctx) -> bool {
+    ctx.is_root() || ctx.is_super_admin()
+  }
+
+  /// 启用多租户（`ultimate.db.tenancy`）时是否按租户隔离，所有租户共用的表应返回 false
+  ///
+  /// default: true
+  fn multi_tenant() -> bool {
+    true
+  }
+
+  /// 乐观锁。启用后 `update_by_id`、`update` 的数据必须包含当前版本号 `optimistic_lock`，
+  /// 只有版本号一致时才会更新（并将版本号加 1），否则返回 `Error::OptimisticLockConflict`
+  ///
   /// default: false
   fn has_optimistic_lock() -> bool {
     false
   }
 
+  /// 由应用生成主键 ID 时返回 ID 生成器，插入数据时若未指定 `id` 列则使用其生成的值。
+  ///
+  /// 注意：`create`、`create_many` 返回 `i64` 类型的 ID，其它类型的 ID 请使用 `insert`、`insert_many`。
+  ///
+  /// default: None，由数据库生成（如自增主键）
+  fn id_generator() -> Option<&'static dyn DbIdGenerator> {
+    None
+  }
+
+  /// `upsert`、`insert_ignore` 的冲突列，必须存在对应的唯一约束或唯一索引
+  ///
+  /// default: ["id"]
+  fn conflict_columns() -> &'static [&'static str] {
+    &["id"]
+  }
+
+  /// `upsert` 冲突时更新的列
+  ///
+  /// default: None，更新除冲突列和创建信息（id、owner_id、cid、ctime）外所有插入的列
+  fn upsert_update_columns() -> Option<&'static [&'static str]> {
+    None
+  }
+
+  /// 与其它表的关系，用于 `load_related`、`related_condition` 等函数
+  ///
+  /// default: 无
+  fn relations() -> Vec<Relation> {
+    Vec::new()
+  }
+
+  /// 审计策略。启用后 `crud_fns` 的创建、更新、删除在同一事务内写入审计记录，见 [crate::audit]
+  ///
+  /// default: None，不审计
+  fn audit() -> Option<AuditPolicy> {
+    None
+  }
+
+  /// 缓存策略。启用后 `find_by_id_cached`、`find_unique_cached` 优先从缓存读取，`crud_fns` 的写入会使缓存失效，
+  /// 见 [crate::cache]
+  ///
+  /// default: None，不缓存
+  fn cache() -> Option<CachePolicy> {
+    None
+  }
+
+  /// 全文检索配置，用于 `search_filter`、`search_many`、`page_search`，见 [super::Search]
+  ///
+  /// default: None，不支持检索
+  fn search() -> Option<SearchPolicy> {
+    None
+  }
+
   /// 是否过滤用 column id
   /// default: false
   fn filter_column_id() -> bool {
@@ -0,0 +1,83 @@
+use modql::SIden;
+use sea_query::{Condition, Expr, SimpleExpr, Value};
+use ultimate::ctx::Ctx;
+
+/// 逻辑删除列，由 [super::DbBmc::logical_deletion] 声明
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogicalDeletion {
+  /// 布尔列，删除时设为 true
+  Flag(&'static str),
+
+  /// 时间戳列，删除时设为删除时间，未删除时为 NULL
+  DeletedAt(&'static str),
+}
+
+impl Default for LogicalDeletion {
+  fn default() -> Self {
+    LogicalDeletion::Flag("logical_deletion")
+  }
+}
+
+impl LogicalDeletion {
+  pub fn column(&self) -> &'static str {
+    match self {
+      LogicalDeletion::Flag(col) | LogicalDeletion::DeletedAt(col) => col,
+    }
+  }
+
+  /// 未删除数据的过滤条件
+  pub fn not_deleted_condition(&self) -> Condition {
+    let col = Expr::col(SIden(self.column()));
+    match self {
+      LogicalDeletion::Flag(_) => Condition::all().add(col.is_not(true)),
+      LogicalDeletion::DeletedAt(_) => Condition::all().add(col.is_null()),
+    }
+  }
+
+  /// 已删除数据的过滤条件
+  pub fn deleted_condition(&self) -> Condition {
+    let col = Expr::col(SIden(self.column()));
+    match self {
+      LogicalDeletion::Flag(_) => Condition::all().add(col.eq(true)),
+      LogicalDeletion::DeletedAt(_) => Condition::all().add(col.is_not_null()),
+    }
+  }
+
+  /// 删除时设置的值
+  pub fn deleted_value(&self, ctx: &Ctx) -> SimpleExpr {
+    match self {
+      LogicalDeletion::Flag(_) => true.into(),
+      LogicalDeletion::DeletedAt(_) => (*ctx.req_time()).into(),
+    }
+  }
+
+  /// 恢复时设置的值
+  pub fn restored_value(&self) -> SimpleExpr {
+    match self {
+      LogicalDeletion::Flag(_) => false.into(),
+      LogicalDeletion::DeletedAt(_) => Value::ChronoDateTimeUtc(None).into(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use sea_query::{PostgresQueryBuilder, Query};
+
+  use super::*;
+
+  #[test]
+  fn test_logical_deletion_conditions() {
+    let to_sql = |cond: Condition| {
+      Query::select().column(SIden("id")).from(SIden("t")).cond_where(cond).to_string(PostgresQueryBuilder)
+    };
+
+    let flag = LogicalDeletion::default();
+    assert_eq!(to_sql(flag.not_deleted_condition()), r#"SELECT "id" FROM "t" WHERE "logical_deletion" IS NOT TRUE"#);
+    assert_eq!(to_sql(flag.deleted_condition()), r#"SELECT "id" FROM "t" WHERE "logical_deletion" = TRUE"#);
+
+    let deleted_at = LogicalDeletion::DeletedAt("deleted_at");
+    assert_eq!(to_sql(deleted_at.not_deleted_condition()), r#"SELECT "id" FROM "t" WHERE "deleted_at" IS NULL"#);
+    assert_eq!(to_sql(deleted_at.deleted_condition()), r#"SELECT "id" FROM "t" WHERE "deleted_at" IS NOT NULL"#);
+  }
+}
@@ -40,6 +40,27 @@ macro_rules! generate_common_bmc_fns {
 					) -> ultimate_db::Result<u64> {
 							ultimate_db::base::insert_many::<Self, _>(mm, entity_c).await
 					}
+
+					pub async fn upsert(
+							mm: &ultimate_db::ModelManager,
+							entity_c: $for_create,
+					) -> ultimate_db::Result<i64> {
+							ultimate_db::base::upsert::<Self, _>(mm, entity_c).await
+					}
+
+					pub async fn upsert_many(
+							mm: &ultimate_db::ModelManager,
+							entity_c: Vec<$for_create>,
+					) -> ultimate_db::Result<u64> {
+							ultimate_db::base::upsert_many::<Self, _>(mm, entity_c).await
+					}
+
+					pub async fn insert_ignore(
+							mm: &ultimate_db::ModelManager,
+							entity_c: $for_create,
+					) -> ultimate_db::Result<bool> {
+							ultimate_db::base::insert_ignore::<Self, _>(mm, entity_c).await
+					}
 			)?
 
 			pub async fn find_by_id(
@@ -119,6 +140,14 @@ macro_rules! generate_filter_bmc_fns {
 				) -> ultimate_db::Result<ultimate_api::v1::PagePayload<$entity>> {
 					ultimate_db::base::page::<Self, _, _>(mm, filter, pagination).await
 				}
+
+				pub async fn page_by_cursor(
+					mm: &ultimate_db::ModelManager,
+					filter: Vec<$filter>,
+					pagination: ultimate_api::v1::CursorPagination,
+				) -> ultimate_db::Result<ultimate_api::v1::CursorPagePayload<$entity>> {
+					ultimate_db::base::page_by_cursor::<Self, _, _>(mm, filter, pagination).await
+				}
 			)?
 		}
 	};
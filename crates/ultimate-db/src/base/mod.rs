@@ -1,12 +1,26 @@
 use sea_query::Iden;
 
+mod aggregate;
+mod conflict;
 mod crud_fns;
+mod cursor;
+mod data_scope;
 mod db_bmc;
+mod logical_deletion;
 mod macro_utils;
+mod relation;
+mod search;
 mod utils;
 
+pub use aggregate::*;
+pub use conflict::*;
 pub use crud_fns::*;
+pub use cursor::CursorCodec;
+pub use data_scope::*;
 pub use db_bmc::*;
+pub use logical_deletion::*;
+pub use relation::*;
+pub use search::*;
 pub use utils::*;
 
 const LIST_LIMIT_DEFAULT: i64 = 1000;
@@ -16,7 +30,9 @@ const LIST_LIMIT_MAX: i64 = 5000;
 pub enum CommonIden {
   Id,
   OwnerId,
-  LogiscalDeletion,
+  OrgId,
+  TenantId,
+  LogicalDeletion,
   OptimisticLock,
 }
 
@@ -0,0 +1,428 @@
+//! 实体间的关系，由 [DbBmc::relations] 声明。
+//!
+//! - [load_related] 按关系批量加载关联数据，一次查询加载所有父数据的关联数据，避免 N+1 查询
+//! - [related_condition]、[find_many_by_related] 按关联表的列过滤数据
+//!
+//! 关系均通过关联表的主键 `id` 连接，关联表同样应用租户隔离、数据范围和逻辑删除的过滤条件。
+use std::collections::{HashMap, HashSet};
+use std::mem::discriminant;
+
+use modql::field::HasSeaFields;
+use modql::filter::{FilterGroups, ListOptions};
+use modql::SIden;
+use sea_query::{Alias, Condition, Expr, JoinType, Query, SelectStatement, TableRef};
+use sea_query_binder::{SqlxBinder, SqlxValues};
+use sqlx::{Decode, FromRow, Row, Type};
+
+use crate::base::{apply_scopes, compute_list_options, CommonIden, DbBmc};
+use crate::store::{DbQueryBuilder, DbRow, DbType, Dbx};
+use crate::{Error, Id, ModelManager, Result};
+
+const RELATED_KEY: &str = "__related_key";
+const RELATED_TARGET: &str = "__related_target";
+const RELATED_THROUGH: &str = "__related_through";
+
+/// 关系所涉及的表
+#[derive(Clone, Copy)]
+pub struct RelatedTable {
+  schema: &'static str,
+  table: &'static str,
+  table_ref: fn(&ModelManager) -> Result<TableRef>,
+  apply_scopes: fn(&ModelManager, &mut SelectStatement) -> Result<()>,
+}
+
+impl RelatedTable {
+  pub fn of<MC: DbBmc>() -> Self {
+    Self {
+      schema: MC::SCHEMA,
+      table: MC::TABLE,
+      table_ref: |mm| mm.table_ref::<MC>(),
+      apply_scopes: apply_scopes::<MC, SelectStatement>,
+    }
+  }
+
+  pub fn qualified_table(&self) -> (&'static str, &'static str) {
+    (self.schema, self.table)
+  }
+
+  /// 查询该表的语句，已加入租户隔离、数据范围和逻辑删除的过滤条件
+  fn select(&self, mm: &ModelManager) -> Result<SelectStatement> {
+    let mut query = Query::select();
+    query.from((self.table_ref)(mm)?);
+    (self.apply_scopes)(mm, &mut query)?;
+    Ok(query)
+  }
+}
+
+#[derive(Clone, Copy)]
+pub enum RelationKind {
+  /// 当前表的 `foreign_key` 列引用关联表的 `id`
+  BelongsTo { foreign_key: &'static str },
+
+  /// 关联表的 `foreign_key` 列引用当前表的 `id`，也用于一对一关系
+  HasMany { foreign_key: &'static str },
+
+  /// 通过中间表关联：中间表的 `source_key` 列引用当前表的 `id`，`target_key` 列引用关联表的 `id`
+  ManyToMany { through: RelatedTable, source_key: &'static str, target_key: &'static str },
+}
+
+/// 实体间的关系
+#[derive(Clone, Copy)]
+pub struct Relation {
+  name: &'static str,
+  target: RelatedTable,
+  kind: RelationKind,
+}
+
+impl Relation {
+  pub fn belongs_to<TC: DbBmc>(name: &'static str, foreign_key: &'static str) -> Self {
+    Self { name, target: RelatedTable::of::<TC>(), kind: RelationKind::BelongsTo { foreign_key } }
+  }
+
+  pub fn has_many<TC: DbBmc>(name: &'static str, foreign_key: &'static str) -> Self {
+    Self { name, target: RelatedTable::of::<TC>(), kind: RelationKind::HasMany { foreign_key } }
+  }
+
+  /// `JC` 为中间表
+  pub fn many_to_many<TC: DbBmc, JC: DbBmc>(
+    name: &'static str,
+    source_key: &'static str,
+    target_key: &'static str,
+  ) -> Self {
+    Self {
+      name,
+      target: RelatedTable::of::<TC>(),
+      kind: RelationKind::ManyToMany { through: RelatedTable::of::<JC>(), source_key, target_key },
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    self.name
+  }
+
+  pub fn target(&self) -> &RelatedTable {
+    &self.target
+  }
+
+  pub fn kind(&self) -> &RelationKind {
+    &self.kind
+  }
+
+  /// 当前表中用于关联的列
+  pub fn source_column(&self) -> &'static str {
+    match self.kind {
+      RelationKind::BelongsTo { foreign_key } => foreign_key,
+      RelationKind::HasMany { .. } | RelationKind::ManyToMany { .. } => "id",
+    }
+  }
+}
+
+/// 查找 [DbBmc::relations] 中声明的关系，不存在时返回 [Error::InvalidArgument]
+pub fn relation_of<MC: DbBmc>(name: &str) -> Result<Relation> {
+  MC::relations().into_iter().find(|r| r.name == name).ok_or_else(|| Error::InvalidArgument {
+    message: format!("Relation '{}' not found in table '{}.{}'", name, MC::SCHEMA, MC::TABLE),
+  })
+}
+
+/// 批量加载关联数据，返回以关联键分组的关联数据。
+///
+/// `keys` 为父数据在 [Relation::source_column] 列的值：`belongs_to` 关系为父数据的外键，其它关系为父数据的 `id`。
+/// 返回结果的键与 `keys` 对应，`keys` 的类型应与关联键列的类型一致，类型不一致时返回 [Error::InvalidArgument]。
+pub async fn load_related<MC, E>(
+  mm: &ModelManager,
+  relation: &str,
+  keys: impl IntoIterator<Item = impl Into<Id>>,
+) -> Result<HashMap<Id, Vec<E>>>
+where
+  MC: DbBmc,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
+  E: HasSeaFields,
+{
+  let relation = relation_of::<MC>(relation)?;
+  let mut seen = HashSet::new();
+  let keys: Vec<Id> = keys.into_iter().map(Into::into).filter(|key: &Id| seen.insert(key.clone())).collect();
+  let Some(first) = keys.first().cloned() else {
+    return Ok(HashMap::new());
+  };
+  if keys.iter().any(|key| discriminant(key) != discriminant(&first)) {
+    return Err(Error::InvalidArgument { message: "Related keys must have the same type".to_string() });
+  }
+
+  // -- Build the query
+  let mut query = related_query(mm, &relation, keys)?;
+  query.columns(E::sea_column_refs());
+
+  // -- Execute the query
+  let (sql, values) = query.build_sqlx(DbQueryBuilder);
+  let dbx = mm.dbx_of::<MC>()?;
+  let rows = match first {
+    Id::I32(_) => fetch_related::<i32, E>(dbx, &sql, values).await?,
+    Id::I64(_) => fetch_related::<i64, E>(dbx, &sql, values).await?,
+    Id::String(_) => fetch_related::<String, E>(dbx, &sql, values).await?,
+    #[cfg(feature = "uuid")]
+    Id::Uuid(_) => fetch_related::<uuid::Uuid, E>(dbx, &sql, values).await?,
+  };
+
+  let mut related: HashMap<Id, Vec<E>> = HashMap::new();
+  for (key, entity) in rows {
+    related.entry(key).or_default().push(entity);
+  }
+  Ok(related)
+}
+
+/// 查询关联数据的语句，关联键以 [RELATED_KEY] 列返回
+fn related_query(mm: &ModelManager, relation: &Relation, keys: Vec<Id>) -> Result<SelectStatement> {
+  let mut query = relation.target.select(mm)?;
+  match relation.kind {
+    RelationKind::BelongsTo { .. } => {
+      query
+        .expr_as(Expr::col(CommonIden::Id), Alias::new(RELATED_KEY))
+        .and_where(Expr::col(CommonIden::Id).is_in(keys));
+    }
+    RelationKind::HasMany { foreign_key } => {
+      query
+        .expr_as(Expr::col(SIden(foreign_key)), Alias::new(RELATED_KEY))
+        .and_where(Expr::col(SIden(foreign_key)).is_in(keys));
+    }
+    RelationKind::ManyToMany { through, source_key, target_key } => {
+      let mut through_query = through.select(mm)?;
+      through_query
+        .expr_as(Expr::col(SIden(source_key)), Alias::new(RELATED_KEY))
+        .expr_as(Expr::col(SIden(target_key)), Alias::new(RELATED_TARGET))
+        .and_where(Expr::col(SIden(source_key)).is_in(keys));
+      query.column(Alias::new(RELATED_KEY)).join_subquery(
+        JoinType::InnerJoin,
+        through_query,
+        Alias::new(RELATED_THROUGH),
+        Expr::col(Alias::new(RELATED_TARGET)).equals(CommonIden::Id),
+      );
+    }
+  }
+  Ok(query)
+}
+
+async fn fetch_related<K, E>(dbx: &Dbx, sql: &str, values: SqlxValues) -> Result<Vec<(Id, E)>>
+where
+  K: for<'r> Decode<'r, DbType> + Type<DbType> + Into<Id> + Unpin + Send,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
+{
+  let sqlx_query = sqlx::query_as_with::<_, RelatedRow<K, E>, _>(sql, values);
+  let rows = dbx.fetch_all(sqlx_query).await?;
+  Ok(rows.into_iter().map(|row| (row.key.into(), row.entity)).collect())
+}
+
+/// 关联数据的过滤条件：存在满足 `filter` 的关联数据。可用于 `find_many_on`、`count_on` 等函数
+pub fn related_condition<MC, F>(mm: &ModelManager, relation: &str, filter: F) -> Result<Condition>
+where
+  MC: DbBmc,
+  F: Into<FilterGroups>,
+{
+  let relation = relation_of::<MC>(relation)?;
+  let filters: FilterGroups = filter.into();
+  let cond: Condition = filters.try_into()?;
+
+  let mut target_query = relation.target.select(mm)?;
+  target_query.cond_where(cond);
+
+  let expr = match relation.kind {
+    RelationKind::BelongsTo { foreign_key } => {
+      target_query.column(CommonIden::Id);
+      Expr::col(SIden(foreign_key)).in_subquery(target_query)
+    }
+    RelationKind::HasMany { foreign_key } => {
+      target_query.column(SIden(foreign_key));
+      Expr::col(CommonIden::Id).in_subquery(target_query)
+    }
+    RelationKind::ManyToMany { through, source_key, target_key } => {
+      target_query.column(CommonIden::Id);
+      let mut through_query = through.select(mm)?;
+      through_query.column(SIden(source_key)).and_where(Expr::col(SIden(target_key)).in_subquery(target_query));
+      Expr::col(CommonIden::Id).in_subquery(through_query)
+    }
+  };
+  Ok(Condition::all().add(expr))
+}
+
+/// 同 `find_many`，并只返回存在满足 `related_filter` 的关联数据的数据
+pub async fn find_many_by_related<MC, E, F, RF>(
+  mm: &ModelManager,
+  filter: F,
+  relation: &str,
+  related_filter: RF,
+  list_options: Option<ListOptions>,
+) -> Result<Vec<E>>
+where
+  MC: DbBmc,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
+  E: HasSeaFields,
+  F: Into<FilterGroups>,
+  RF: Into<FilterGroups>,
+{
+  // -- Build the query
+  let mut query = Query::select();
+  query.from(mm.table_ref::<MC>()?).columns(E::sea_column_refs());
+
+  let filters: FilterGroups = filter.into();
+  let cond: Condition = filters.try_into()?;
+  query.cond_where(cond).cond_where(related_condition::<MC, _>(mm, relation, related_filter)?);
+  apply_scopes::<MC, _>(mm, &mut query)?;
+
+  let list_options = compute_list_options::<MC>(list_options)?;
+  list_options.apply_to_sea_query(&mut query);
+
+  // -- Execute the query
+  let (sql, values) = query.build_sqlx(DbQueryBuilder);
+  let sqlx_query = sqlx::query_as_with::<_, E, _>(&sql, values);
+  let entities = mm.dbx_of::<MC>()?.fetch_all(sqlx_query).await?;
+
+  Ok(entities)
+}
+
+/// 关联数据及其关联键
+struct RelatedRow<K, E> {
+  key: K,
+  entity: E,
+}
+
+impl<'r, K, E> FromRow<'r, DbRow> for RelatedRow<K, E>
+where
+  K: Decode<'r, DbType> + Type<DbType>,
+  E: FromRow<'r, DbRow>,
+{
+  fn from_row(row: &'r DbRow) -> sqlx::Result<Self> {
+    Ok(Self { key: row.try_get(RELATED_KEY)?, entity: E::from_row(row)? })
+  }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+  use modql::field::Fields;
+  use modql::filter::FilterNode;
+  use sea_query::SqliteQueryBuilder;
+  use ultimate::configuration::model::DbConf;
+  use ultimate::ctx::Ctx;
+
+  use super::*;
+
+  struct UserBmc;
+  impl DbBmc for UserBmc {
+    const TABLE: &'static str = "user";
+
+    fn relations() -> Vec<Relation> {
+      vec![
+        Relation::belongs_to::<OrgBmc>("org", "org_id"),
+        Relation::has_many::<AddressBmc>("addresses", "user_id"),
+        Relation::many_to_many::<RoleBmc, UserRoleBmc>("roles", "user_id", "role_id"),
+      ]
+    }
+  }
+
+  struct OrgBmc;
+  impl DbBmc for OrgBmc {
+    const TABLE: &'static str = "org";
+  }
+
+  struct AddressBmc;
+  impl DbBmc for AddressBmc {
+    const TABLE: &'static str = "address";
+  }
+
+  struct RoleBmc;
+  impl DbBmc for RoleBmc {
+    const TABLE: &'static str = "role";
+  }
+
+  struct UserRoleBmc;
+  impl DbBmc for UserRoleBmc {
+    const TABLE: &'static str = "user_role";
+  }
+
+  #[derive(Debug, FromRow, Fields)]
+  struct Named {
+    name: String,
+  }
+
+  async fn new_mm() -> anyhow::Result<ModelManager> {
+    let conf: DbConf = serde_json::from_value(serde_json::json!({ "enable": true, "url": "sqlite::memory:" }))?;
+    let mm = ModelManager::new(&conf).await?.with_ctx(Ctx::new_root());
+    let sqls = [
+      "CREATE TABLE user (id INTEGER PRIMARY KEY, name TEXT NOT NULL, org_id INTEGER)",
+      "CREATE TABLE org (id INTEGER PRIMARY KEY, name TEXT NOT NULL)",
+      "CREATE TABLE address (id INTEGER PRIMARY KEY, name TEXT NOT NULL, user_id INTEGER NOT NULL)",
+      "CREATE TABLE role (id INTEGER PRIMARY KEY, name TEXT NOT NULL)",
+      "CREATE TABLE user_role (user_id INTEGER NOT NULL, role_id INTEGER NOT NULL)",
+      "INSERT INTO org (id, name) VALUES (1, 'o1'), (2, 'o2')",
+      "INSERT INTO user (id, name, org_id) VALUES (1, 'u1', 1), (2, 'u2', 2), (3, 'u3', 1)",
+      "INSERT INTO address (id, name, user_id) VALUES (1, 'a1', 1), (2, 'a2', 1), (3, 'a3', 2)",
+      "INSERT INTO role (id, name) VALUES (1, 'r1'), (2, 'r2')",
+      "INSERT INTO user_role (user_id, role_id) VALUES (1, 1), (1, 2), (2, 2)",
+    ];
+    for sql in sqls {
+      mm.dbx().execute(sqlx::query(sql)).await?;
+    }
+    Ok(mm)
+  }
+
+  fn names(related: &HashMap<Id, Vec<Named>>, key: i64) -> Vec<&str> {
+    let mut names: Vec<&str> = related.get(&key.into()).into_iter().flatten().map(|e| e.name.as_str()).collect();
+    names.sort_unstable();
+    names
+  }
+
+  #[tokio::test]
+  async fn test_related_query() -> anyhow::Result<()> {
+    let mm = new_mm().await?;
+    let to_sql = |relation: &str| -> anyhow::Result<String> {
+      let relation = relation_of::<UserBmc>(relation)?;
+      Ok(related_query(&mm, &relation, vec![Id::I64(1), Id::I64(2)])?.to_string(SqliteQueryBuilder))
+    };
+    assert_eq!(to_sql("org")?, r#"SELECT "id" AS "__related_key" FROM "org" WHERE "id" IN (1, 2)"#);
+    assert_eq!(to_sql("addresses")?, r#"SELECT "user_id" AS "__related_key" FROM "address" WHERE "user_id" IN (1, 2)"#);
+    assert_eq!(
+      to_sql("roles")?,
+      r#"SELECT "__related_key" FROM "role" INNER JOIN (SELECT "user_id" AS "__related_key", "role_id" AS "__related_target" FROM "user_role" WHERE "user_id" IN (1, 2)) AS "__related_through" ON "__related_target" = "id""#
+    );
+
+    let related = load_related::<UserBmc, Named>(&mm, "org", [1i64, 2, 1]).await?;
+    assert_eq!((names(&related, 1), names(&related, 2)), (vec!["o1"], vec!["o2"]));
+    let related = load_related::<UserBmc, Named>(&mm, "addresses", [1i64, 2, 3]).await?;
+    assert_eq!((names(&related, 1), names(&related, 2)), (vec!["a1", "a2"], vec!["a3"]));
+    assert!(!related.contains_key(&Id::I64(3)));
+    let related = load_related::<UserBmc, Named>(&mm, "roles", [1i64, 2]).await?;
+    assert_eq!((names(&related, 1), names(&related, 2)), (vec!["r1", "r2"], vec!["r2"]));
+
+    let err = load_related::<UserBmc, Named>(&mm, "roles", [Id::I64(1), Id::String("2".to_string())]).await;
+    assert!(matches!(err, Err(Error::InvalidArgument { .. })));
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_related_condition() -> anyhow::Result<()> {
+    let mm = new_mm().await?;
+    let by_name = |name: &str| vec![FilterNode::from(("name", name))];
+    let to_sql = |relation: &str, name: &str| -> anyhow::Result<String> {
+      let cond = related_condition::<UserBmc, _>(&mm, relation, by_name(name))?;
+      Ok(Query::select().column(CommonIden::Id).from(Alias::new("user")).cond_where(cond).to_string(SqliteQueryBuilder))
+    };
+    assert_eq!(
+      to_sql("org", "o1")?,
+      r#"SELECT "id" FROM "user" WHERE "org_id" IN (SELECT "id" FROM "org" WHERE "name" = 'o1')"#
+    );
+    assert_eq!(
+      to_sql("addresses", "a3")?,
+      r#"SELECT "id" FROM "user" WHERE "id" IN (SELECT "user_id" FROM "address" WHERE "name" = 'a3')"#
+    );
+    assert_eq!(
+      to_sql("roles", "r2")?,
+      r#"SELECT "id" FROM "user" WHERE "id" IN (SELECT "user_id" FROM "user_role" WHERE "role_id" IN (SELECT "id" FROM "role" WHERE "name" = 'r2'))"#
+    );
+
+    let names = |users: Vec<Named>| users.into_iter().map(|u| u.name).collect::<Vec<_>>();
+    let no_filter = Vec::<FilterNode>::new;
+    let users = find_many_by_related::<UserBmc, Named, _, _>(&mm, no_filter(), "org", by_name("o1"), None).await?;
+    assert_eq!(names(users), vec!["u1", "u3"]);
+    let users = find_many_by_related::<UserBmc, Named, _, _>(&mm, no_filter(), "roles", by_name("r2"), None).await?;
+    assert_eq!(names(users), vec!["u1", "u2"]);
+    Ok(())
+  }
+}
@@ -0,0 +1,524 @@
+//! 全文检索，只支持 PostgreSQL。
+//!
+//! 由 [DbBmc::search] 声明检索的 `tsvector` 列（或参与检索的文本列），[Search] 为检索条件：
+//! - [search_filter] 生成检索的过滤条件节点，可与其它过滤条件一起传给 [super::page]、[super::find_many] 等；
+//! - [search_condition] 生成检索的过滤条件，可与 [super::find_many_on]、[super::count_on] 等组合使用；
+//! - [search_many]、[page_search] 返回带相关度及高亮片段的 [SearchHit]，未指定排序时按相关度倒序。
+//!
+//! `simple` 等分词配置不会切分中文，可以通过 [SearchPolicy::with_trigram] 启用 `pg_trgm` 的相似度匹配，
+//! 与全文检索的结果合并（需要 `CREATE EXTENSION pg_trgm`，并为这些列建立 `gin_trgm_ops` 索引）。
+//! 相似度匹配使用可走索引的 `<%` 运算符，阈值为 `pg_trgm.word_similarity_threshold`：[search_many]、[page_search]
+//! 在事务内将其设置为 [SearchPolicy::with_trigram] 的阈值；使用 [search_filter]、[search_condition] 时需在同一事务内
+//! 先调用 [set_trigram_threshold]，否则使用数据库的设置（默认为 0.6）。
+//!
+//! ```rust,no_run
+//! use ultimate_db::base::{DbBmc, SearchPolicy};
+//!
+//! struct UserBmc;
+//! impl DbBmc for UserBmc {
+//!   const TABLE: &'static str = "user";
+//!   const SCHEMA: &'static str = "iam";
+//!
+//!   fn search() -> Option<SearchPolicy> {
+//!     Some(SearchPolicy::columns(&["name", "email"]).with_trigram(&["name"], 0.3).with_highlight("name"))
+//!   }
+//! }
+//! ```
+use std::future::Future;
+
+use modql::field::HasSeaFields;
+use modql::filter::{
+  FilterGroups, FilterNode, IntoSeaError, ListOptions, OpValValue, SeaResult, ToSeaConditionFnHolder,
+};
+use modql::SIden;
+use sea_query::{Alias, ColumnRef, Condition, ConditionExpression, Expr, Order, Query, SelectStatement, SimpleExpr};
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row};
+use ultimate_api::v1::{Page, PagePayload, Pagination};
+
+use super::{apply_scopes, compute_list_options, CommonIden, DbBmc};
+use crate::store::{DbKind, DbQueryBuilder, DbRow, DB_KIND};
+use crate::{Error, ModelManager, Result};
+
+const RANK_COLUMN: &str = "__search_rank";
+const HIGHLIGHT_COLUMN: &str = "__search_highlight";
+
+/// [search_filter] 生成的过滤条件节点的名称
+const SEARCH_FILTER: &str = "__search";
+
+/// 检索的文档
+#[derive(Debug, Clone, Copy)]
+enum SearchVector {
+  /// 已存储的 `tsvector` 列，通常为生成列并建有 GIN 索引
+  Column(&'static str),
+
+  /// 检索时由文本列计算 `to_tsvector`，适合数据量较小或建有对应表达式索引的表
+  Columns(&'static [&'static str]),
+}
+
+/// 全文检索配置，由 [DbBmc::search] 返回
+#[derive(Debug, Clone)]
+pub struct SearchPolicy {
+  vector: SearchVector,
+  config: &'static str,
+  trigram_columns: &'static [&'static str],
+  trigram_threshold: f32,
+  highlight_column: Option<&'static str>,
+  highlight_options: &'static str,
+}
+
+impl SearchPolicy {
+  /// 检索已存储的 `tsvector` 列
+  pub fn column(column: &'static str) -> Self {
+    Self::new(SearchVector::Column(column))
+  }
+
+  /// 检索由文本列拼接后计算的 `tsvector`
+  pub fn columns(columns: &'static [&'static str]) -> Self {
+    Self::new(SearchVector::Columns(columns))
+  }
+
+  fn new(vector: SearchVector) -> Self {
+    Self {
+      vector,
+      config: "simple",
+      trigram_columns: &[],
+      trigram_threshold: 0.3,
+      highlight_column: None,
+      highlight_options: "StartSel=<mark>, StopSel=</mark>",
+    }
+  }
+
+  /// 分词配置（`regconfig`），默认为 `simple`。使用 `tsvector` 列时应与生成该列时的配置一致
+  pub fn with_config(mut self, config: &'static str) -> Self {
+    self.config = config;
+    self
+  }
+
+  /// 同时使用 `pg_trgm` 的 `<%` 匹配 `columns`，`word_similarity` 不小于 `threshold`（0 ~ 1）的数据也作为结果，
+  /// 见 [set_trigram_threshold]
+  pub fn with_trigram(mut self, columns: &'static [&'static str], threshold: f32) -> Self {
+    self.trigram_columns = columns;
+    self.trigram_threshold = threshold;
+    self
+  }
+
+  /// 使用 `ts_headline` 生成 `column` 的高亮片段
+  pub fn with_highlight(mut self, column: &'static str) -> Self {
+    self.highlight_column = Some(column);
+    self
+  }
+
+  /// `ts_headline` 的选项，默认为 `StartSel=<mark>, StopSel=</mark>`
+  pub fn with_highlight_options(mut self, options: &'static str) -> Self {
+    self.highlight_options = options;
+    self
+  }
+
+  fn config_literal(&self) -> String {
+    format!("'{}'::regconfig", self.config.replace('\'', "''"))
+  }
+
+  fn vector_expr(&self) -> SimpleExpr {
+    match self.vector {
+      SearchVector::Column(column) => Expr::col(SIden(column)).into(),
+      SearchVector::Columns(columns) => {
+        let placeholders = (1..=columns.len()).map(|i| format!("${i}")).collect::<Vec<_>>().join(", ");
+        Expr::cust_with_exprs(
+          format!("to_tsvector({}, concat_ws(' ', {}))", self.config_literal(), placeholders),
+          columns.iter().map(|column| Expr::col(SIden(column)).into()),
+        )
+      }
+    }
+  }
+
+  fn query_expr(&self, search: &Search) -> SimpleExpr {
+    Expr::cust_with_values(format!("{}({}, $1)", search.mode.function(), self.config_literal()), [search.query.trim()])
+  }
+
+  fn trigram_exprs(&self, search: &Search) -> impl Iterator<Item = SimpleExpr> + '_ {
+    let query = search.query.trim().to_string();
+    self.trigram_columns.iter().map(move |column| {
+      Expr::cust_with_exprs(
+        "word_similarity($1, $2)",
+        [Expr::val(query.clone()).into(), Expr::col(SIden(column)).into()],
+      )
+    })
+  }
+
+  fn condition(&self, search: &Search) -> Condition {
+    let mut cond =
+      Condition::any().add(Expr::cust_with_exprs("$1 @@ $2", [self.vector_expr(), self.query_expr(search)]));
+    for column in self.trigram_columns {
+      let query = Expr::val(search.query.trim()).into();
+      cond = cond.add(Expr::cust_with_exprs("$1 <% $2", [query, Expr::col(SIden(column)).into()]));
+    }
+    cond
+  }
+
+  /// 查询文本不为空且启用了 `pg_trgm` 时需要设置相似度阈值
+  fn uses_trigram(&self, search: &Search) -> bool {
+    !self.trigram_columns.is_empty() && !search.is_empty()
+  }
+
+  fn rank_expr(&self, search: &Search) -> SimpleExpr {
+    let rank = Expr::cust_with_exprs("ts_rank($1, $2)", [self.vector_expr(), self.query_expr(search)]);
+    let exprs: Vec<SimpleExpr> = std::iter::once(rank).chain(self.trigram_exprs(search)).collect();
+    let placeholders = (1..=exprs.len()).map(|i| format!("${i}")).collect::<Vec<_>>().join(", ");
+    Expr::cust_with_exprs(format!("CAST(GREATEST({}) AS REAL)", placeholders), exprs)
+  }
+
+  fn highlight_expr(&self, search: &Search) -> Option<SimpleExpr> {
+    self.highlight_column.map(|column| {
+      Expr::cust_with_exprs(
+        format!("ts_headline({}, $1, $2, $3)", self.config_literal()),
+        [Expr::col(SIden(column)).into(), self.query_expr(search), Expr::val(self.highlight_options).into()],
+      )
+    })
+  }
+}
+
+/// 查询文本的解析方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMode {
+  /// `websearch_to_tsquery`：支持 `"短语"`、`or`、`-排除` 等搜索引擎语法，不会因语法错误而失败
+  #[default]
+  WebSearch,
+
+  /// `plainto_tsquery`：所有词都需要匹配
+  Plain,
+
+  /// `phraseto_tsquery`：词需要按顺序相邻
+  Phrase,
+
+  /// `to_tsquery`：原始的 tsquery 语法，如 `rust & (web | db)`，语法错误时查询失败
+  Raw,
+}
+
+impl SearchMode {
+  fn function(&self) -> &'static str {
+    match self {
+      SearchMode::WebSearch => "websearch_to_tsquery",
+      SearchMode::Plain => "plainto_tsquery",
+      SearchMode::Phrase => "phraseto_tsquery",
+      SearchMode::Raw => "to_tsquery",
+    }
+  }
+}
+
+/// 检索条件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Search {
+  /// 查询文本，为空时不过滤
+  pub query: String,
+
+  #[serde(default)]
+  pub mode: SearchMode,
+}
+
+impl Search {
+  pub fn new(query: impl Into<String>) -> Self {
+    Self { query: query.into(), mode: SearchMode::default() }
+  }
+
+  pub fn with_mode(mut self, mode: SearchMode) -> Self {
+    self.mode = mode;
+    self
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.query.trim().is_empty()
+  }
+}
+
+/// 检索结果
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit<E> {
+  #[serde(flatten)]
+  pub entity: E,
+
+  /// 相关度，查询文本为空时为 0
+  pub rank: f32,
+
+  /// 高亮片段，未设置 [SearchPolicy::with_highlight] 或查询文本为空时为 None
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub highlight: Option<String>,
+}
+
+impl<'r, E> FromRow<'r, DbRow> for SearchHit<E>
+where
+  E: FromRow<'r, DbRow>,
+{
+  fn from_row(row: &'r DbRow) -> sqlx::Result<Self> {
+    let entity = E::from_row(row)?;
+    let rank = row.try_get(RANK_COLUMN)?;
+    let highlight = if row.try_column(HIGHLIGHT_COLUMN).is_ok() { row.try_get(HIGHLIGHT_COLUMN)? } else { None };
+    Ok(Self { entity, rank, highlight })
+  }
+}
+
+/// 检索的过滤条件节点，查询文本为空时不过滤
+pub fn search_filter<MC>(search: &Search) -> Result<FilterNode>
+where
+  MC: DbBmc,
+{
+  _require_search::<MC>()?;
+  let mut node = FilterNode::new(SEARCH_FILTER, vec![OpValValue::Eq(serde_json::to_value(search)?).into()]);
+  node.for_sea_condition = Some(ToSeaConditionFnHolder::new(_search_sea_condition::<MC>).into());
+  Ok(node)
+}
+
+/// 检索的过滤条件，查询文本为空时不过滤
+pub fn search_condition<MC>(search: &Search) -> Result<Condition>
+where
+  MC: DbBmc,
+{
+  let policy = _require_search::<MC>()?;
+  if search.is_empty() {
+    return Ok(Condition::all());
+  }
+  Ok(policy.condition(search))
+}
+
+/// 将当前事务的 `pg_trgm.word_similarity_threshold` 设置为 [SearchPolicy::with_trigram] 的阈值，在事务结束前有效。
+/// 需要在事务内（[ModelManager::transaction]）调用
+pub async fn set_trigram_threshold<MC>(mm: &ModelManager) -> Result<()>
+where
+  MC: DbBmc,
+{
+  let policy = _require_search::<MC>()?;
+  let dbx = mm.dbx_of::<MC>()?;
+  if !dbx.has_open_txn().await {
+    return Err(Error::InvalidArgument { message: "Set trigram threshold need a transaction".to_string() });
+  }
+  let sqlx_query = sqlx::query("SELECT set_config('pg_trgm.word_similarity_threshold', $1, true)")
+    .bind(policy.trigram_threshold.to_string());
+  dbx.execute(sqlx_query).await?;
+  Ok(())
+}
+
+/// 检索数据，`list_options` 未指定排序时按相关度倒序
+pub async fn search_many<MC, E, F>(
+  mm: &ModelManager,
+  filter: F,
+  search: &Search,
+  list_options: Option<ListOptions>,
+) -> Result<Vec<SearchHit<E>>>
+where
+  MC: DbBmc,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
+  E: HasSeaFields,
+  F: Into<FilterGroups>,
+{
+  let filters: FilterGroups = filter.into();
+  _with_trigram_threshold::<MC, _, _, _>(mm, search, |mm| {
+    let (filters, list_options) = (filters.clone(), list_options.clone());
+    async move { _search_many::<MC, E>(&mm, filters, search, list_options).await }
+  })
+  .await
+}
+
+async fn _search_many<MC, E>(
+  mm: &ModelManager,
+  filters: FilterGroups,
+  search: &Search,
+  list_options: Option<ListOptions>,
+) -> Result<Vec<SearchHit<E>>>
+where
+  MC: DbBmc,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
+  E: HasSeaFields,
+{
+  let query = _build_search_query::<MC, E>(mm, filters, search, compute_list_options::<MC>(list_options)?)?;
+  let (sql, values) = query.build_sqlx(DbQueryBuilder);
+  let sqlx_query = sqlx::query_as_with::<_, SearchHit<E>, _>(&sql, values);
+  let hits = mm.dbx_of::<MC>()?.fetch_all(sqlx_query).await?;
+  Ok(hits)
+}
+
+/// 分页检索数据，同 [super::page]，`pagination` 未指定排序时按相关度倒序
+pub async fn page_search<MC, E, F>(
+  mm: &ModelManager,
+  filter: F,
+  search: &Search,
+  pagination: Pagination,
+) -> Result<PagePayload<SearchHit<E>>>
+where
+  MC: DbBmc,
+  E: for<'r> FromRow<'r, DbRow> + Unpin + Send,
+  E: HasSeaFields,
+  F: Into<FilterGroups>,
+{
+  let filters: FilterGroups = filter.into();
+  let cond = search_condition::<MC>(search)?;
+  let (total_size, items) = _with_trigram_threshold::<MC, _, _, _>(mm, search, |mm| {
+    let (filters, cond, list_options) = (filters.clone(), cond.clone(), (&pagination).into());
+    async move {
+      let total_size = super::count_on::<MC, _>(&mm, |query| {
+        let filter_cond: Condition = filters.clone().try_into()?;
+        query.cond_where(filter_cond).cond_where(cond);
+        Ok(())
+      })
+      .await?;
+      let items = _search_many::<MC, E>(&mm, filters, search, Some(list_options)).await?;
+      Ok((total_size, items))
+    }
+  })
+  .await?;
+
+  Ok(PagePayload::new(Page::new(&pagination, total_size), items))
+}
+
+/// 需要相似度阈值时在事务内设置后执行 `f`，否则直接执行
+async fn _with_trigram_threshold<MC, F, Fut, T>(mm: &ModelManager, search: &Search, mut f: F) -> Result<T>
+where
+  MC: DbBmc,
+  F: FnMut(ModelManager) -> Fut,
+  Fut: Future<Output = Result<T>>,
+{
+  if !_require_search::<MC>()?.uses_trigram(search) {
+    return f(mm.clone()).await;
+  }
+  mm.transaction(|mm| {
+    let fut = f(mm.clone());
+    async move {
+      set_trigram_threshold::<MC>(&mm).await?;
+      fut.await
+    }
+  })
+  .await
+}
+
+fn _search_sea_condition<MC>(_col: &ColumnRef, value: OpValValue) -> SeaResult<ConditionExpression>
+where
+  MC: DbBmc,
+{
+  let OpValValue::Eq(value) = value else {
+    return Err(IntoSeaError::Custom(format!("'{}' only supports the eq operator", SEARCH_FILTER)));
+  };
+  let search: Search = serde_json::from_value(value).map_err(|e| IntoSeaError::Custom(e.to_string()))?;
+  let cond = search_condition::<MC>(&search).map_err(|e| IntoSeaError::Custom(e.to_string()))?;
+  Ok(ConditionExpression::Condition(cond))
+}
+
+fn _build_search_query<MC, E>(
+  mm: &ModelManager,
+  filters: FilterGroups,
+  search: &Search,
+  list_options: ListOptions,
+) -> Result<SelectStatement>
+where
+  MC: DbBmc,
+  E: HasSeaFields,
+{
+  let policy = _require_search::<MC>()?;
+  let mut query = Query::select();
+  query.from(mm.table_ref::<MC>()?).columns(E::sea_column_refs());
+
+  let cond: Condition = filters.try_into()?;
+  query.cond_where(cond);
+  if search.is_empty() {
+    query.expr_as(Expr::cust("CAST(0 AS REAL)"), Alias::new(RANK_COLUMN));
+  } else {
+    query.cond_where(policy.condition(search));
+    query.expr_as(policy.rank_expr(search), Alias::new(RANK_COLUMN));
+    if let Some(highlight) = policy.highlight_expr(search) {
+      query.expr_as(highlight, Alias::new(HIGHLIGHT_COLUMN));
+    }
+  }
+  apply_scopes::<MC, _>(mm, &mut query)?;
+
+  let mut list_options = list_options;
+  if !list_options.order_bys.as_ref().is_some_and(|order_bys| order_bys.into_iter().next().is_some()) {
+    list_options.order_bys = None;
+    query.order_by(Alias::new(RANK_COLUMN), Order::Desc).order_by(CommonIden::Id, Order::Asc);
+  }
+  list_options.apply_to_sea_query(&mut query);
+  Ok(query)
+}
+
+fn _require_search<MC>() -> Result<SearchPolicy>
+where
+  MC: DbBmc,
+{
+  if DB_KIND != DbKind::Postgres {
+    return Err(Error::UnsupportedByBackend { feature: "search", backend: DB_KIND });
+  }
+  MC::search()
+    .ok_or_else(|| Error::InvalidArgument { message: format!("'{}.{}' not declare search", MC::SCHEMA, MC::TABLE) })
+}
+
+#[cfg(all(test, feature = "postgres"))]
+mod tests {
+  use sea_query::PostgresQueryBuilder;
+
+  use super::*;
+
+  fn to_sql(policy: &SearchPolicy, search: &Search) -> String {
+    let mut query = Query::select();
+    query
+      .expr_as(policy.rank_expr(search), Alias::new(RANK_COLUMN))
+      .from(SIden("user"))
+      .cond_where(policy.condition(search));
+    query.to_string(PostgresQueryBuilder)
+  }
+
+  #[test]
+  fn test_search_sql() {
+    let search = Search::new(" rust db ");
+    let policy = SearchPolicy::column("tsv");
+    assert_eq!(
+      to_sql(&policy, &search),
+      r#"SELECT CAST(GREATEST(ts_rank("tsv", websearch_to_tsquery('simple'::regconfig, 'rust db'))) AS REAL) AS "__search_rank" FROM "user" WHERE "tsv" @@ websearch_to_tsquery('simple'::regconfig, 'rust db')"#
+    );
+
+    let policy = SearchPolicy::columns(&["name", "email"]).with_config("english").with_trigram(&["name"], 0.4);
+    let search = Search::new("张三").with_mode(SearchMode::Plain);
+    let vector = r#"to_tsvector('english'::regconfig, concat_ws(' ', "name", "email"))"#;
+    let query = "plainto_tsquery('english'::regconfig, '张三')";
+    assert_eq!(
+      to_sql(&policy, &search),
+      format!(
+        r#"SELECT CAST(GREATEST(ts_rank({vector}, {query}), word_similarity('张三', "name")) AS REAL) AS "__search_rank" FROM "user" WHERE ({vector} @@ {query}) OR ('张三' <% "name")"#
+      )
+    );
+  }
+
+  struct UserBmc;
+  impl DbBmc for UserBmc {
+    const TABLE: &'static str = "user";
+
+    fn search() -> Option<SearchPolicy> {
+      Some(SearchPolicy::column("tsv").with_trigram(&["name"], 0.3))
+    }
+  }
+
+  struct DictBmc;
+  impl DbBmc for DictBmc {
+    const TABLE: &'static str = "dict";
+  }
+
+  #[test]
+  fn test_search_filter() -> anyhow::Result<()> {
+    let filters: FilterGroups =
+      vec![FilterNode::from(("status", 1i64)), search_filter::<UserBmc>(&Search::new("rust"))?].into();
+    let cond: Condition = filters.try_into()?;
+    let mut query = Query::select();
+    query.column(SIden("id")).from(SIden("user")).cond_where(cond);
+    assert_eq!(
+      query.to_string(PostgresQueryBuilder),
+      r#"SELECT "id" FROM "user" WHERE "status" = 1 AND (("tsv" @@ websearch_to_tsquery('simple'::regconfig, 'rust')) OR ('rust' <% "name"))"#
+    );
+
+    // 查询文本为空时不过滤
+    let filters: FilterGroups = vec![search_filter::<UserBmc>(&Search::new(" "))?].into();
+    let cond: Condition = filters.try_into()?;
+    let mut query = Query::select();
+    query.column(SIden("id")).from(SIden("user")).cond_where(cond);
+    assert_eq!(query.to_string(PostgresQueryBuilder), r#"SELECT "id" FROM "user" WHERE TRUE"#);
+    assert!(search_filter::<DictBmc>(&Search::new("rust")).is_err());
+    Ok(())
+  }
+}
@@ -1,10 +1,10 @@
 use modql::field::{SeaField, SeaFields};
-use sea_query::{DynIden, IntoIden};
-use ultimate::ctx::Ctx;
+use sea_query::{ConditionalStatement, DynIden, Expr, IntoIden, SimpleExpr};
+use ultimate::{configuration::model::TenancyMode, ctx::Ctx};
 
 use crate::{
-  base::{CommonIden, DbBmc, TimestampIden},
-  Error, Result,
+  base::{CommonIden, DataScope, DbBmc, TimestampIden},
+  Error, ModelManager, Result,
 };
 
 /// This method must be called when a model controller intends to create its entity.
@@ -27,6 +27,21 @@ where
   fields
 }
 
+/// 当 [DbBmc::id_generator] 返回生成器且未指定 `id` 列时，为插入数据生成主键 ID
+pub fn prep_id_for_create<MC>(fields: SeaFields) -> Result<SeaFields>
+where
+  MC: DbBmc,
+{
+  let Some(generator) = MC::id_generator() else {
+    return Ok(fields);
+  };
+  let mut fields = fields.into_vec();
+  if !_exists_in_fields(&fields, CommonIden::Id.into_iden()) {
+    fields.push(SeaField::new(CommonIden::Id, generator.next_db_id()?));
+  }
+  Ok(SeaFields::new(fields))
+}
+
 /// This method must be calledwhen a Model Controller plans to update its entity.
 pub fn prep_fields_for_update<MC>(fields: SeaFields, ctx: &Ctx) -> SeaFields
 where
@@ -39,15 +54,110 @@ where
   }
 }
 
+/// `shared_table` 多租户模式下为插入数据填充 `tenant_id` 列
+pub fn prep_tenant_for_create<MC>(mm: &ModelManager, fields: SeaFields) -> Result<SeaFields>
+where
+  MC: DbBmc,
+{
+  if mm.tenancy().mode() != TenancyMode::SharedTable {
+    return Ok(fields);
+  }
+  let Some(tenant_id) = mm.tenancy().tenant_of::<MC>(mm.ctx_opt_ref())? else {
+    return Ok(fields);
+  };
+  let mut fields = fields.into_vec();
+  if !_exists_in_fields(&fields, CommonIden::TenantId.into_iden()) {
+    fields.push(SeaField::new(CommonIden::TenantId, tenant_id));
+  }
+  Ok(SeaFields::new(fields))
+}
+
+/// 将租户隔离条件（`shared_table` 模式）、[DbBmc::data_scope] 的过滤条件和逻辑删除的过滤条件加入 select、update、delete 语句
+pub fn apply_scopes<MC, Q>(mm: &ModelManager, query: &mut Q) -> Result<()>
+where
+  MC: DbBmc,
+  Q: ConditionalStatement,
+{
+  apply_scopes_with_deleted::<MC, Q>(mm, query)?;
+  if MC::use_logical_deletion() {
+    query.cond_where(MC::logical_deletion().not_deleted_condition());
+  }
+  Ok(())
+}
+
+/// 同 [apply_scopes]，但不过滤已逻辑删除的数据
+pub fn apply_scopes_with_deleted<MC, Q>(mm: &ModelManager, query: &mut Q) -> Result<()>
+where
+  MC: DbBmc,
+  Q: ConditionalStatement,
+{
+  if mm.tenancy().mode() == TenancyMode::SharedTable {
+    if let Some(tenant_id) = mm.tenancy().tenant_of::<MC>(mm.ctx_opt_ref())? {
+      query.and_where(Expr::col(CommonIden::TenantId).eq(tenant_id));
+    }
+  }
+  apply_data_scope::<MC, Q>(query, mm.ctx_opt_ref())
+}
+
+/// 将 [DbBmc::data_scope] 的过滤条件加入 select、update、delete 语句。
+///
+/// 数据范围不为 `DataScope::All` 时需要 `Ctx`，否则返回 [Error::Unauthorized]。
+pub fn apply_data_scope<MC, Q>(query: &mut Q, ctx: Option<&Ctx>) -> Result<()>
+where
+  MC: DbBmc,
+  Q: ConditionalStatement,
+{
+  let scope = MC::data_scope();
+  if matches!(scope, DataScope::All) {
+    return Ok(());
+  }
+  let ctx = ctx.ok_or(Error::Unauthorized)?;
+  if MC::bypass_data_scope(ctx) {
+    return Ok(());
+  }
+  if let Some(cond) = scope.to_condition(ctx) {
+    query.cond_where(cond);
+  }
+  Ok(())
+}
+
+/// update 语句的列及值
+pub type UpdateValues = Vec<(DynIden, SimpleExpr)>;
+
+/// 为启用乐观锁（[DbBmc::has_optimistic_lock]）的更新准备字段。
+///
+/// 从 `fields` 中取出 `optimistic_lock` 列的值作为当前版本号（未设置时返回 [Error::InvalidArgument]），
+/// 并将其改为自增 1。返回更新字段及当前版本号，调用方应将 `optimistic_lock = 当前版本号` 加入更新条件。
+pub fn prep_fields_for_optimistic_lock<MC>(fields: SeaFields) -> Result<(UpdateValues, Option<SimpleExpr>)>
+where
+  MC: DbBmc,
+{
+  if !MC::has_optimistic_lock() {
+    return Ok((fields.for_sea_update().collect(), None));
+  }
+
+  let iden = CommonIden::OptimisticLock.into_iden();
+  let mut fields = fields.into_vec();
+  let Some(i) = fields.iter().position(|f| f.iden.to_string() == iden.to_string()) else {
+    return Err(Error::InvalidArgument { message: format!("Column '{}' is required for update", iden.to_string()) });
+  };
+  let version = fields.remove(i).value;
+
+  let mut values: Vec<_> = SeaFields::new(fields).for_sea_update().collect();
+  values.push((iden, Expr::col(CommonIden::OptimisticLock).add(1)));
+  Ok((values, Some(version)))
+}
+
 pub fn clear_id_from_fields<MC>(fields: SeaFields) -> SeaFields {
   let mut fields = fields.into_vec();
   fields.retain(|f| f.iden != CommonIden::Id.into_iden());
   SeaFields::new(fields)
 }
 
+/// 按列名比较：`DynIden` 的相等比较要求类型相同，`#[derive(Fields)]` 生成的列与 [CommonIden] 不相等
 fn _exists_in_fields(fields: &[SeaField], iden: DynIden) -> bool {
-  // let iden = iden.into_iden();
-  fields.iter().any(|f| f.iden == iden)
+  let name = iden.to_string();
+  fields.iter().any(|f| f.iden.to_string() == name)
 }
 
 /// Update the timestamps info for create
@@ -0,0 +1,117 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use async_trait::async_trait;
+
+use super::{CacheBackend, CachePolicy};
+use crate::Result;
+
+/// 进程内的 LRU 缓存，每个命名空间（表）按 [CachePolicy::max_entries] 限制条目数，超出时淘汰最久未访问的条目
+#[derive(Default)]
+pub struct LruCacheBackend {
+  namespaces: Mutex<HashMap<String, LruMap>>,
+}
+
+#[async_trait]
+impl CacheBackend for LruCacheBackend {
+  async fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>> {
+    let mut namespaces = self.namespaces.lock().unwrap();
+    Ok(namespaces.get_mut(namespace).and_then(|map| map.get(key, Instant::now())))
+  }
+
+  async fn put(&self, namespace: &str, key: &str, value: Vec<u8>, policy: &CachePolicy) -> Result<()> {
+    let mut namespaces = self.namespaces.lock().unwrap();
+    let map = namespaces.entry(namespace.to_string()).or_default();
+    map.put(key.to_string(), value, Instant::now() + policy.ttl(), policy.max_entries());
+    Ok(())
+  }
+
+  async fn invalidate(&self, namespace: &str) -> Result<()> {
+    self.namespaces.lock().unwrap().remove(namespace);
+    Ok(())
+  }
+
+  async fn clear(&self) -> Result<()> {
+    self.namespaces.lock().unwrap().clear();
+    Ok(())
+  }
+}
+
+#[derive(Default)]
+struct LruMap {
+  entries: HashMap<String, LruEntry>,
+  /// 访问序号 -> key，序号最小的为最久未访问的条目
+  order: BTreeMap<u64, String>,
+  tick: u64,
+}
+
+struct LruEntry {
+  value: Vec<u8>,
+  expires_at: Instant,
+  tick: u64,
+}
+
+impl LruMap {
+  fn get(&mut self, key: &str, now: Instant) -> Option<Vec<u8>> {
+    let entry = self.entries.get_mut(key)?;
+    if entry.expires_at <= now {
+      self.remove(key);
+      return None;
+    }
+
+    self.tick += 1;
+    self.order.remove(&entry.tick);
+    entry.tick = self.tick;
+    self.order.insert(self.tick, key.to_string());
+    Some(entry.value.clone())
+  }
+
+  fn put(&mut self, key: String, value: Vec<u8>, expires_at: Instant, max_entries: usize) {
+    self.remove(&key);
+    self.tick += 1;
+    self.order.insert(self.tick, key.clone());
+    self.entries.insert(key, LruEntry { value, expires_at, tick: self.tick });
+
+    while self.entries.len() > max_entries {
+      let Some((_, key)) = self.order.pop_first() else {
+        break;
+      };
+      self.entries.remove(&key);
+    }
+  }
+
+  fn remove(&mut self, key: &str) {
+    if let Some(entry) = self.entries.remove(key) {
+      self.order.remove(&entry.tick);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use super::*;
+
+  #[test]
+  fn test_lru_map() {
+    let now = Instant::now();
+    let ttl = Duration::from_secs(60);
+    let mut map = LruMap::default();
+    map.put("a".to_string(), b"1".to_vec(), now + ttl, 2);
+    map.put("b".to_string(), b"2".to_vec(), now + ttl, 2);
+
+    // 访问 a 后，b 为最久未访问的条目
+    assert_eq!(map.get("a", now), Some(b"1".to_vec()));
+    map.put("c".to_string(), b"3".to_vec(), now + ttl, 2);
+    assert_eq!(map.get("b", now), None);
+    assert_eq!(map.get("a", now), Some(b"1".to_vec()));
+    assert_eq!(map.get("c", now), Some(b"3".to_vec()));
+
+    // 过期
+    assert_eq!(map.get("a", now + ttl), None);
+    assert_eq!(map.entries.len(), 1);
+    assert_eq!(map.order.len(), 1);
+  }
+}
@@ -0,0 +1,283 @@
+//! BMC 查询结果缓存。
+//!
+//! [DbBmc::cache] 返回缓存策略的表，`crud_fns::find_by_id_cached`、`crud_fns::find_unique_cached` 会优先从缓存读取，
+//! 缓存键由查询语句、参数及租户组成，数据范围等过滤条件不同的查询互不影响。事务内的查询不使用缓存。
+//!
+//! 通过 `crud_fns` 写入时会使整张表的缓存失效，并通过 `NOTIFY` 通知其它实例；在事务内写入时二者都在最外层事务提交后生效，
+//! 回滚时不生效。调用 `listen` 后当前实例接收其它实例的失效通知。绕过 `crud_fns` 写入时需调用 [invalidate]。
+//! 失效通知只支持 PostgreSQL 后端，其它后端只使当前实例的缓存失效。
+//!
+//! 默认使用进程内的 [LruCacheBackend]，可通过 [ModelManager::with_cache_backend] 替换为实现了 [CacheBackend] 的外部缓存。
+//!
+//! ```rust,no_run
+//! use ultimate_db::base::DbBmc;
+//! use ultimate_db::cache::CachePolicy;
+//!
+//! pub struct DictBmc;
+//!
+//! impl DbBmc for DictBmc {
+//!   const SCHEMA: &'static str = "iam";
+//!   const TABLE: &'static str = "dict";
+//!
+//!   fn cache() -> Option<CachePolicy> {
+//!     Some(CachePolicy::default().with_ttl(std::time::Duration::from_secs(300)).with_max_entries(10_000))
+//!   }
+//! }
+//! ```
+use std::future::Future;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use sea_query_binder::SqlxValues;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+#[cfg(feature = "postgres")]
+use sqlx::postgres::PgListener;
+#[cfg(feature = "postgres")]
+use tokio::task::JoinHandle;
+use tracing::warn;
+#[cfg(feature = "postgres")]
+use tracing::{error, info};
+
+use crate::base::DbBmc;
+#[cfg(feature = "postgres")]
+use crate::store::dbx;
+use crate::store::{DbKind, DB_KIND};
+use crate::{ModelManager, Result};
+
+mod lru;
+
+pub use lru::*;
+
+/// 缓存失效通知的 `NOTIFY` 通道，消息内容为表的命名空间，见 [namespace]
+pub const INVALIDATION_CHANNEL: &str = "ultimate_cache_invalidation";
+
+/// 表的缓存策略
+#[derive(Debug, Clone)]
+pub struct CachePolicy {
+  ttl: Duration,
+  max_entries: usize,
+}
+
+impl Default for CachePolicy {
+  fn default() -> Self {
+    Self { ttl: Duration::from_secs(60), max_entries: 1000 }
+  }
+}
+
+impl CachePolicy {
+  /// 缓存条目的有效期，默认为 60 秒
+  pub fn with_ttl(mut self, ttl: Duration) -> Self {
+    self.ttl = ttl;
+    self
+  }
+
+  /// 表的最大缓存条目数，默认为 1000
+  pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+    self.max_entries = max_entries.max(1);
+    self
+  }
+
+  pub fn ttl(&self) -> Duration {
+    self.ttl
+  }
+
+  pub fn max_entries(&self) -> usize {
+    self.max_entries
+  }
+}
+
+/// 缓存后端。值为 JSON 序列化后的数据，`namespace` 为表的命名空间
+#[async_trait]
+pub trait CacheBackend: Send + Sync {
+  async fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>>;
+
+  async fn put(&self, namespace: &str, key: &str, value: Vec<u8>, policy: &CachePolicy) -> Result<()>;
+
+  /// 删除命名空间下的所有条目
+  async fn invalidate(&self, namespace: &str) -> Result<()>;
+
+  /// 删除所有条目
+  async fn clear(&self) -> Result<()>;
+}
+
+/// 表的命名空间：`schema.table`
+pub fn namespace<MC: DbBmc>() -> String {
+  format!("{}.{}", MC::SCHEMA, MC::TABLE)
+}
+
+/// 使表 `MC` 的缓存失效，并通知其它实例。
+///
+/// 通知通过表所在数据库（[ModelManager::dbx_of]）发送，表所在数据库存在事务时通知随该事务提交送达，
+/// 当前实例的缓存在最外层事务（[ModelManager::transaction]）提交后失效，以免提交前读取到旧数据并写入缓存
+pub async fn invalidate<MC: DbBmc>(mm: &ModelManager) -> Result<()> {
+  let namespace = namespace::<MC>();
+  let dbx = mm.dbx_of::<MC>()?;
+  if DB_KIND == DbKind::Postgres {
+    let sqlx_query = sqlx::query("SELECT pg_notify($1, $2)").bind(INVALIDATION_CHANNEL).bind(&namespace);
+    dbx.execute(sqlx_query).await?;
+  }
+
+  if dbx.has_open_txn().await {
+    mm.defer_invalidation(namespace);
+  } else if let Err(e) = mm.cache().invalidate(&namespace).await {
+    warn!("Invalidate cache '{}' failed: {}", namespace, e);
+  }
+  Ok(())
+}
+
+/// 在后台监听缓存失效通知，使当前实例的缓存失效。监听连接断开后会自动重连，并清空所有缓存以免遗漏断开期间的通知。
+///
+/// 监听 `mm` 所在的数据库：`database_per_tenant` 模式下租户表的通知由租户数据库发送，需对各租户的 ModelManager
+///（[ModelManager::with_ctx]）分别调用
+#[cfg(feature = "postgres")]
+pub async fn listen(mm: &ModelManager) -> Result<JoinHandle<()>> {
+  let db = mm.tenant_dbx().unwrap_or(mm.dbx()).db();
+  let mut listener = PgListener::connect_with(db).await.map_err(dbx::Error::from)?;
+  listener.listen(INVALIDATION_CHANNEL).await.map_err(dbx::Error::from)?;
+
+  let backend = mm.cache().clone();
+  let handle = tokio::spawn(async move {
+    loop {
+      match listener.try_recv().await {
+        Ok(Some(notification)) => {
+          if let Err(e) = backend.invalidate(notification.payload()).await {
+            warn!("Invalidate cache '{}' failed: {}", notification.payload(), e);
+          }
+        }
+        Ok(None) => {
+          info!("Cache invalidation listener reconnecting, clear all cache entries");
+          if let Err(e) = backend.clear().await {
+            warn!("Clear cache failed: {}", e);
+          }
+        }
+        Err(e) => {
+          error!("Cache invalidation listener failed: {}", e);
+          tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+      }
+    }
+  });
+  Ok(handle)
+}
+
+/// 查询的缓存键
+pub(crate) fn query_key<MC: DbBmc>(mm: &ModelManager, sql: &str, values: &SqlxValues) -> Result<String> {
+  let tenant_id = mm.tenancy().tenant_of::<MC>(mm.ctx_opt_ref())?;
+  Ok(format!("{:?}|{}|{:?}", tenant_id, sql, values.0))
+}
+
+/// 表 `MC` 启用缓存且不在事务内时先从缓存读取，未命中时执行 `load` 并写入缓存。缓存后端出错时只记录日志
+pub(crate) async fn get_or_load<MC, T, F, Fut>(mm: &ModelManager, key: String, load: F) -> Result<T>
+where
+  MC: DbBmc,
+  T: Serialize + DeserializeOwned,
+  F: FnOnce() -> Fut,
+  Fut: Future<Output = Result<T>>,
+{
+  let Some(policy) = MC::cache().filter(|_| mm.dbx().non_txn()) else {
+    return load().await;
+  };
+
+  let namespace = namespace::<MC>();
+  match mm.cache().get(&namespace, &key).await {
+    Ok(Some(bytes)) => match serde_json::from_slice(&bytes) {
+      Ok(value) => return Ok(value),
+      Err(e) => warn!("Deserialize cache entry of '{}' failed: {}", namespace, e),
+    },
+    Ok(None) => {}
+    Err(e) => warn!("Get cache entry of '{}' failed: {}", namespace, e),
+  }
+
+  let value = load().await?;
+  match serde_json::to_vec(&value) {
+    Ok(bytes) => {
+      if let Err(e) = mm.cache().put(&namespace, &key, bytes, &policy).await {
+        warn!("Put cache entry of '{}' failed: {}", namespace, e);
+      }
+    }
+    Err(e) => warn!("Serialize cache entry of '{}' failed: {}", namespace, e),
+  }
+  Ok(value)
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+  use modql::field::Fields;
+  use serde::{Deserialize, Serialize};
+  use sqlx::FromRow;
+  use ultimate::configuration::model::DbConf;
+  use ultimate::ctx::Ctx;
+
+  use super::CachePolicy;
+  use crate::base::{self, DbBmc};
+  use crate::{Error, ModelManager};
+
+  struct CachedDictBmc;
+  impl DbBmc for CachedDictBmc {
+    const TABLE: &'static str = "dict";
+
+    fn cache() -> Option<CachePolicy> {
+      Some(CachePolicy::default())
+    }
+  }
+
+  #[derive(Fields)]
+  struct DictForUpdate {
+    value: String,
+  }
+
+  #[derive(Debug, FromRow, Fields, Serialize, Deserialize)]
+  struct Dict {
+    id: i64,
+    name: String,
+    value: String,
+  }
+
+  #[derive(Fields)]
+  struct DictForCreate {
+    name: String,
+    value: String,
+  }
+
+  fn dict(name: &str, value: &str) -> DictForCreate {
+    DictForCreate { name: name.to_string(), value: value.to_string() }
+  }
+
+  #[tokio::test]
+  async fn test_cache_invalidation_after_commit_on_sqlite() -> anyhow::Result<()> {
+    let conf: DbConf = serde_json::from_value(serde_json::json!({ "enable": true, "url": "sqlite::memory:" }))?;
+    let mm = ModelManager::new(&conf).await?.with_ctx(Ctx::new_root());
+    let create_table = "CREATE TABLE dict (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL UNIQUE, \
+      value TEXT NOT NULL, cid INTEGER, ctime TEXT, mid INTEGER, mtime TEXT)";
+    mm.dbx().execute(sqlx::query(create_table)).await?;
+    let id = base::create::<CachedDictBmc, _>(&mm, dict("a", "1")).await?;
+    let cached = |mm: ModelManager| async move { base::find_by_id_cached::<CachedDictBmc, Dict>(&mm, id.into()).await };
+    assert_eq!(cached(mm.clone()).await?.value, "1");
+
+    // 提交前其它查询仍读取缓存，提交后缓存失效
+    let outer = mm.clone();
+    mm.transaction(|txn_mm| {
+      let outer = outer.clone();
+      async move {
+        base::update_by_id::<CachedDictBmc, _>(&txn_mm, id.into(), DictForUpdate { value: "2".to_string() }).await?;
+        assert_eq!(cached(outer).await?.value, "1");
+        Ok(())
+      }
+    })
+    .await?;
+    assert_eq!(cached(mm.clone()).await?.value, "2");
+
+    // 回滚时缓存不失效
+    let result: crate::Result<()> = mm
+      .transaction(|mm| async move {
+        base::update_by_id::<CachedDictBmc, _>(&mm, id.into(), DictForUpdate { value: "3".to_string() }).await?;
+        Err(Error::InvalidArgument { message: "rollback".to_string() })
+      })
+      .await;
+    assert!(result.is_err());
+    let (value,): (String,) = sqlx::query_as("SELECT value FROM dict").fetch_one(mm.dbx().db()).await?;
+    assert_eq!((value.as_str(), cached(mm.clone()).await?.value.as_str()), ("2", "2"));
+    Ok(())
+  }
+}
@@ -1,9 +1,9 @@
-use std::borrow::Cow;
-
-use sqlx::error::DatabaseError;
+use sqlx::error::{DatabaseError, ErrorKind};
 use thiserror::Error;
+use ultimate::configuration::model::TenancyMode;
 use ultimate::DataError;
 
+use crate::store::{backend, DbKind};
 use crate::Id;
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -13,6 +13,18 @@ pub enum Error {
   #[error("Unauthorized")]
   Unauthorized,
 
+  #[error("Forbidden, missing privilege: {privilege}")]
+  Forbidden { privilege: String },
+
+  #[error("Tenant required")]
+  TenantRequired,
+
+  #[error("Access control system not initialized.")]
+  AcsNotInitialized,
+
+  #[error("Access control system already initialized.")]
+  AcsAlreadyInitialized,
+
   #[error("Invalid argment, error message: {message}")]
   InvalidArgument { message: String },
 
@@ -25,9 +37,30 @@ pub enum Error {
   #[error("List limit over max. max: {max}, actual: {actual}")]
   ListLimitOverMax { max: i64, actual: i64 },
 
+  #[error("Invalid cursor")]
+  InvalidCursor,
+
+  #[error("Optimistic lock conflict. table is '{schema}.{table}'")]
+  OptimisticLockConflict { schema: &'static str, table: &'static str },
+
   #[error("Count fail")]
   CountFail,
 
+  #[error("Outbox dispatch failed. sink: {sink}, error: {message}")]
+  OutboxDispatchFailed { sink: String, message: String },
+
+  #[error("Invalid cron expression '{expression}', error: {message}")]
+  InvalidCronExpression { expression: String, message: String },
+
+  #[error("Cache backend failed. error: {message}")]
+  CacheBackendFailed { message: String },
+
+  #[error("'{feature}' is not supported by {backend}")]
+  UnsupportedByBackend { feature: &'static str, backend: DbKind },
+
+  #[error("'{feature}' is not supported in {mode:?} tenancy mode")]
+  UnsupportedByTenancy { feature: &'static str, mode: TenancyMode },
+
   // -- DB
   #[error("User already exists. {key}: '{value}'")]
   UserAlreadyExists { key: &'static str, value: String },
@@ -39,14 +72,23 @@ pub enum Error {
   #[error("Can't create ModelManagerProvider. provider: {0}")]
   CantCreateModelManagerProvider(String),
 
+  #[error("Transaction partially committed, the primary database was committed. error: {message}")]
+  PartialCommit { message: String },
+
   // -- Modules
   #[error(transparent)]
   SecurityError(#[from] ultimate::security::Error),
 
+  #[error(transparent)]
+  UltimateCommonError(#[from] ultimate_common::Error),
+
   #[error(transparent)]
   DbxError(#[from] crate::store::dbx::Error),
 
   // -- Externals
+  #[error(transparent)]
+  MigrateError(#[from] sqlx::migrate::MigrateError),
+
   #[error(transparent)]
   SeaQueryError(#[from] sea_query::error::Error),
 
@@ -58,23 +100,28 @@ pub enum Error {
 }
 
 impl Error {
-  /// This function will transform the error into a more precise variant if it is an SQLX or PGError Unique Violation.
+  /// This function will transform the error into a more precise variant if it is an SQLX Unique Violation.
   /// The resolver can contain a function (table_name: &str, constraint: &str) that may return a specific Error if desired.
   /// If the resolver is None, or if the resolver function returns None, it will default to Error::UniqueViolation {table, constraint}.
+  ///
+  /// 只有 PostgreSQL 的错误包含表名及约束名，MySQL、SQLite 时二者为空字符串
   pub fn resolve_unique_violation<F>(self, resolver: Option<F>) -> Self
   where
     F: FnOnce(&str, &str) -> Option<Self>,
   {
-    match self.as_database_error().map(|db_error| (db_error.code(), db_error.table(), db_error.constraint())) {
-      // "23505" => postgresql "unique violation"
-      Some((Some(Cow::Borrowed("23505")), Some(table), Some(constraint))) => resolver
-        .and_then(|fun| fun(table, constraint))
-        .unwrap_or_else(|| Error::UniqueViolation { table: table.to_string(), constraint: constraint.to_string() }),
+    match self.as_database_error() {
+      Some(db_error) if db_error.kind() == ErrorKind::UniqueViolation => {
+        let table = db_error.table().unwrap_or_default();
+        let constraint = db_error.constraint().unwrap_or_default();
+        resolver
+          .and_then(|fun| fun(table, constraint))
+          .unwrap_or_else(|| Error::UniqueViolation { table: table.to_string(), constraint: constraint.to_string() })
+      }
       _ => self,
     }
   }
 
-  /// A convenient function to return the eventual database error (Postgres)
+  /// A convenient function to return the eventual database error
   /// if this Error is an SQLX Error that contains a database error.
   pub fn as_database_error(&self) -> Option<&(dyn DatabaseError + 'static)> {
     match self {
@@ -82,15 +129,28 @@ impl Error {
       _ => None,
     }
   }
+
+  /// 是否为序列化失败（SQLSTATE 40001，SQLite 为数据库被锁定），重试整个事务通常可以成功
+  pub fn is_serialization_failure(&self) -> bool {
+    let code = self.as_database_error().and_then(|db_error| db_error.code());
+    code.is_some_and(|code| backend::SERIALIZATION_FAILURE_CODES.contains(&code.as_ref()))
+  }
 }
 
 impl From<Error> for DataError {
   fn from(e: Error) -> Self {
     match e {
+      Error::Unauthorized => Self::unauthorized(e.to_string()),
+      Error::Forbidden { .. } => Self::forbidden(e.to_string()),
+      Error::TenantRequired => Self::forbidden(e.to_string()),
       Error::EntityNotFound { .. } => Self::not_found(e.to_string()),
       Error::NotFound { .. } => Self::not_found(e.to_string()),
       Error::UserAlreadyExists { .. } => Self::confilicted(e.to_string()),
       Error::UniqueViolation { .. } => Self::confilicted(e.to_string()),
+      Error::OptimisticLockConflict { .. } => Self::confilicted(e.to_string()),
+      Error::InvalidArgument { .. } => Self::bad_request(e.to_string()),
+      Error::InvalidCursor => Self::bad_request(e.to_string()),
+      Error::InvalidCronExpression { .. } => Self::bad_request(e.to_string()),
       Error::SeaQueryError(_) => Self::bad_request(e.to_string()),
       _ => DataError::server_error(e.to_string()),
     }
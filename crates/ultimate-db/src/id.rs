@@ -5,11 +5,14 @@ use modql::{
 };
 use sea_query::SimpleExpr;
 use serde::{Deserialize, Serialize};
-use sqlx::{postgres::PgRow, FromRow};
+use sqlx::FromRow;
+use ultimate_common::id::IdGenerator;
 
-pub trait DbRowType: HasSeaFields + for<'r> FromRow<'r, PgRow> + Unpin + Send {}
+use crate::store::DbRow;
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Display)]
+pub trait DbRowType: HasSeaFields + for<'r> FromRow<'r, DbRow> + Unpin + Send {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Display)]
 #[serde(untagged)]
 pub enum Id {
   I32(i32),
@@ -93,6 +96,30 @@ impl From<&uuid::Uuid> for Id {
   }
 }
 
+#[cfg(feature = "ulid")]
+impl From<ulid::Ulid> for Id {
+  fn from(value: ulid::Ulid) -> Self {
+    Id::String(value.to_string())
+  }
+}
+
+/// 在插入数据时由应用生成主键 ID，见 [crate::base::DbBmc::id_generator]。
+///
+/// 已为所有 `Id` 类型可转换为 [Id] 的 [IdGenerator] 实现。
+pub trait DbIdGenerator: Send + Sync {
+  fn next_db_id(&self) -> crate::Result<Id>;
+}
+
+impl<G> DbIdGenerator for G
+where
+  G: IdGenerator,
+  G::Id: Into<Id>,
+{
+  fn next_db_id(&self) -> crate::Result<Id> {
+    Ok(self.next_id()?.into())
+  }
+}
+
 pub fn to_vec_id<V, I>(ids: I) -> Vec<Id>
 where
   V: Into<Id>,
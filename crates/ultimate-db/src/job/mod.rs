@@ -0,0 +1,326 @@
+//! 基于 Postgres 的后台任务队列。
+//!
+//! 任务通过 [enqueue] 写入 `job.job` 表（存在事务时在该事务内写入），[JobWorker] 在后台以 `FOR UPDATE SKIP LOCKED`
+//! 领取到期的任务并交给按任务类型注册的处理函数执行，多个实例可同时运行。
+//!
+//! - 延时任务：[NewJob::with_run_at]、[NewJob::with_delay]
+//! - 唯一键：同一任务类型下，唯一键相同的任务在待执行或执行中时只会存在一个，见 [NewJob::with_unique_key]
+//! - 可见性超时：任务被领取后在 `visibility_timeout` 内未完成（如进程崩溃）时，会被其它 worker 重新领取
+//! - 重试：执行失败时按指数退避重试，超过最大次数后转为死信（[JobStatus::Dead]），可通过 [requeue_dead] 重新执行
+//! - Cron：[CronJob] 按 cron 表达式定时入队
+//!
+//! 任务至少执行一次（at-least-once），处理函数应保证幂等。表结构见 [MIGRATOR]。
+//!
+//! ```rust,no_run
+//! use serde::{Deserialize, Serialize};
+//! use ultimate_db::{job, ModelManager};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct SendMessage {
+//!   user_id: i64,
+//!   content: String,
+//! }
+//!
+//! impl job::Job for SendMessage {
+//!   const JOB_TYPE: &'static str = "send_message";
+//! }
+//!
+//! async fn send_later(mm: &ModelManager) -> ultimate_db::Result<()> {
+//!   let message = SendMessage { user_id: 1, content: "Hello".to_string() };
+//!   let new_job = job::NewJob::new(&message)?.with_delay(std::time::Duration::from_secs(60)).with_unique_key("1");
+//!   job::enqueue(mm, new_job).await?;
+//!   Ok(())
+//! }
+//!
+//! fn start_worker(mm: ModelManager) -> ultimate_db::Result<()> {
+//!   let daily = SendMessage { user_id: 1, content: "Daily report".to_string() };
+//!   job::JobWorker::new(mm)
+//!     .with_handler(|_mm: ModelManager, message: SendMessage| async move {
+//!       println!("send to {}: {}", message.user_id, message.content);
+//!       Ok(())
+//!     })
+//!     .with_cron(job::CronJob::new("daily-report", "0 0 1 * * *", job::NewJob::new(&daily)?)?)
+//!     .spawn();
+//!   Ok(())
+//! }
+//! ```
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use modql::SIden;
+use sea_query::{Alias, Expr, IntoIden, OnConflict, PostgresQueryBuilder, Query, TableRef};
+use sea_query_binder::SqlxBinder;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::FromRow;
+use ultimate_common::time::UtcDateTime;
+
+use crate::migration::{self, Migrator};
+use crate::queue::{self, now_minus, now_plus};
+use crate::{ModelManager, Result};
+
+mod scheduler;
+mod worker;
+
+pub use scheduler::*;
+pub use worker::*;
+
+/// 创建任务队列表的迁移
+pub static MIGRATOR: LazyLock<Migrator> = LazyLock::new(|| migration::builtin(sqlx::migrate!("./migrations/job")));
+
+/// 任务队列表所在的 schema
+pub const JOB_SCHEMA: &str = "job";
+
+/// 任务表
+pub const JOB_TABLE: &str = "job";
+
+/// Cron Job 表
+pub const JOB_CRON_TABLE: &str = "job_cron";
+
+/// 默认队列
+pub const DEFAULT_QUEUE: &str = "default";
+
+/// 默认最大执行次数
+pub const DEFAULT_MAX_ATTEMPTS: i32 = 10;
+
+const JOB_COLUMNS: [&str; 13] = [
+  "id",
+  "queue",
+  "job_type",
+  "payload",
+  "status",
+  "attempts",
+  "max_attempts",
+  "unique_key",
+  "run_at",
+  "last_error",
+  "tenant_id",
+  "request_id",
+  "ctime",
+];
+
+/// 任务状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[repr(i32)]
+pub enum JobStatus {
+  Pending = 100,
+  Running = 200,
+  Succeeded = 300,
+  Dead = 400,
+}
+
+impl From<JobStatus> for sea_query::Value {
+  fn from(value: JobStatus) -> Self {
+    sea_query::Value::Int(Some(value as i32))
+  }
+}
+
+/// 强类型的任务，以 JSON 格式保存在任务表中
+pub trait Job: Serialize + DeserializeOwned + Send + 'static {
+  /// 任务类型，用于匹配处理函数
+  const JOB_TYPE: &'static str;
+
+  /// 任务所在的队列
+  const QUEUE: &'static str = DEFAULT_QUEUE;
+
+  /// 最大执行次数，超过后转为死信
+  const MAX_ATTEMPTS: i32 = DEFAULT_MAX_ATTEMPTS;
+}
+
+/// 待写入的任务
+#[derive(Debug, Clone)]
+pub struct NewJob {
+  queue: String,
+  job_type: String,
+  payload: Value,
+  max_attempts: i32,
+  unique_key: Option<String>,
+  run_at: Option<UtcDateTime>,
+  delay: Option<Duration>,
+}
+
+impl NewJob {
+  pub fn new<J: Job>(job: &J) -> Result<Self> {
+    Ok(Self {
+      queue: J::QUEUE.to_string(),
+      job_type: J::JOB_TYPE.to_string(),
+      payload: serde_json::to_value(job)?,
+      max_attempts: J::MAX_ATTEMPTS,
+      unique_key: None,
+      run_at: None,
+      delay: None,
+    })
+  }
+
+  /// 覆盖 [Job::QUEUE]
+  pub fn with_queue(mut self, queue: impl Into<String>) -> Self {
+    self.queue = queue.into();
+    self
+  }
+
+  /// 覆盖 [Job::MAX_ATTEMPTS]
+  pub fn with_max_attempts(mut self, max_attempts: i32) -> Self {
+    self.max_attempts = max_attempts.max(1);
+    self
+  }
+
+  /// 唯一键。同一任务类型下已存在唯一键相同且待执行或执行中的任务时，不会写入新任务
+  pub fn with_unique_key(mut self, unique_key: impl Into<String>) -> Self {
+    self.unique_key = Some(unique_key.into());
+    self
+  }
+
+  /// 在指定时间之后执行
+  pub fn with_run_at(mut self, run_at: UtcDateTime) -> Self {
+    self.run_at = Some(run_at);
+    self.delay = None;
+    self
+  }
+
+  /// 在数据库当前时间的 `delay` 之后执行
+  pub fn with_delay(mut self, delay: Duration) -> Self {
+    self.delay = Some(delay);
+    self.run_at = None;
+    self
+  }
+
+  pub fn queue(&self) -> &str {
+    &self.queue
+  }
+
+  pub fn job_type(&self) -> &str {
+    &self.job_type
+  }
+
+  pub fn payload(&self) -> &Value {
+    &self.payload
+  }
+
+  pub fn unique_key(&self) -> Option<&str> {
+    self.unique_key.as_deref()
+  }
+}
+
+/// 任务表中的任务
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct JobRecord {
+  pub id: i64,
+  pub queue: String,
+  pub job_type: String,
+  pub payload: Value,
+  pub status: JobStatus,
+  pub attempts: i32,
+  pub max_attempts: i32,
+  pub unique_key: Option<String>,
+  pub run_at: UtcDateTime,
+  pub last_error: Option<String>,
+  pub tenant_id: Option<i64>,
+  pub request_id: Option<String>,
+  pub ctime: UtcDateTime,
+}
+
+/// 写入任务，返回任务 ID。因唯一键重复未写入时返回 None。`mm` 存在事务时在该事务内写入。
+///
+/// 任务表位于主库，`database_per_tenant` 模式下 `mm` 已切换到租户数据库时返回 [crate::Error::UnsupportedByTenancy]
+pub async fn enqueue(mm: &ModelManager, job: NewJob) -> Result<Option<i64>> {
+  let ctx = mm.ctx_ref()?;
+  let dbx = mm.primary_dbx("job")?;
+
+  let run_at = match (job.run_at, job.delay) {
+    (Some(run_at), _) => Expr::val(run_at).into(),
+    (None, Some(delay)) => now_plus(delay),
+    (None, None) => Expr::cust("now()"),
+  };
+
+  // -- Build query
+  let mut query = Query::insert();
+  query
+    .into_table(job_table_ref())
+    .columns(
+      ["queue", "job_type", "payload", "max_attempts", "unique_key", "run_at", "tenant_id", "request_id", "cid"]
+        .map(Alias::new),
+    )
+    .values([
+      job.queue.into(),
+      job.job_type.into(),
+      Expr::cust_with_values("CAST($1 AS JSONB)", [job.payload.to_string()]),
+      job.max_attempts.into(),
+      job.unique_key.into(),
+      run_at,
+      ctx.tenant_id().into(),
+      ctx.request_id().map(ToString::to_string).into(),
+      ctx.uid().into(),
+    ])?
+    // 推断唯一索引 job_uidx_unique_key，条件需与索引的 where 子句一致
+    .on_conflict(
+      OnConflict::columns([Alias::new("job_type"), Alias::new("unique_key")])
+        .target_and_where(Expr::cust("status in (100, 200)"))
+        .do_nothing()
+        .to_owned(),
+    )
+    .returning(Query::returning().column(Alias::new("id")));
+
+  // -- Exec query
+  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+  let sqlx_query = sqlx::query_as_with::<_, (i64,), _>(&sql, values);
+  let id = dbx.fetch_optional(sqlx_query).await?;
+  Ok(id.map(|(id,)| id))
+}
+
+/// 查询任务
+pub async fn find_by_id(mm: &ModelManager, id: i64) -> Result<Option<JobRecord>> {
+  let mut query = Query::select();
+  query.from(job_table_ref()).columns(JOB_COLUMNS.map(Alias::new)).and_where(Expr::col(Alias::new("id")).eq(id));
+
+  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+  let sqlx_query = sqlx::query_as_with::<_, JobRecord, _>(&sql, values);
+  let job = mm.dbx().fetch_optional(sqlx_query).await?;
+  Ok(job)
+}
+
+/// 查询死信任务，按 ID 升序排列
+pub async fn find_dead(mm: &ModelManager, limit: u64) -> Result<Vec<JobRecord>> {
+  queue::find_dead(mm, job_table_ref(), &JOB_COLUMNS, JobStatus::Dead, limit).await
+}
+
+/// 将死信任务重新置为待执行并重置执行次数，返回重新执行的任务数
+pub async fn requeue_dead(mm: &ModelManager, ids: impl IntoIterator<Item = i64>) -> Result<u64> {
+  let values = [("run_at", Expr::cust("now()")), ("finished_at", Expr::cust("NULL"))];
+  queue::requeue_dead(mm, job_table_ref(), ids, JobStatus::Dead, JobStatus::Pending, values).await
+}
+
+/// 取消待执行的任务，返回取消的任务数。执行中的任务不受影响
+pub async fn cancel(mm: &ModelManager, ids: impl IntoIterator<Item = i64>) -> Result<u64> {
+  let mut query = Query::delete();
+  query
+    .from_table(job_table_ref())
+    .and_where(Expr::col(Alias::new("id")).is_in(ids))
+    .and_where(Expr::col(Alias::new("status")).eq(JobStatus::Pending));
+
+  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+  let n = mm.dbx().execute(sqlx::query_with(&sql, values)).await?;
+  Ok(n)
+}
+
+/// 删除完成时间早于 `older_than` 之前的已完成任务，返回删除的任务数
+pub async fn purge_succeeded(mm: &ModelManager, older_than: Duration) -> Result<u64> {
+  let mut query = Query::delete();
+  query
+    .from_table(job_table_ref())
+    .and_where(Expr::col(Alias::new("status")).eq(JobStatus::Succeeded))
+    .and_where(Expr::col(Alias::new("finished_at")).lt(now_minus(older_than)));
+
+  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+  let n = mm.dbx().execute(sqlx::query_with(&sql, values)).await?;
+  Ok(n)
+}
+
+fn job_table_ref() -> TableRef {
+  TableRef::SchemaTable(SIden(JOB_SCHEMA).into_iden(), SIden(JOB_TABLE).into_iden())
+}
+
+fn job_cron_table_ref() -> TableRef {
+  TableRef::SchemaTable(SIden(JOB_SCHEMA).into_iden(), SIden(JOB_CRON_TABLE).into_iden())
+}
@@ -0,0 +1,122 @@
+use std::str::FromStr;
+
+use cron::Schedule;
+use sea_query::{Alias, Expr, LockBehavior, LockType, OnConflict, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use ultimate_common::time::{self, UtcDateTime};
+
+use super::{enqueue, job_cron_table_ref, NewJob};
+use crate::{Error, ModelManager, Result};
+
+/// 按 cron 表达式定时入队的任务，通过 [JobWorker::with_cron](super::JobWorker::with_cron) 注册。
+///
+/// 下次执行时间保存在 `job.job_cron` 表中，多个实例注册同名的 Cron Job 时每次只会入队一次；
+/// 实例全部停止期间错过的多次执行在恢复后只补一次。
+#[derive(Debug, Clone)]
+pub struct CronJob {
+  name: String,
+  expression: String,
+  schedule: Schedule,
+  job: NewJob,
+}
+
+impl CronJob {
+  /// `expression` 为 6 或 7 段的 cron 表达式：`秒 分 时 日 月 周 [年]`，按 UTC 时间计算。
+  /// 如 `0 30 1 * * *` 为每天 01:30:00
+  pub fn new(name: impl Into<String>, expression: impl Into<String>, job: NewJob) -> Result<Self> {
+    let expression = expression.into();
+    let schedule = Schedule::from_str(&expression)
+      .map_err(|e| Error::InvalidCronExpression { expression: expression.clone(), message: e.to_string() })?;
+    Ok(Self { name: name.into(), expression, schedule, job })
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn expression(&self) -> &str {
+    &self.expression
+  }
+
+  /// `time` 之后的下次执行时间，没有下次执行时返回 None
+  pub fn next_after(&self, time: &UtcDateTime) -> Option<UtcDateTime> {
+    self.schedule.after(time).next()
+  }
+
+  /// 到达执行时间时入队，返回是否入队
+  pub(crate) async fn enqueue_due(&self, mm: &ModelManager) -> Result<bool> {
+    let now = time::now_utc();
+    let Some(first_run_at) = self.next_after(&now) else {
+      return Ok(false);
+    };
+
+    mm.transaction(|mm| async move {
+      let mut insert = Query::insert();
+      insert
+        .into_table(job_cron_table_ref())
+        .columns(["name", "next_run_at"].map(Alias::new))
+        .values([self.name.clone().into(), first_run_at.into()])?
+        .on_conflict(OnConflict::column(Alias::new("name")).do_nothing().to_owned());
+      let (sql, values) = insert.build_sqlx(PostgresQueryBuilder);
+      mm.dbx().execute(sqlx::query_with(&sql, values)).await?;
+
+      // 其它实例正在处理时跳过
+      let mut select = Query::select();
+      select
+        .from(job_cron_table_ref())
+        .column(Alias::new("next_run_at"))
+        .and_where(Expr::col(Alias::new("name")).eq(self.name.as_str()))
+        .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked);
+      let (sql, values) = select.build_sqlx(PostgresQueryBuilder);
+      let sqlx_query = sqlx::query_as_with::<_, (UtcDateTime,), _>(&sql, values);
+      let Some((next_run_at,)) = mm.dbx().fetch_optional(sqlx_query).await? else {
+        return Ok(false);
+      };
+      if next_run_at > now {
+        return Ok(false);
+      }
+
+      let unique_key = format!("cron:{}:{}", self.name, next_run_at.timestamp());
+      enqueue(&mm, self.job.clone().with_run_at(next_run_at).with_unique_key(unique_key)).await?;
+
+      let mut update = Query::update();
+      update
+        .table(job_cron_table_ref())
+        .values([(Alias::new("next_run_at"), first_run_at.into()), (Alias::new("mtime"), Expr::cust("now()"))])
+        .and_where(Expr::col(Alias::new("name")).eq(self.name.as_str()));
+      let (sql, values) = update.build_sqlx(PostgresQueryBuilder);
+      mm.dbx().execute(sqlx::query_with(&sql, values)).await?;
+      Ok(true)
+    })
+    .await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::{TimeZone, Utc};
+  use serde::{Deserialize, Serialize};
+
+  use super::*;
+  use crate::job::Job;
+
+  #[derive(Serialize, Deserialize)]
+  struct Report;
+
+  impl Job for Report {
+    const JOB_TYPE: &'static str = "report";
+  }
+
+  #[test]
+  fn test_cron_job() -> Result<()> {
+    let cron_job = CronJob::new("daily-report", "0 30 1 * * *", NewJob::new(&Report)?)?;
+    let time = Utc.with_ymd_and_hms(2024, 5, 1, 2, 0, 0).unwrap();
+    assert_eq!(cron_job.next_after(&time), Some(Utc.with_ymd_and_hms(2024, 5, 2, 1, 30, 0).unwrap()));
+
+    assert!(matches!(
+      CronJob::new("invalid", "every day", NewJob::new(&Report)?),
+      Err(Error::InvalidCronExpression { .. })
+    ));
+    Ok(())
+  }
+}
@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::time::Duration;
+
+use futures::future::{self, BoxFuture};
+use futures::FutureExt;
+use sea_query::{Alias, Cond, Expr, IntoIden, LockBehavior, LockType, Order, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde_json::Value;
+use tokio::task::JoinHandle;
+use tracing::{error, warn};
+use ultimate::ctx::Ctx;
+
+use super::{job_table_ref, CronJob, Job, JobRecord, JobStatus, DEFAULT_QUEUE, JOB_COLUMNS};
+use crate::queue::{now_plus, RetryBackoff};
+use crate::{ModelManager, Result};
+
+/// [JobWorker] 的选项
+#[derive(Debug, Clone)]
+pub struct WorkerOptions {
+  worker_id: String,
+  queues: Vec<String>,
+  concurrency: u64,
+  poll_interval: Duration,
+  visibility_timeout: Duration,
+  retry_backoff: RetryBackoff,
+}
+
+impl Default for WorkerOptions {
+  fn default() -> Self {
+    Self {
+      worker_id: format!("worker-{}-{}", std::process::id(), ultimate_common::time::now_epoch_millis()),
+      queues: vec![DEFAULT_QUEUE.to_string()],
+      concurrency: 10,
+      poll_interval: Duration::from_secs(1),
+      visibility_timeout: Duration::from_secs(300),
+      retry_backoff: RetryBackoff::default(),
+    }
+  }
+}
+
+impl WorkerOptions {
+  /// worker 标识，记录在任务的 `locked_by` 列中。默认由进程 ID 及启动时间生成
+  pub fn with_worker_id(mut self, worker_id: impl Into<String>) -> Self {
+    self.worker_id = worker_id.into();
+    self
+  }
+
+  /// 处理的队列，默认为 [DEFAULT_QUEUE]
+  pub fn with_queues(mut self, queues: impl IntoIterator<Item = impl Into<String>>) -> Self {
+    self.queues = queues.into_iter().map(Into::into).collect();
+    self
+  }
+
+  /// 每次领取并同时执行的任务数，默认为 10
+  pub fn with_concurrency(mut self, concurrency: u64) -> Self {
+    self.concurrency = concurrency.max(1);
+    self
+  }
+
+  /// 没有到期任务时的轮询间隔，默认为 1 秒
+  pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+    self.poll_interval = poll_interval;
+    self
+  }
+
+  /// 可见性超时，默认为 5 分钟。任务执行超过该时间视为失败，worker 崩溃时任务在该时间之后可被重新领取
+  pub fn with_visibility_timeout(mut self, visibility_timeout: Duration) -> Self {
+    self.visibility_timeout = visibility_timeout;
+    self
+  }
+
+  /// 执行失败后的重试退避
+  pub fn with_retry_backoff(mut self, retry_backoff: RetryBackoff) -> Self {
+    self.retry_backoff = retry_backoff;
+    self
+  }
+
+  pub fn worker_id(&self) -> &str {
+    &self.worker_id
+  }
+
+  pub fn retry_backoff(&self) -> &RetryBackoff {
+    &self.retry_backoff
+  }
+}
+
+type BoxHandler = Box<dyn Fn(ModelManager, Value) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+/// 执行任务队列中的任务。
+///
+/// 只领取已注册处理函数的任务类型，处理函数使用以 [Ctx::new_root] 创建的 [ModelManager] 执行，
+/// 任务写入时的租户 ID 及请求 ID 会带入该上下文。
+pub struct JobWorker {
+  mm: ModelManager,
+  handlers: HashMap<&'static str, BoxHandler>,
+  crons: Vec<CronJob>,
+  options: WorkerOptions,
+}
+
+impl JobWorker {
+  pub fn new(mm: ModelManager) -> Self {
+    Self {
+      mm: mm.with_ctx(Ctx::new_root()),
+      handlers: HashMap::new(),
+      crons: Vec::new(),
+      options: WorkerOptions::default(),
+    }
+  }
+
+  /// 注册任务 `J` 的处理函数，同一任务类型重复注册时后注册的生效
+  pub fn with_handler<J, F, Fut>(mut self, handler: F) -> Self
+  where
+    J: Job,
+    F: Fn(ModelManager, J) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+  {
+    let handler: BoxHandler = Box::new(move |mm, payload| match serde_json::from_value::<J>(payload) {
+      Ok(job) => handler(mm, job).boxed(),
+      Err(e) => future::ready(Err(e.into())).boxed(),
+    });
+    self.handlers.insert(J::JOB_TYPE, handler);
+    self
+  }
+
+  /// 注册 Cron Job，由 worker 在每次轮询时检查是否到达执行时间
+  pub fn with_cron(mut self, cron: CronJob) -> Self {
+    self.crons.push(cron);
+    self
+  }
+
+  pub fn with_options(mut self, options: WorkerOptions) -> Self {
+    self.options = options;
+    self
+  }
+
+  /// 在后台持续执行任务
+  pub fn spawn(self) -> JoinHandle<()> {
+    tokio::spawn(self.run())
+  }
+
+  /// 持续执行任务。领取的任务数达到 `concurrency` 时立即领取下一批，否则等待 `poll_interval`
+  pub async fn run(self) {
+    loop {
+      match self.run_once().await {
+        Ok(n) if n as u64 >= self.options.concurrency => continue,
+        Ok(_) => {}
+        Err(e) => error!("Job worker failed: {}", e),
+      }
+      tokio::time::sleep(self.options.poll_interval).await;
+    }
+  }
+
+  /// 入队到期的 Cron Job，并领取、执行一批到期的任务，返回执行的任务数
+  pub async fn run_once(&self) -> Result<usize> {
+    for cron in self.crons.iter() {
+      if let Err(e) = cron.enqueue_due(&self.mm).await {
+        error!("Enqueue cron job '{}' failed: {}", cron.name(), e);
+      }
+    }
+    if self.handlers.is_empty() {
+      return Ok(0);
+    }
+
+    self.bury_expired().await?;
+    let jobs = self.claim().await?;
+    let n = jobs.len();
+    let results = future::join_all(jobs.into_iter().map(|job| self.execute(job))).await;
+    results.into_iter().collect::<Result<Vec<_>>>()?;
+    Ok(n)
+  }
+
+  /// 以 `FOR UPDATE SKIP LOCKED` 领取到期的任务及可见性超时的任务
+  async fn claim(&self) -> Result<Vec<JobRecord>> {
+    let now = || Expr::cust("now()");
+    let mut subquery = Query::select();
+    subquery
+      .column(Alias::new("id"))
+      .from(job_table_ref())
+      .and_where(Expr::col(Alias::new("queue")).is_in(self.options.queues.iter().map(String::as_str)))
+      .and_where(Expr::col(Alias::new("job_type")).is_in(self.handlers.keys().copied()))
+      .cond_where(
+        Cond::any()
+          .add(
+            Cond::all()
+              .add(Expr::col(Alias::new("status")).eq(JobStatus::Pending))
+              .add(Expr::col(Alias::new("run_at")).lte(now())),
+          )
+          .add(
+            Cond::all()
+              .add(Expr::col(Alias::new("status")).eq(JobStatus::Running))
+              .add(Expr::col(Alias::new("locked_until")).lt(now()))
+              .add(Expr::col(Alias::new("attempts")).lt(Expr::col(Alias::new("max_attempts")))),
+          ),
+      )
+      .order_by(Alias::new("run_at"), Order::Asc)
+      .limit(self.options.concurrency)
+      .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked);
+
+    let mut query = Query::update();
+    query
+      .table(job_table_ref())
+      .values([
+        (Alias::new("status").into_iden(), JobStatus::Running.into()),
+        (Alias::new("attempts").into_iden(), Expr::col(Alias::new("attempts")).add(1)),
+        (Alias::new("locked_by").into_iden(), self.options.worker_id.as_str().into()),
+        (Alias::new("locked_until").into_iden(), now_plus(self.options.visibility_timeout)),
+      ])
+      .and_where(Expr::col(Alias::new("id")).in_subquery(subquery))
+      .returning(Query::returning().columns(JOB_COLUMNS.map(Alias::new)));
+
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    let sqlx_query = sqlx::query_as_with::<_, JobRecord, _>(&sql, values);
+    let jobs = self.mm.dbx().fetch_all(sqlx_query).await?;
+    Ok(jobs)
+  }
+
+  /// 可见性超时且已达到最大执行次数的任务转为死信
+  async fn bury_expired(&self) -> Result<()> {
+    let mut query = Query::update();
+    query
+      .table(job_table_ref())
+      .values([
+        (Alias::new("status").into_iden(), JobStatus::Dead.into()),
+        (Alias::new("last_error").into_iden(), "Visibility timeout expired".into()),
+        (Alias::new("locked_by").into_iden(), Expr::cust("NULL")),
+        (Alias::new("locked_until").into_iden(), Expr::cust("NULL")),
+        (Alias::new("finished_at").into_iden(), Expr::cust("now()")),
+      ])
+      .and_where(Expr::col(Alias::new("status")).eq(JobStatus::Running))
+      .and_where(Expr::col(Alias::new("locked_until")).lt(Expr::cust("now()")))
+      .and_where(Expr::col(Alias::new("attempts")).gte(Expr::col(Alias::new("max_attempts"))));
+
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    let n = self.mm.dbx().execute(sqlx::query_with(&sql, values)).await?;
+    if n > 0 {
+      error!("{} jobs moved to dead letter after visibility timeout expired", n);
+    }
+    Ok(())
+  }
+
+  /// 执行任务并记录结果。处理函数返回错误、panic 或超过可见性超时均视为失败
+  async fn execute(&self, job: JobRecord) -> Result<()> {
+    let result = match self.handlers.get(job.job_type.as_str()) {
+      Some(handler) => 'run: {
+        let mut ctx = Ctx::new_root();
+        if let Some(tenant_id) = job.tenant_id {
+          ctx = ctx.with_tenant_id(tenant_id);
+        }
+        if let Some(request_id) = job.request_id.as_deref() {
+          ctx = ctx.with_request_id(request_id);
+        }
+        let mm = match self.mm.clone().try_with_ctx(ctx) {
+          Ok(mm) => mm,
+          Err(e) => break 'run Err(e.to_string()),
+        };
+
+        let fut = AssertUnwindSafe(handler(mm, job.payload.clone())).catch_unwind();
+        match tokio::time::timeout(self.options.visibility_timeout, fut).await {
+          Ok(Ok(Ok(()))) => Ok(()),
+          Ok(Ok(Err(e))) => Err(e.to_string()),
+          Ok(Err(_)) => Err("Job handler panicked".to_string()),
+          Err(_) => Err(format!("Job timed out after {:?}", self.options.visibility_timeout)),
+        }
+      }
+      None => Err(format!("No handler for job type '{}'", job.job_type)),
+    };
+    self.finish(&job, result).await
+  }
+
+  /// 更新执行结果
+  async fn finish(&self, job: &JobRecord, result: std::result::Result<(), String>) -> Result<()> {
+    let mut values = vec![
+      (Alias::new("locked_by").into_iden(), Expr::cust("NULL")),
+      (Alias::new("locked_until").into_iden(), Expr::cust("NULL")),
+    ];
+    match result {
+      Ok(()) => {
+        values.push((Alias::new("status").into_iden(), JobStatus::Succeeded.into()));
+        values.push((Alias::new("finished_at").into_iden(), Expr::cust("now()")));
+        values.push((Alias::new("last_error").into_iden(), Expr::cust("NULL")));
+      }
+      Err(e) => {
+        if job.attempts >= job.max_attempts {
+          error!("Job #{} ({}) moved to dead letter after {} attempts: {}", job.id, job.job_type, job.attempts, e);
+          values.push((Alias::new("status").into_iden(), JobStatus::Dead.into()));
+          values.push((Alias::new("finished_at").into_iden(), Expr::cust("now()")));
+        } else {
+          warn!("Job #{} ({}) failed, attempts: {}: {}", job.id, job.job_type, job.attempts, e);
+          let delay = self.options.retry_backoff.delay(job.attempts);
+          values.push((Alias::new("status").into_iden(), JobStatus::Pending.into()));
+          values.push((Alias::new("run_at").into_iden(), now_plus(delay)));
+        }
+        values.push((Alias::new("last_error").into_iden(), e.into()));
+      }
+    }
+
+    // 只更新仍由当前 worker 持有的任务，可见性超时后被其它 worker 领取的任务由对方更新
+    let mut query = Query::update();
+    query
+      .table(job_table_ref())
+      .values(values)
+      .and_where(Expr::col(Alias::new("id")).eq(job.id))
+      .and_where(Expr::col(Alias::new("status")).eq(JobStatus::Running))
+      .and_where(Expr::col(Alias::new("locked_by")).eq(self.options.worker_id.as_str()));
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    let n = self.mm.dbx().execute(sqlx::query_with(&sql, values)).await?;
+    if n == 0 {
+      warn!("Job #{} ({}) is no longer held by worker '{}'", job.id, job.job_type, self.options.worker_id);
+    }
+    Ok(())
+  }
+}
@@ -1,17 +1,32 @@
 use ultimate::configuration::model::DbConf;
 
+use crate::migration::{MigrationRunner, Migrator};
+
 pub mod acs;
+pub mod audit;
 pub mod base;
+pub mod cache;
 mod error;
 mod id;
+#[cfg(feature = "postgres")]
+pub mod job;
+pub mod migration;
 mod model_manager;
 mod modql_utils;
+#[cfg(feature = "postgres")]
+pub mod outbox;
+#[cfg(feature = "postgres")]
+pub mod queue;
 pub mod store;
+mod tenancy;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub use error::{Error, Result};
 pub use id::*;
 pub use model_manager::*;
 pub use modql_utils::*;
+pub use tenancy::*;
 
 #[derive(Clone)]
 pub struct DbState {
@@ -24,6 +39,19 @@ impl DbState {
     Ok(DbState { mm })
   }
 
+  /// 同 [DbState::from_config]，`ultimate.db.migrate_on_startup` 为 true 时执行 `migrator` 中未执行的迁移
+  pub async fn from_config_with_migrator(db: &DbConf, migrator: &Migrator) -> Result<Self> {
+    let state = Self::from_config(db).await?;
+    if db.migrate_on_startup() {
+      state.migration_runner(migrator).apply().await?;
+    }
+    Ok(state)
+  }
+
+  pub fn migration_runner<'a>(&self, migrator: &'a Migrator) -> MigrationRunner<'a> {
+    MigrationRunner::new(self.mm.dbx().db().clone(), migrator)
+  }
+
   pub fn mm(&self) -> &ModelManager {
     &self.mm
   }
@@ -0,0 +1,175 @@
+//! 数据库迁移。
+//!
+//! 迁移文件在编译时通过 `sqlx::migrate!` 嵌入程序，文件名格式为 `<VERSION>_<DESCRIPTION>.sql`，
+//! 可回滚的迁移使用 `<VERSION>_<DESCRIPTION>.up.sql` 和 `<VERSION>_<DESCRIPTION>.down.sql`。
+//! 已执行的迁移及其校验和记录在 `_sqlx_migrations` 表中，已执行的迁移文件被修改时返回错误；
+//! 执行迁移时持有 PostgreSQL advisory lock，多个实例同时启动时只有一个实例执行迁移。
+//!
+//! 内置模块的迁移（如 [crate::acs::MIGRATOR]）使用时间戳作为版本号并忽略其它迁移器已执行的迁移，
+//! 可与应用的迁移依次执行；此时应用的迁移器也需要调用 `set_ignore_missing(true)`，否则会因为数据库中存在
+//! 内置模块的迁移而返回错误。
+//!
+//! ```rust,no_run
+//! use ultimate::configuration::model::DbConf;
+//! use ultimate_db::{migration::Migrator, DbState};
+//!
+//! static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+//!
+//! async fn init(db_conf: &DbConf) -> ultimate_db::Result<DbState> {
+//!   // `ultimate.db.migrate_on_startup = true` 时在启动时执行迁移
+//!   DbState::from_config_with_migrator(db_conf, &MIGRATOR).await
+//! }
+//! ```
+use std::collections::HashMap;
+
+use serde::Serialize;
+pub use sqlx::migrate::{Migrate, Migration, MigrationType, Migrator};
+
+use crate::{store::dbx::Db, Result};
+
+/// 迁移的执行状态
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatus {
+  pub version: i64,
+  pub description: String,
+  pub applied: bool,
+  /// 已执行的迁移的校验和是否与迁移文件一致，未执行时为 None
+  pub checksum_matched: Option<bool>,
+  /// 是否可回滚
+  pub reversible: bool,
+}
+
+/// 忽略其它迁移器已执行的迁移，用于内置模块的迁移
+pub(crate) fn builtin(mut migrator: Migrator) -> Migrator {
+  migrator.set_ignore_missing(true);
+  migrator
+}
+
+pub struct MigrationRunner<'a> {
+  db: Db,
+  migrator: &'a Migrator,
+}
+
+impl<'a> MigrationRunner<'a> {
+  pub fn new(db: Db, migrator: &'a Migrator) -> Self {
+    Self { db, migrator }
+  }
+
+  /// 列出所有迁移及其执行状态
+  pub async fn list(&self) -> Result<Vec<MigrationStatus>> {
+    let mut conn = self.db.acquire().await.map_err(crate::store::dbx::Error::from)?;
+    conn.ensure_migrations_table().await?;
+    let applied: HashMap<i64, Vec<u8>> =
+      conn.list_applied_migrations().await?.into_iter().map(|m| (m.version, m.checksum.into_owned())).collect();
+
+    let statuses = self
+      .migrator
+      .iter()
+      .filter(|m| m.migration_type.is_up_migration())
+      .map(|m| {
+        let checksum = applied.get(&m.version);
+        MigrationStatus {
+          version: m.version,
+          description: m.description.to_string(),
+          applied: checksum.is_some(),
+          checksum_matched: checksum.map(|c| c.as_slice() == m.checksum.as_ref()),
+          reversible: m.migration_type.is_reversible(),
+        }
+      })
+      .collect();
+    Ok(statuses)
+  }
+
+  /// 执行所有未执行的迁移
+  pub async fn apply(&self) -> Result<()> {
+    self.migrator.run(&self.db).await?;
+    Ok(())
+  }
+
+  /// 回滚版本大于 `target` 的所有已执行的迁移
+  pub async fn rollback(&self, target: i64) -> Result<()> {
+    self.migrator.undo(&self.db, target).await?;
+    Ok(())
+  }
+
+  /// 回滚最后一个已执行的迁移，返回回滚的版本
+  pub async fn rollback_last(&self) -> Result<Option<i64>> {
+    let mut applied: Vec<i64> = self.list().await?.into_iter().filter(|m| m.applied).map(|m| m.version).collect();
+    let Some(last) = applied.pop() else {
+      return Ok(None);
+    };
+    self.rollback(applied.last().copied().unwrap_or(0)).await?;
+    Ok(Some(last))
+  }
+}
+
+/// 数据库迁移命令，可嵌入应用的命令行。需要启用 `cli` feature
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum MigrateCommand {
+  /// 列出所有迁移及其执行状态
+  List,
+
+  /// 执行所有未执行的迁移
+  Apply,
+
+  /// 回滚迁移，未指定 `--target` 时回滚最后一个已执行的迁移
+  Rollback {
+    /// 回滚版本大于 target 的所有迁移
+    #[arg(long)]
+    target: Option<i64>,
+  },
+}
+
+#[cfg(feature = "cli")]
+impl MigrateCommand {
+  pub async fn run(&self, runner: &MigrationRunner<'_>) -> Result<()> {
+    match self {
+      MigrateCommand::List => {
+        for m in runner.list().await? {
+          let status = match m.checksum_matched {
+            None => "pending",
+            Some(true) => "applied",
+            Some(false) => "applied (checksum mismatch)",
+          };
+          println!("{:>16}  {:<28}  {}", m.version, status, m.description);
+        }
+      }
+      MigrateCommand::Apply => {
+        runner.apply().await?;
+        println!("All migrations applied.");
+      }
+      MigrateCommand::Rollback { target: Some(target) } => {
+        runner.rollback(*target).await?;
+        println!("Rolled back to version {target}.");
+      }
+      MigrateCommand::Rollback { target: None } => match runner.rollback_last().await? {
+        Some(version) => println!("Rolled back version {version}."),
+        None => println!("No applied migrations."),
+      },
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+
+  use super::*;
+
+  #[test]
+  fn test_builtin_migrators() {
+    #[allow(unused_mut)]
+    let mut migrators: Vec<&Migrator> = vec![&crate::acs::MIGRATOR, &crate::audit::MIGRATOR];
+    #[cfg(feature = "postgres")]
+    migrators.extend([&*crate::outbox::MIGRATOR, &*crate::job::MIGRATOR]);
+    let mut versions = HashSet::new();
+    for migrator in migrators {
+      assert!(migrator.ignore_missing);
+      for m in migrator.iter() {
+        assert!(versions.insert(m.version), "Duplicate migration version {}", m.version);
+      }
+    }
+  }
+}
@@ -1,6 +1,21 @@
-use ultimate::{configuration::model::DbConf, ctx::Ctx};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
 
-use crate::store::{dbx::new_db_pool_from_config, Dbx};
+use futures::FutureExt;
+use sea_query::TableRef;
+use tracing::warn;
+use ultimate::{
+  configuration::model::{DbConf, TenancyMode},
+  ctx::Ctx,
+};
+
+use crate::base::{CursorCodec, DbBmc};
+use crate::cache::{CacheBackend, LruCacheBackend};
+use crate::store::dbx::{new_db_pool_from_config, new_replica_set_from_config};
+use crate::store::{Dbx, TxnOptions};
+use crate::Tenancy;
 
 use crate::{Error, Result};
 
@@ -8,6 +23,15 @@ use crate::{Error, Result};
 pub struct ModelManager {
   dbx: Dbx,
   ctx: Option<Ctx>,
+  tenancy: Tenancy,
+  /// `database_per_tenant` 模式下当前租户的数据库
+  tenant_dbx: Option<Dbx>,
+  /// 事务内各租户数据库的 Dbx，在事务内切换 Ctx 时共享同一租户的事务
+  tenant_txn_dbxs: Arc<Mutex<HashMap<i64, Dbx>>>,
+  /// 事务内写入的表的缓存命名空间，在最外层事务提交后失效
+  pending_invalidations: Arc<Mutex<HashSet<String>>>,
+  cursor_codec: CursorCodec,
+  cache: Arc<dyn CacheBackend>,
 }
 
 impl ModelManager {
@@ -15,13 +39,41 @@ impl ModelManager {
   pub async fn new(db_config: &DbConf) -> Result<Self> {
     let db_pool =
       new_db_pool_from_config(db_config).await.map_err(|ex| Error::CantCreateModelManagerProvider(ex.to_string()))?;
-    let dbx = Dbx::new(db_pool, false)?;
-    Ok(ModelManager { dbx, ctx: None })
+    let replicas =
+      new_replica_set_from_config(db_config).map_err(|ex| Error::CantCreateModelManagerProvider(ex.to_string()))?;
+    let dbx = Dbx::new(db_pool, false)?.with_replicas(replicas);
+    let tenancy = Tenancy::from_config(db_config)?;
+    let cursor_codec = CursorCodec::new(db_config.cursor_secret());
+    let cache = Arc::new(LruCacheBackend::default());
+    Ok(ModelManager {
+      dbx,
+      ctx: None,
+      tenancy,
+      tenant_dbx: None,
+      tenant_txn_dbxs: Arc::default(),
+      pending_invalidations: Arc::default(),
+      cursor_codec,
+      cache,
+    })
   }
 
   pub fn clone_with_txn(&self) -> Result<ModelManager> {
     let dbx = Dbx::new(self.dbx.db().clone(), true)?;
-    Ok(ModelManager { dbx, ctx: self.ctx.clone() })
+    let tenant_dbx = self.tenant_dbx.as_ref().map(|d| Dbx::new(d.db().clone(), true)).transpose()?;
+    let mut tenant_txn_dbxs = HashMap::new();
+    if let (Some(tenant_dbx), Some(tenant_id)) = (&tenant_dbx, self.ctx.as_ref().and_then(Ctx::tenant_id)) {
+      tenant_txn_dbxs.insert(tenant_id, tenant_dbx.clone());
+    }
+    Ok(ModelManager {
+      dbx,
+      ctx: self.ctx.clone(),
+      tenancy: self.tenancy.clone(),
+      tenant_dbx,
+      tenant_txn_dbxs: Arc::new(Mutex::new(tenant_txn_dbxs)),
+      pending_invalidations: Arc::default(),
+      cursor_codec: self.cursor_codec.clone(),
+      cache: self.cache.clone(),
+    })
   }
 
   pub fn get_or_clone_with_txn(&self) -> Result<ModelManager> {
@@ -32,10 +84,215 @@ impl ModelManager {
     }
   }
 
+  /// 在事务中执行 `f`：`f` 返回 `Ok` 时提交，返回 `Err` 或 panic 时回滚。
+  ///
+  /// `f` 的参数为事务内的 ModelManager，所有数据库操作都应通过它执行。在事务内嵌套调用时使用保存点（`SAVEPOINT`），
+  /// 内层失败只回滚内层的修改。
+  ///
+  /// ```rust,no_run
+  /// # async fn example(mm: &ultimate_db::ModelManager) -> ultimate_db::Result<()> {
+  /// let in_txn = mm
+  ///   .transaction(|mm| async move {
+  ///     // 使用事务内的 mm 执行数据库操作
+  ///     Ok(mm.dbx().is_txn())
+  ///   })
+  ///   .await?;
+  /// assert!(in_txn);
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub async fn transaction<F, Fut, T>(&self, f: F) -> Result<T>
+  where
+    F: FnMut(ModelManager) -> Fut,
+    Fut: Future<Output = Result<T>>,
+  {
+    self.transaction_with(TxnOptions::default(), f).await
+  }
+
+  /// 同 [ModelManager::transaction]，可指定隔离级别、只读及序列化失败（SQLSTATE 40001）时的重试次数。
+  ///
+  /// 选项只对最外层事务有效。重试时会再次调用 `f`，因此 `f` 不应有数据库以外的副作用。
+  ///
+  /// `database_per_tenant` 模式下事务同时包含主库和租户数据库的事务，二者依次提交，不是原子的：
+  /// 主库已提交而租户数据库提交失败时返回 [Error::PartialCommit]，主库的修改不会回滚，也不会重试，需要调用方自行补偿。
+  pub async fn transaction_with<F, Fut, T>(&self, options: TxnOptions, mut f: F) -> Result<T>
+  where
+    F: FnMut(ModelManager) -> Fut,
+    Fut: Future<Output = Result<T>>,
+  {
+    let mm = self.get_or_clone_with_txn()?;
+    let mut attempt = 0;
+    loop {
+      let outermost = mm.begin_nested(&options).await?;
+
+      let result = match AssertUnwindSafe(f(mm.clone())).catch_unwind().await {
+        Ok(Ok(value)) => mm.commit_nested(outermost).await.map(|_| value),
+        Ok(Err(e)) => {
+          mm.rollback_nested_or_warn().await;
+          Err(e)
+        }
+        Err(panic) => {
+          mm.rollback_nested_or_warn().await;
+          if outermost {
+            mm.flush_invalidations(false).await;
+          }
+          std::panic::resume_unwind(panic);
+        }
+      };
+      if outermost {
+        mm.flush_invalidations(matches!(result, Ok(_) | Err(Error::PartialCommit { .. }))).await;
+      }
+
+      match result {
+        Err(e) if outermost && attempt < options.max_retries() && e.is_serialization_failure() => {
+          attempt += 1;
+          warn!("Transaction serialization failure, retry {}/{}: {}", attempt, options.max_retries(), e);
+          tokio::time::sleep(options.retry_delay(attempt)).await;
+        }
+        result => return result,
+      }
+    }
+  }
+
+  fn txn_dbxs(&self) -> impl Iterator<Item = &Dbx> {
+    std::iter::once(&self.dbx).chain(self.tenant_dbx.as_ref())
+  }
+
+  async fn begin_nested(&self, options: &TxnOptions) -> Result<bool> {
+    let mut outermost = false;
+    let mut begun = Vec::new();
+    for dbx in self.txn_dbxs() {
+      match dbx.begin_nested(options).await {
+        Ok(v) => {
+          outermost |= v;
+          begun.push(dbx);
+        }
+        Err(e) => {
+          for dbx in begun {
+            if let Err(e) = dbx.rollback_nested().await {
+              warn!("Transaction rollback failed: {}", e);
+            }
+          }
+          return Err(e.into());
+        }
+      }
+    }
+    Ok(outermost)
+  }
+
+  /// 依次提交主库和租户数据库，提交失败时回滚其余未提交的数据库
+  async fn commit_nested(&self, outermost: bool) -> Result<()> {
+    let mut committed = false;
+    let mut dbxs = self.txn_dbxs();
+    while let Some(dbx) = dbxs.next() {
+      if let Err(e) = dbx.commit_nested().await {
+        for dbx in dbxs {
+          if let Err(e) = dbx.rollback_nested().await {
+            warn!("Transaction rollback failed: {}", e);
+          }
+        }
+        return Err(if outermost && committed { Error::PartialCommit { message: e.to_string() } } else { e.into() });
+      }
+      committed = true;
+    }
+    Ok(())
+  }
+
+  async fn rollback_nested_or_warn(&self) {
+    for dbx in self.txn_dbxs() {
+      if let Err(e) = dbx.rollback_nested().await {
+        warn!("Transaction rollback failed: {}", e);
+      }
+    }
+  }
+
+  /// 记录事务内写入的表的缓存命名空间，见 [crate::cache::invalidate]
+  pub(crate) fn defer_invalidation(&self, namespace: String) {
+    self.pending_invalidations.lock().unwrap().insert(namespace);
+  }
+
+  /// 最外层事务结束后处理记录的缓存命名空间：`committed` 为 true 时使其失效，否则丢弃
+  async fn flush_invalidations(&self, committed: bool) {
+    let namespaces = std::mem::take(&mut *self.pending_invalidations.lock().unwrap());
+    if !committed {
+      return;
+    }
+    for namespace in namespaces {
+      if let Err(e) = self.cache.invalidate(&namespace).await {
+        warn!("Invalidate cache '{}' failed: {}", namespace, e);
+      }
+    }
+  }
+
+  /// 返回所有查询都发送到主库的 ModelManager，用于需要读取刚写入数据（read-your-writes）的查询
+  pub fn read_primary(&self) -> ModelManager {
+    ModelManager {
+      dbx: self.dbx.with_read_primary(),
+      tenant_dbx: self.tenant_dbx.as_ref().map(Dbx::with_read_primary),
+      ..self.clone()
+    }
+  }
+
   pub fn dbx(&self) -> &Dbx {
     &self.dbx
   }
 
+  /// 表所在数据库的 Dbx，`database_per_tenant` 模式下为当前租户的数据库
+  pub fn dbx_of<MC: DbBmc>(&self) -> Result<&Dbx> {
+    match (&self.tenant_dbx, self.tenancy.tenant_of::<MC>(self.ctx.as_ref())?) {
+      (Some(dbx), Some(_)) => Ok(dbx),
+      (None, Some(_)) if self.tenancy.mode() == TenancyMode::DatabasePerTenant => Err(Error::TenantRequired),
+      _ => Ok(&self.dbx),
+    }
+  }
+
+  /// 主库的 Dbx，用于位于主库的 Outbox、任务队列等表，`feature` 为使用该表的功能。
+  ///
+  /// 已切换到租户数据库时写入无法与租户数据库的事务一起提交，返回 [Error::UnsupportedByTenancy]
+  #[cfg(feature = "postgres")]
+  pub(crate) fn primary_dbx(&self, feature: &'static str) -> Result<&Dbx> {
+    match self.tenant_dbx {
+      Some(_) => Err(Error::UnsupportedByTenancy { feature, mode: self.tenancy.mode() }),
+      None => Ok(&self.dbx),
+    }
+  }
+
+  /// 当前租户数据库的 Dbx，非 `database_per_tenant` 模式或未设置租户时为 None
+  pub fn tenant_dbx(&self) -> Option<&Dbx> {
+    self.tenant_dbx.as_ref()
+  }
+
+  /// 事务内使用过的所有租户数据库的 Dbx
+  #[cfg(feature = "testing")]
+  pub(crate) fn tenant_txn_dbxs(&self) -> Vec<Dbx> {
+    self.tenant_txn_dbxs.lock().unwrap().values().cloned().collect()
+  }
+
+  /// 租户感知的表引用
+  pub fn table_ref<MC: DbBmc>(&self) -> Result<TableRef> {
+    self.tenancy.table_ref::<MC>(self.ctx.as_ref())
+  }
+
+  pub fn tenancy(&self) -> &Tenancy {
+    &self.tenancy
+  }
+
+  /// 游标分页的游标编码
+  pub fn cursor_codec(&self) -> &CursorCodec {
+    &self.cursor_codec
+  }
+
+  /// 查询结果缓存，见 [crate::cache]
+  pub fn cache(&self) -> &Arc<dyn CacheBackend> {
+    &self.cache
+  }
+
+  /// 替换缓存后端，默认为进程内的 [LruCacheBackend]
+  pub fn with_cache_backend(mut self, backend: impl CacheBackend + 'static) -> Self {
+    self.cache = Arc::new(backend);
+    self
+  }
+
   pub fn ctx_ref(&self) -> Result<&Ctx> {
     self.ctx.as_ref().ok_or(Error::Unauthorized)
   }
@@ -44,8 +301,98 @@ impl ModelManager {
     self.ctx.as_ref()
   }
 
-  pub fn with_ctx(mut self, ctx: Ctx) -> Self {
+  /// 使用 `ctx` 的 ModelManager。`database_per_tenant` 模式下切换到 `ctx` 租户的数据库，
+  /// 在事务内时与其它同一事务的 ModelManager 共享该租户数据库的事务。
+  ///
+  /// 无法切换到租户数据库时不回退到主库，访问租户表时由 [ModelManager::dbx_of] 返回 [Error::TenantRequired]，
+  /// 需要立即得到错误时使用 [ModelManager::try_with_ctx]
+  pub fn with_ctx(self, ctx: Ctx) -> Self {
+    match self.clone().try_with_ctx(ctx.clone()) {
+      Ok(mm) => mm,
+      Err(e) => {
+        warn!("Switch to tenant database failed: {}", e);
+        Self { ctx: Some(ctx), tenant_dbx: None, ..self }
+      }
+    }
+  }
+
+  /// 同 [ModelManager::with_ctx]，无法切换到租户数据库时返回错误
+  pub fn try_with_ctx(mut self, ctx: Ctx) -> Result<Self> {
+    self.tenant_dbx = match ctx.tenant_id() {
+      Some(tenant_id) => self.tenant_dbx_for(tenant_id)?,
+      None => None,
+    };
     self.ctx = Some(ctx);
-    self
+    Ok(self)
+  }
+
+  fn tenant_dbx_for(&self, tenant_id: i64) -> Result<Option<Dbx>> {
+    let Some(db) = self.tenancy.tenant_pool(tenant_id) else {
+      return Ok(None);
+    };
+    if !self.dbx.is_txn() {
+      return Ok(Some(Dbx::new(db, false)?));
+    }
+
+    let mut dbxs = self.tenant_txn_dbxs.lock().unwrap();
+    if let Some(dbx) = dbxs.get(&tenant_id) {
+      return Ok(Some(dbx.clone()));
+    }
+    let dbx = Dbx::new(db, true)?;
+    dbxs.insert(tenant_id, dbx.clone());
+    Ok(Some(dbx))
+  }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+  use ultimate::configuration::model::DbConf;
+  use ultimate::ctx::Ctx;
+
+  use crate::{Error, ModelManager};
+
+  #[tokio::test]
+  async fn test_transaction_partial_commit_on_sqlite() -> anyhow::Result<()> {
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_nanos();
+    let dir = std::env::temp_dir().join(format!("ultimate-db-test-{}-{}", std::process::id(), nanos));
+    std::fs::create_dir_all(&dir)?;
+    let conf: DbConf = serde_json::from_value(serde_json::json!({
+      "enable": true,
+      "url": format!("sqlite://{}?mode=rwc", dir.join("main.db").display()),
+      "tenancy": { "mode": "database_per_tenant", "database_template": dir.join("tenant_{tenant_id}.db").display().to_string() },
+    }))?;
+    let mm = ModelManager::new(&conf).await?.with_ctx(Ctx::new_root().with_tenant_id(1));
+    mm.dbx().execute(sqlx::query("CREATE TABLE dict (id INTEGER PRIMARY KEY, name TEXT NOT NULL)")).await?;
+    let tenant_dbx = mm.tenant_dbx().unwrap();
+    tenant_dbx.execute(sqlx::query("CREATE TABLE parent (id INTEGER PRIMARY KEY)")).await?;
+    // 延迟的外键约束在提交时才检查，使租户数据库的提交失败
+    let create_child = "CREATE TABLE child (id INTEGER PRIMARY KEY, \
+      parent_id INTEGER REFERENCES parent (id) DEFERRABLE INITIALLY DEFERRED)";
+    tenant_dbx.execute(sqlx::query(create_child)).await?;
+
+    let calls = std::sync::atomic::AtomicUsize::new(0);
+    let result: crate::Result<()> = mm
+      .transaction(|mm| {
+        calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        async move {
+          mm.dbx().execute(sqlx::query("INSERT INTO dict (id, name) VALUES (1, 'a')")).await?;
+          mm.tenant_dbx().unwrap().execute(sqlx::query("INSERT INTO child (id, parent_id) VALUES (1, 9)")).await?;
+          Ok(())
+        }
+      })
+      .await;
+
+    // 主库已提交，租户数据库未提交，且不会重试
+    assert!(matches!(result, Err(Error::PartialCommit { .. })), "{:?}", result);
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM dict").fetch_one(mm.dbx().db()).await?;
+    assert_eq!(count, 1);
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM child").fetch_one(tenant_dbx.db()).await?;
+    assert_eq!(count, 0);
+
+    mm.dbx().db().close().await;
+    tenant_dbx.db().close().await;
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
   }
 }
@@ -1,6 +1,6 @@
 use modql::filter::{IntoSeaError, SeaResult};
 use serde::Deserialize;
-use ultimate_common::time::{local_offset, Duration, OffsetDateTime, UtcDateTime};
+use ultimate_common::time::{self, local_zone, OffsetDateTime, UtcDateTime};
 
 pub fn time_to_sea_value(json_value: serde_json::Value) -> SeaResult<sea_query::Value> {
   Ok(UtcDateTime::deserialize(json_value)?.into())
@@ -10,7 +10,7 @@ pub fn to_sea_chrono_utc(v: serde_json::Value) -> SeaResult<sea_query::Value> {
   if v.as_str().is_some() {
     Ok(UtcDateTime::deserialize(v)?.into())
   } else if let Some(i) = v.as_i64() {
-    let d = UtcDateTime::MIN_UTC + Duration::milliseconds(i);
+    let d = time::from_milliseconds(i).map_err(|e| IntoSeaError::Custom(e.to_string()))?;
     Ok(sea_query::Value::ChronoDateTimeUtc(Some(Box::new(d))))
   } else {
     Err(IntoSeaError::Custom(format!("Invalid value: incoming is {:?}", v)))
@@ -20,7 +20,8 @@ pub fn to_sea_chrono_offset(v: serde_json::Value) -> SeaResult<sea_query::Value>
   if v.as_str().is_some() {
     Ok(OffsetDateTime::deserialize(v)?.into())
   } else if let Some(i) = v.as_i64() {
-    let d = (OffsetDateTime::MIN_UTC + Duration::milliseconds(i)).with_timezone(local_offset());
+    let d = time::from_milliseconds(i).map_err(|e| IntoSeaError::Custom(e.to_string()))?;
+    let d = d.with_timezone(&local_zone()).fixed_offset();
     Ok(sea_query::Value::ChronoDateTimeWithTimeZone(Some(Box::new(d))))
   } else {
     Err(IntoSeaError::Custom(format!("Invalid value: incoming is {:?}", v)))
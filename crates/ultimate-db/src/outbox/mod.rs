@@ -0,0 +1,181 @@
+//! 事务性 Outbox。
+//!
+//! 业务代码通过 [enqueue] 在当前 `Dbx` 事务内写入事件，事件与业务数据一起提交或回滚；
+//! [OutboxRelay] 在后台使用 `FOR UPDATE SKIP LOCKED` 领取待投递的事件，在事务外分发到 [OutboxSink]，
+//! 投递失败时按指数退避重试，超过最大次数后转为死信（[OutboxStatus::Dead]），可通过 [requeue_dead] 重新投递。
+//!
+//! 事件至少投递一次（at-least-once），sink 应根据 [OutboxMessage::id] 做幂等处理。表结构见 [MIGRATOR]。
+//!
+//! ```rust,no_run
+//! use serde_json::json;
+//! use ultimate_db::{outbox, ModelManager};
+//!
+//! async fn create_order(mm: &ModelManager) -> ultimate_db::Result<()> {
+//!   mm.transaction(|mm| async move {
+//!     // ... 写入业务数据
+//!     outbox::enqueue(&mm, outbox::OutboxEvent::new("order.created", &json!({"id": 1}))?.with_key("1")).await?;
+//!     Ok(())
+//!   })
+//!   .await
+//! }
+//!
+//! fn start_relay(mm: ModelManager) {
+//!   outbox::OutboxRelay::new(mm)
+//!     .with_sink(outbox::LogSink)
+//!     .with_sink(outbox::HandlerSink::default().on("order.created", |message| async move {
+//!       println!("order created: {}", message.payload);
+//!       Ok(())
+//!     }))
+//!     .spawn();
+//! }
+//! ```
+use std::sync::LazyLock;
+
+use modql::SIden;
+use sea_query::{Alias, Expr, IntoIden, PostgresQueryBuilder, Query, TableRef};
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::FromRow;
+use ultimate_common::time::UtcDateTime;
+
+use crate::migration::{self, Migrator};
+use crate::queue;
+use crate::{ModelManager, Result};
+
+mod relay;
+mod sink;
+
+pub use relay::*;
+pub use sink::*;
+
+/// 创建 Outbox 表的迁移
+pub static MIGRATOR: LazyLock<Migrator> = LazyLock::new(|| migration::builtin(sqlx::migrate!("./migrations/outbox")));
+
+/// Outbox 表所在的 schema
+pub const OUTBOX_SCHEMA: &str = "outbox";
+
+/// Outbox 表
+pub const OUTBOX_TABLE: &str = "outbox_event";
+
+const MESSAGE_COLUMNS: [&str; 10] =
+  ["id", "topic", "event_key", "payload", "tenant_id", "request_id", "status", "attempts", "last_error", "ctime"];
+
+/// 事件的投递状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[repr(i32)]
+pub enum OutboxStatus {
+  Pending = 100,
+  Dispatched = 200,
+  Dead = 300,
+}
+
+impl From<OutboxStatus> for sea_query::Value {
+  fn from(value: OutboxStatus) -> Self {
+    sea_query::Value::Int(Some(value as i32))
+  }
+}
+
+/// 待写入 Outbox 的事件
+#[derive(Debug, Clone)]
+pub struct OutboxEvent {
+  topic: String,
+  key: Option<String>,
+  payload: Value,
+}
+
+impl OutboxEvent {
+  pub fn new(topic: impl Into<String>, payload: &impl Serialize) -> Result<Self> {
+    Ok(Self { topic: topic.into(), key: None, payload: serde_json::to_value(payload)? })
+  }
+
+  /// 事件的业务键，如实体 ID
+  pub fn with_key(mut self, key: impl Into<String>) -> Self {
+    self.key = Some(key.into());
+    self
+  }
+
+  pub fn topic(&self) -> &str {
+    &self.topic
+  }
+
+  pub fn key(&self) -> Option<&str> {
+    self.key.as_deref()
+  }
+
+  pub fn payload(&self) -> &Value {
+    &self.payload
+  }
+}
+
+/// Outbox 中的事件
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct OutboxMessage {
+  pub id: i64,
+  pub topic: String,
+  pub event_key: Option<String>,
+  pub payload: Value,
+  pub tenant_id: Option<i64>,
+  pub request_id: Option<String>,
+  pub status: OutboxStatus,
+  pub attempts: i32,
+  pub last_error: Option<String>,
+  pub ctime: UtcDateTime,
+}
+
+/// 写入事件，返回事件 ID。`mm` 存在事务时在该事务内写入。
+///
+/// Outbox 表位于主库，`database_per_tenant` 模式下 `mm` 已切换到租户数据库时返回 [crate::Error::UnsupportedByTenancy]
+pub async fn enqueue(mm: &ModelManager, event: OutboxEvent) -> Result<i64> {
+  let ids = enqueue_many(mm, [event]).await?;
+  Ok(ids[0])
+}
+
+/// 批量写入事件，返回的 ID 与输入的事件一一对应
+pub async fn enqueue_many(mm: &ModelManager, events: impl IntoIterator<Item = OutboxEvent>) -> Result<Vec<i64>> {
+  let ctx = mm.ctx_ref()?;
+  let dbx = mm.primary_dbx("outbox")?;
+  let events: Vec<OutboxEvent> = events.into_iter().collect();
+  if events.is_empty() {
+    return Ok(Vec::new());
+  }
+
+  // -- Build query
+  let mut query = Query::insert();
+  query
+    .into_table(outbox_table_ref())
+    .columns(["topic", "event_key", "payload", "tenant_id", "request_id", "cid"].map(Alias::new))
+    .returning(Query::returning().column(Alias::new("id")));
+  for event in events {
+    query.values([
+      event.topic.into(),
+      event.key.into(),
+      Expr::cust_with_values("CAST($1 AS JSONB)", [event.payload.to_string()]),
+      ctx.tenant_id().into(),
+      ctx.request_id().map(ToString::to_string).into(),
+      ctx.uid().into(),
+    ])?;
+  }
+
+  // -- Exec query
+  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+  let sqlx_query = sqlx::query_as_with::<_, (i64,), _>(&sql, values);
+  let rows = dbx.fetch_all(sqlx_query).await?;
+  Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
+/// 查询死信事件，按 ID 升序排列
+pub async fn find_dead(mm: &ModelManager, limit: u64) -> Result<Vec<OutboxMessage>> {
+  queue::find_dead(mm, outbox_table_ref(), &MESSAGE_COLUMNS, OutboxStatus::Dead, limit).await
+}
+
+/// 将死信事件重新置为待投递并重置重试次数，返回重新投递的事件数
+pub async fn requeue_dead(mm: &ModelManager, ids: impl IntoIterator<Item = i64>) -> Result<u64> {
+  let values = [("next_attempt_at", Expr::cust("now()"))];
+  queue::requeue_dead(mm, outbox_table_ref(), ids, OutboxStatus::Dead, OutboxStatus::Pending, values).await
+}
+
+fn outbox_table_ref() -> TableRef {
+  TableRef::SchemaTable(SIden(OUTBOX_SCHEMA).into_iden(), SIden(OUTBOX_TABLE).into_iden())
+}
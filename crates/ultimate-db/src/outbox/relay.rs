@@ -0,0 +1,245 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use sea_query::{Alias, Expr, IntoIden, LockBehavior, LockType, Order, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use tokio::task::JoinHandle;
+use tracing::{error, warn};
+
+use super::{outbox_table_ref, OutboxMessage, OutboxSink, OutboxStatus, MESSAGE_COLUMNS};
+use crate::queue::{now_plus, RetryBackoff};
+use crate::{ModelManager, Result};
+
+/// [OutboxRelay] 的选项
+#[derive(Debug, Clone)]
+pub struct RelayOptions {
+  batch_size: u64,
+  poll_interval: Duration,
+  visibility_timeout: Duration,
+  max_attempts: i32,
+  retry_backoff: RetryBackoff,
+}
+
+impl Default for RelayOptions {
+  fn default() -> Self {
+    Self {
+      batch_size: 100,
+      poll_interval: Duration::from_secs(1),
+      visibility_timeout: Duration::from_secs(300),
+      max_attempts: 10,
+      retry_backoff: RetryBackoff::default(),
+    }
+  }
+}
+
+impl RelayOptions {
+  /// 每次轮询处理的事件数，默认为 100
+  pub fn with_batch_size(mut self, batch_size: u64) -> Self {
+    self.batch_size = batch_size.max(1);
+    self
+  }
+
+  /// 没有待投递事件时的轮询间隔，默认为 1 秒
+  pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+    self.poll_interval = poll_interval;
+    self
+  }
+
+  /// 可见性超时，默认为 5 分钟。领取的一批事件应在该时间内投递完，超时未投递的事件视为失败，
+  /// relay 崩溃时事件在该时间之后可被重新领取
+  pub fn with_visibility_timeout(mut self, visibility_timeout: Duration) -> Self {
+    self.visibility_timeout = visibility_timeout;
+    self
+  }
+
+  /// 最大投递次数，超过后转为死信，默认为 10
+  pub fn with_max_attempts(mut self, max_attempts: i32) -> Self {
+    self.max_attempts = max_attempts.max(1);
+    self
+  }
+
+  /// 投递失败后的重试退避
+  pub fn with_retry_backoff(mut self, retry_backoff: RetryBackoff) -> Self {
+    self.retry_backoff = retry_backoff;
+    self
+  }
+
+  pub fn retry_backoff(&self) -> &RetryBackoff {
+    &self.retry_backoff
+  }
+}
+
+/// 将 Outbox 中待投递的事件分发到 sink。
+///
+/// 事件以 `FOR UPDATE SKIP LOCKED` 领取后在事务外投递，投递期间不持有锁，多个实例可同时运行。
+/// 投递到所有接受该主题的 sink，任一 sink 失败时整个事件会被重试；没有 sink 接受的事件直接转为死信。
+pub struct OutboxRelay {
+  mm: ModelManager,
+  sinks: Vec<Arc<dyn OutboxSink>>,
+  options: RelayOptions,
+}
+
+impl OutboxRelay {
+  pub fn new(mm: ModelManager) -> Self {
+    Self { mm, sinks: Vec::new(), options: RelayOptions::default() }
+  }
+
+  pub fn with_sink(mut self, sink: impl OutboxSink + 'static) -> Self {
+    self.sinks.push(Arc::new(sink));
+    self
+  }
+
+  pub fn with_options(mut self, options: RelayOptions) -> Self {
+    self.options = options;
+    self
+  }
+
+  /// 在后台持续投递事件
+  pub fn spawn(self) -> JoinHandle<()> {
+    tokio::spawn(self.run())
+  }
+
+  /// 持续投递事件。一批事件处理满时立即处理下一批，否则等待 `poll_interval`
+  pub async fn run(self) {
+    loop {
+      match self.relay_once().await {
+        Ok(n) if n as u64 >= self.options.batch_size => continue,
+        Ok(_) => {}
+        Err(e) => error!("Outbox relay failed: {}", e),
+      }
+      tokio::time::sleep(self.options.poll_interval).await;
+    }
+  }
+
+  /// 领取并投递一批到期的待投递事件，返回领取的事件数
+  pub async fn relay_once(&self) -> Result<usize> {
+    self.bury_expired().await?;
+    let messages = self.claim().await?;
+    let deadline = Instant::now() + self.options.visibility_timeout;
+    for message in messages.iter() {
+      let result = match deadline.checked_duration_since(Instant::now()).filter(|d| !d.is_zero()) {
+        Some(remaining) => match tokio::time::timeout(remaining, self.dispatch(message)).await {
+          Ok(result) => result,
+          Err(_) => Err(format!("Dispatch timed out after {:?}", self.options.visibility_timeout)),
+        },
+        None => Err(format!("Visibility timeout expired after {:?}", self.options.visibility_timeout)),
+      };
+      self.mark(message, result).await?;
+    }
+    Ok(messages.len())
+  }
+
+  /// 以 `FOR UPDATE SKIP LOCKED` 领取到期的事件：增加投递次数，并将 `next_attempt_at` 延后可见性超时作为租约
+  async fn claim(&self) -> Result<Vec<OutboxMessage>> {
+    let mut subquery = Query::select();
+    subquery
+      .column(Alias::new("id"))
+      .from(outbox_table_ref())
+      .and_where(Expr::col(Alias::new("status")).eq(OutboxStatus::Pending))
+      .and_where(Expr::col(Alias::new("next_attempt_at")).lte(Expr::cust("now()")))
+      .and_where(Expr::col(Alias::new("attempts")).lt(self.options.max_attempts))
+      .order_by(Alias::new("id"), Order::Asc)
+      .limit(self.options.batch_size)
+      .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked);
+
+    let mut query = Query::update();
+    query
+      .table(outbox_table_ref())
+      .values([
+        (Alias::new("attempts").into_iden(), Expr::col(Alias::new("attempts")).add(1)),
+        (Alias::new("next_attempt_at").into_iden(), now_plus(self.options.visibility_timeout)),
+      ])
+      .and_where(Expr::col(Alias::new("id")).in_subquery(subquery))
+      .returning(Query::returning().columns(MESSAGE_COLUMNS.map(Alias::new)));
+
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    let sqlx_query = sqlx::query_as_with::<_, OutboxMessage, _>(&sql, values);
+    let mut messages = self.mm.dbx().fetch_all(sqlx_query).await?;
+    messages.sort_unstable_by_key(|m| m.id);
+    Ok(messages)
+  }
+
+  /// 租约到期且已达到最大投递次数的事件（relay 在投递期间崩溃）转为死信
+  async fn bury_expired(&self) -> Result<()> {
+    let mut query = Query::update();
+    query
+      .table(outbox_table_ref())
+      .values([
+        (Alias::new("status").into_iden(), OutboxStatus::Dead.into()),
+        (Alias::new("last_error").into_iden(), "Visibility timeout expired".into()),
+      ])
+      .and_where(Expr::col(Alias::new("status")).eq(OutboxStatus::Pending))
+      .and_where(Expr::col(Alias::new("next_attempt_at")).lte(Expr::cust("now()")))
+      .and_where(Expr::col(Alias::new("attempts")).gte(self.options.max_attempts));
+
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    let n = self.mm.dbx().execute(sqlx::query_with(&sql, values)).await?;
+    if n > 0 {
+      error!("{} outbox events moved to dead letter after visibility timeout expired", n);
+    }
+    Ok(())
+  }
+
+  /// 投递到所有接受该主题的 sink，返回失败信息
+  async fn dispatch(&self, message: &OutboxMessage) -> std::result::Result<(), String> {
+    let sinks: Vec<&Arc<dyn OutboxSink>> = self.sinks.iter().filter(|s| s.accepts(&message.topic)).collect();
+    if sinks.is_empty() {
+      return Err(format!("No sink accepts topic '{}'", message.topic));
+    }
+
+    let mut errors = Vec::new();
+    for sink in sinks {
+      if let Err(e) = sink.dispatch(message).await {
+        errors.push(format!("{}: {}", sink.name(), e));
+      }
+    }
+    if errors.is_empty() {
+      Ok(())
+    } else {
+      Err(errors.join("; "))
+    }
+  }
+
+  /// 更新投递结果
+  async fn mark(&self, message: &OutboxMessage, result: std::result::Result<(), String>) -> Result<()> {
+    let attempts = message.attempts;
+    let mut values = Vec::new();
+    match result {
+      Ok(()) => {
+        values.push((Alias::new("status").into_iden(), OutboxStatus::Dispatched.into()));
+        values.push((Alias::new("dispatched_at").into_iden(), Expr::cust("now()")));
+        values.push((Alias::new("last_error").into_iden(), Expr::cust("NULL")));
+      }
+      Err(e) => {
+        let dead = attempts >= self.options.max_attempts || !self.sinks.iter().any(|s| s.accepts(&message.topic));
+        if dead {
+          error!(
+            "Outbox event #{} ({}) moved to dead letter after {} attempts: {}",
+            message.id, message.topic, attempts, e
+          );
+          values.push((Alias::new("status").into_iden(), OutboxStatus::Dead.into()));
+        } else {
+          warn!("Outbox event #{} ({}) dispatch failed, attempts: {}: {}", message.id, message.topic, attempts, e);
+          let delay = self.options.retry_backoff.delay(attempts);
+          values.push((Alias::new("next_attempt_at").into_iden(), now_plus(delay)));
+        }
+        values.push((Alias::new("last_error").into_iden(), e.into()));
+      }
+    }
+
+    // 只更新仍由本次领取持有的事件，租约到期后被重新领取的事件由对方更新
+    let mut query = Query::update();
+    query
+      .table(outbox_table_ref())
+      .values(values)
+      .and_where(Expr::col(Alias::new("id")).eq(message.id))
+      .and_where(Expr::col(Alias::new("status")).eq(OutboxStatus::Pending))
+      .and_where(Expr::col(Alias::new("attempts")).eq(attempts));
+    let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+    let n = self.mm.dbx().execute(sqlx::query_with(&sql, values)).await?;
+    if n == 0 {
+      warn!("Outbox event #{} ({}) was claimed again after visibility timeout expired", message.id, message.topic);
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::future::Future;
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use tracing::info;
+
+use super::OutboxMessage;
+use crate::Result;
+
+/// 事件的投递目标
+#[async_trait]
+pub trait OutboxSink: Send + Sync {
+  /// 名称，用于日志和错误信息
+  fn name(&self) -> &str;
+
+  /// 是否投递该主题的事件
+  ///
+  /// default: true
+  fn accepts(&self, _topic: &str) -> bool {
+    true
+  }
+
+  /// 投递事件，返回错误时事件会被重试
+  async fn dispatch(&self, message: &OutboxMessage) -> Result<()>;
+}
+
+type BoxHandler = Box<dyn Fn(OutboxMessage) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+/// 进程内的事件处理函数，按主题注册
+#[derive(Default)]
+pub struct HandlerSink {
+  handlers: HashMap<String, Vec<BoxHandler>>,
+}
+
+impl HandlerSink {
+  /// 注册主题 `topic` 的处理函数，同一主题可注册多个
+  pub fn on<F, Fut>(mut self, topic: impl Into<String>, handler: F) -> Self
+  where
+    F: Fn(OutboxMessage) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+  {
+    let handler: BoxHandler = Box::new(move |message| handler(message).boxed());
+    self.handlers.entry(topic.into()).or_default().push(handler);
+    self
+  }
+}
+
+#[async_trait]
+impl OutboxSink for HandlerSink {
+  fn name(&self) -> &str {
+    "handler"
+  }
+
+  fn accepts(&self, topic: &str) -> bool {
+    self.handlers.contains_key(topic)
+  }
+
+  async fn dispatch(&self, message: &OutboxMessage) -> Result<()> {
+    for handler in self.handlers.get(&message.topic).into_iter().flatten() {
+      handler(message.clone()).await?;
+    }
+    Ok(())
+  }
+}
+
+/// 将事件输出到日志
+pub struct LogSink;
+
+#[async_trait]
+impl OutboxSink for LogSink {
+  fn name(&self) -> &str {
+    "log"
+  }
+
+  async fn dispatch(&self, message: &OutboxMessage) -> Result<()> {
+    info!(
+      "Outbox event dispatched. id: {}, topic: {}, key: {:?}, payload: {}",
+      message.id, message.topic, message.event_key, message.payload
+    );
+    Ok(())
+  }
+}
+
+/// 通过 HTTP POST 将事件以 JSON 格式投递到 webhook。需要启用 `webhook` feature
+///
+/// 请求头 `x-outbox-id`、`x-outbox-topic` 为事件 ID 及主题；设置了密钥时，
+/// `x-outbox-signature` 为请求体的 HMAC-SHA256 签名（十六进制）。响应状态码非 2xx 时视为投递失败。
+/// 请求超时默认为 [WEBHOOK_TIMEOUT]，可通过 [WebhookSink::with_client] 使用自定义的客户端。
+#[cfg(feature = "webhook")]
+pub struct WebhookSink {
+  name: String,
+  url: String,
+  secret: Option<String>,
+  topics: Option<Vec<String>>,
+  client: reqwest::Client,
+}
+
+/// [WebhookSink] 默认的请求超时
+#[cfg(feature = "webhook")]
+pub const WEBHOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[cfg(feature = "webhook")]
+impl WebhookSink {
+  pub fn new(name: impl Into<String>, url: impl Into<String>) -> Self {
+    let client = reqwest::Client::builder().timeout(WEBHOOK_TIMEOUT).build().expect("Failed to build webhook client");
+    Self { name: name.into(), url: url.into(), secret: None, topics: None, client }
+  }
+
+  pub fn with_secret(mut self, secret: impl Into<String>) -> Self {
+    self.secret = Some(secret.into());
+    self
+  }
+
+  /// 只投递指定主题的事件，默认投递所有事件
+  pub fn with_topics(mut self, topics: impl IntoIterator<Item = impl Into<String>>) -> Self {
+    self.topics = Some(topics.into_iter().map(Into::into).collect());
+    self
+  }
+
+  pub fn with_client(mut self, client: reqwest::Client) -> Self {
+    self.client = client;
+    self
+  }
+}
+
+#[cfg(feature = "webhook")]
+#[async_trait]
+impl OutboxSink for WebhookSink {
+  fn name(&self) -> &str {
+    &self.name
+  }
+
+  fn accepts(&self, topic: &str) -> bool {
+    self.topics.as_ref().map_or(true, |topics| topics.iter().any(|t| t == topic))
+  }
+
+  async fn dispatch(&self, message: &OutboxMessage) -> Result<()> {
+    let body = serde_json::to_vec(message)?;
+    let mut request = self
+      .client
+      .post(&self.url)
+      .header(reqwest::header::CONTENT_TYPE, "application/json")
+      .header("x-outbox-id", message.id.to_string())
+      .header("x-outbox-topic", &message.topic);
+    if let Some(secret) = &self.secret {
+      let signature = ultimate_common::digest::hmac_sha256_string(secret.as_bytes(), &body)
+        .map_err(|e| crate::Error::OutboxDispatchFailed { sink: self.name.clone(), message: e.to_string() })?;
+      request = request.header("x-outbox-signature", signature);
+    }
+
+    let response = request
+      .body(body)
+      .send()
+      .await
+      .map_err(|e| crate::Error::OutboxDispatchFailed { sink: self.name.clone(), message: e.to_string() })?;
+    if !response.status().is_success() {
+      return Err(crate::Error::OutboxDispatchFailed {
+        sink: self.name.clone(),
+        message: format!("Unexpected response status: {}", response.status()),
+      });
+    }
+    Ok(())
+  }
+}
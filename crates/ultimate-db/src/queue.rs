@@ -0,0 +1,105 @@
+//! [crate::outbox] 与 [crate::job] 共用的重试退避及死信处理。
+use std::time::Duration;
+
+use sea_query::{Alias, Expr, IntoIden, Order, PostgresQueryBuilder, Query, SimpleExpr, TableRef};
+use sea_query_binder::SqlxBinder;
+use sqlx::FromRow;
+
+use crate::store::DbRow;
+use crate::{ModelManager, Result};
+
+/// 失败后的指数退避：第 n 次重试前等待 `backoff * 2^(n-1)`，最多等待 `max_backoff`。默认为 1 秒及 1 小时
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryBackoff {
+  backoff: Duration,
+  max_backoff: Duration,
+}
+
+impl Default for RetryBackoff {
+  fn default() -> Self {
+    Self { backoff: Duration::from_secs(1), max_backoff: Duration::from_secs(3600) }
+  }
+}
+
+impl RetryBackoff {
+  pub fn new(backoff: Duration, max_backoff: Duration) -> Self {
+    Self { backoff, max_backoff }
+  }
+
+  /// 第 `attempts` 次失败后到下次重试的等待时间
+  pub fn delay(&self, attempts: i32) -> Duration {
+    let exp = attempts.saturating_sub(1).clamp(0, 31) as u32;
+    self.backoff.saturating_mul(2u32.saturating_pow(exp)).min(self.max_backoff)
+  }
+}
+
+/// `now() + duration`
+pub(crate) fn now_plus(duration: Duration) -> SimpleExpr {
+  Expr::cust_with_values("now() + $1 * INTERVAL '1 millisecond'", [duration.as_millis() as i64])
+}
+
+/// `now() - duration`
+pub(crate) fn now_minus(duration: Duration) -> SimpleExpr {
+  Expr::cust_with_values("now() - $1 * INTERVAL '1 millisecond'", [duration.as_millis() as i64])
+}
+
+/// 查询 `table` 中状态为 `dead` 的死信，按 ID 升序排列
+pub(crate) async fn find_dead<T>(
+  mm: &ModelManager,
+  table: TableRef,
+  columns: &[&str],
+  dead: impl Into<SimpleExpr>,
+  limit: u64,
+) -> Result<Vec<T>>
+where
+  T: for<'r> FromRow<'r, DbRow> + Unpin + Send,
+{
+  let mut query = Query::select();
+  query
+    .from(table)
+    .columns(columns.iter().map(|c| Alias::new(*c)))
+    .and_where(Expr::col(Alias::new("status")).eq(dead))
+    .order_by(Alias::new("id"), Order::Asc)
+    .limit(limit);
+
+  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+  let sqlx_query = sqlx::query_as_with::<_, T, _>(&sql, values);
+  let rows = mm.dbx().fetch_all(sqlx_query).await?;
+  Ok(rows)
+}
+
+/// 将 `ids` 中状态为 `dead` 的死信置为 `pending` 并重置执行次数，同时更新 `values` 中的列。返回更新的行数
+pub(crate) async fn requeue_dead(
+  mm: &ModelManager,
+  table: TableRef,
+  ids: impl IntoIterator<Item = i64>,
+  dead: impl Into<SimpleExpr>,
+  pending: impl Into<SimpleExpr>,
+  values: impl IntoIterator<Item = (&'static str, SimpleExpr)>,
+) -> Result<u64> {
+  let mut query = Query::update();
+  query
+    .table(table)
+    .values([(Alias::new("status").into_iden(), pending.into()), (Alias::new("attempts").into_iden(), 0.into())])
+    .values(values.into_iter().map(|(col, value)| (Alias::new(col).into_iden(), value)))
+    .and_where(Expr::col(Alias::new("id")).is_in(ids))
+    .and_where(Expr::col(Alias::new("status")).eq(dead));
+
+  let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+  let n = mm.dbx().execute(sqlx::query_with(&sql, values)).await?;
+  Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_retry_backoff() {
+    let backoff = RetryBackoff::new(Duration::from_secs(1), Duration::from_secs(60));
+    assert_eq!(backoff.delay(1), Duration::from_secs(1));
+    assert_eq!(backoff.delay(3), Duration::from_secs(4));
+    assert_eq!(backoff.delay(10), Duration::from_secs(60));
+    assert_eq!(backoff.delay(100), Duration::from_secs(60));
+  }
+}
@@ -0,0 +1,85 @@
+//! 数据库后端。
+//!
+//! 后端由 feature 选择：`postgres`（默认）、`mysql`、`sqlite`，只能启用其中一个。使用 `mysql`、`sqlite` 时需关闭默认 feature：
+//! `ultimate-db = { version = "0.1", default-features = false, features = ["sqlite", "modql"] }`。
+//! [DbType]、[DbRow]、[DbQueryBuilder] 等为所选后端的类型，`crud_fns` 及 [Dbx](super::Dbx) 均基于这些类型实现。
+//!
+//! 各后端的差异：
+//!
+//! - MySQL 不支持 `RETURNING`，`create`、`create_many`、`upsert` 通过 `LAST_INSERT_ID()` 获取 ID，
+//!   `upsert` 不支持 [DbBmc::data_scope](crate::base::DbBmc::data_scope) 条件
+//! - SQLite 不支持 schema，表引用只使用表名，`schema_per_tenant` 多租户模式不可用；事务的隔离级别及只读选项被忽略
+//! - 审计（[crate::audit]）、发件箱、任务队列、跨实例的缓存失效通知及服务端游标只支持 PostgreSQL
+use derive_more::derive::Display;
+use sea_query::{DynIden, TableRef};
+use sqlx::pool::PoolOptions;
+use sqlx::Database;
+
+#[cfg(not(any(feature = "postgres", feature = "mysql", feature = "sqlite")))]
+compile_error!("ultimate-db requires one of the features: `postgres`, `mysql`, `sqlite`");
+
+#[cfg(any(
+  all(feature = "postgres", feature = "mysql"),
+  all(feature = "postgres", feature = "sqlite"),
+  all(feature = "mysql", feature = "sqlite")
+))]
+compile_error!("ultimate-db features `postgres`, `mysql`, `sqlite` are mutually exclusive");
+
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "postgres")]
+pub use postgres::*;
+
+#[cfg(feature = "mysql")]
+mod mysql;
+#[cfg(feature = "mysql")]
+pub use mysql::*;
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "sqlite")]
+pub use sqlite::*;
+
+/// 数据库的行
+pub type DbRow = <DbType as Database>::Row;
+
+/// 数据库连接
+pub type DbConnection = <DbType as Database>::Connection;
+
+/// 执行写语句的结果
+pub type DbQueryResult = <DbType as Database>::QueryResult;
+
+/// 连接池选项
+pub type DbPoolOptions = PoolOptions<DbType>;
+
+/// 数据库后端的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum DbKind {
+  #[display("PostgreSQL")]
+  Postgres,
+  #[display("MySQL")]
+  MySql,
+  #[display("SQLite")]
+  Sqlite,
+}
+
+impl DbKind {
+  /// 是否支持 `INSERT/UPDATE/DELETE ... RETURNING`
+  pub fn supports_returning(&self) -> bool {
+    !matches!(self, DbKind::MySql)
+  }
+
+  /// 表引用是否可以包含 schema。MySQL 的 schema 即为数据库
+  pub fn supports_schema(&self) -> bool {
+    !matches!(self, DbKind::Sqlite)
+  }
+}
+
+/// 所选后端的表引用，不支持 schema 时只使用表名
+pub fn table_ref(schema: DynIden, table: DynIden) -> TableRef {
+  if DB_KIND.supports_schema() {
+    TableRef::SchemaTable(schema, table)
+  } else {
+    TableRef::Table(table)
+  }
+}
@@ -0,0 +1,86 @@
+use sea_query::{Expr, SimpleExpr};
+use sqlx::mysql::{MySqlConnectOptions, MySqlQueryResult};
+use sqlx::{ConnectOptions, Transaction};
+use ultimate::configuration::model::DbConf;
+
+use super::DbKind;
+use crate::store::dbx::{Db, Result};
+use crate::store::TxnOptions;
+
+pub use sea_query::MysqlQueryBuilder as DbQueryBuilder;
+
+/// 数据库类型
+pub type DbType = sqlx::MySql;
+
+/// 连接选项
+pub type DbConnectOptions = MySqlConnectOptions;
+
+pub const DB_KIND: DbKind = DbKind::MySql;
+
+/// 将列的值转换为文本的类型
+pub(crate) const TEXT_TYPE: &str = "CHAR";
+
+/// 将值转换为整数、浮点数的类型
+pub(crate) const INTEGER_TYPE: &str = "SIGNED";
+pub(crate) const FLOAT_TYPE: &str = "DOUBLE";
+
+/// 序列化失败的错误码（SQLSTATE），死锁（ER_LOCK_DEADLOCK）同样为 40001
+pub(crate) const SERIALIZATION_FAILURE_CODES: &[&str] = &["40001"];
+
+pub(crate) fn connect_options_from_config(c: &DbConf) -> Result<MySqlConnectOptions> {
+  let opts: MySqlConnectOptions = match c.url() {
+    Some(url) => url.parse()?,
+    None => {
+      let mut o = MySqlConnectOptions::new();
+      if let Some(host) = c.host() {
+        o = o.host(host);
+      }
+      if let Some(port) = c.port() {
+        o = o.port(port);
+      }
+      if let Some(socket) = c.socket() {
+        o = o.socket(socket);
+      }
+      if let Some(database) = c.database() {
+        o = o.database(database)
+      }
+      if let Some(username) = c.username() {
+        o = o.username(username)
+      }
+      if let Some(password) = c.password() {
+        o = o.password(password)
+      }
+      o
+    }
+  };
+  Ok(opts)
+}
+
+pub(crate) fn resolve_connect_options(opts: MySqlConnectOptions) -> Result<MySqlConnectOptions> {
+  let level = log::LevelFilter::Debug;
+  Ok(opts.log_statements(level))
+}
+
+pub(crate) fn with_database(opts: MySqlConnectOptions, database: &str) -> MySqlConnectOptions {
+  opts.database(database)
+}
+
+/// 本次插入的第一行的自增 ID，未生成自增 ID 时返回 None
+pub(crate) fn last_insert_id(result: &MySqlQueryResult) -> Option<i64> {
+  Some(result.last_insert_id() as i64).filter(|id| *id > 0)
+}
+
+/// MySQL 的 `SET TRANSACTION` 作用于下一个事务，需要在开启事务前执行
+pub(crate) async fn begin_txn(db: &Db, options: &TxnOptions) -> sqlx::Result<Transaction<'static, DbType>> {
+  let mut conn = db.acquire().await?;
+  if let Some(sql) = options.set_transaction_sql() {
+    sqlx::query(&sql).execute(&mut *conn).await?;
+  }
+  // 同 `Pool::begin`，事务持有连接的所有权
+  Transaction::begin(conn).await
+}
+
+/// 返回值类型名的表达式。MySQL 比较时会将文本隐式转换为列的类型，统一使用 `CHAR`
+pub(crate) fn type_of_expr(_expr: SimpleExpr) -> SimpleExpr {
+  Expr::cust("'CHAR'")
+}
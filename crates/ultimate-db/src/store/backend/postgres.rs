@@ -0,0 +1,104 @@
+use std::io;
+use std::net::ToSocketAddrs;
+
+use sea_query::{Expr, SimpleExpr};
+use sqlx::postgres::{PgConnectOptions, PgQueryResult};
+use sqlx::{ConnectOptions, Transaction};
+use tracing::trace;
+use ultimate::configuration::model::DbConf;
+
+use super::DbKind;
+use crate::store::dbx::{Db, Result};
+use crate::store::TxnOptions;
+
+pub use sea_query::PostgresQueryBuilder as DbQueryBuilder;
+
+/// 数据库类型
+pub type DbType = sqlx::Postgres;
+
+/// 连接选项
+pub type DbConnectOptions = PgConnectOptions;
+
+pub const DB_KIND: DbKind = DbKind::Postgres;
+
+/// 将列的值转换为文本的类型
+pub(crate) const TEXT_TYPE: &str = "TEXT";
+
+/// 将值转换为整数、浮点数的类型
+pub(crate) const INTEGER_TYPE: &str = "BIGINT";
+pub(crate) const FLOAT_TYPE: &str = "DOUBLE PRECISION";
+
+/// 序列化失败的错误码（SQLSTATE）
+pub(crate) const SERIALIZATION_FAILURE_CODES: &[&str] = &["40001"];
+
+/// 设置了 `schema_search_path` 时连接的 `search_path` 为该值
+pub(crate) fn connect_options_from_config(c: &DbConf) -> Result<PgConnectOptions> {
+  let mut opts: PgConnectOptions = match c.url() {
+    Some(url) => url.parse()?,
+    None => {
+      let mut o = PgConnectOptions::new();
+      if let Some(host) = c.host() {
+        o = o.host(host);
+      }
+      if let Some(port) = c.port() {
+        o = o.port(port);
+      }
+      if let Some(socket) = c.socket() {
+        o = o.socket(socket);
+      }
+      if let Some(database) = c.database() {
+        o = o.database(database)
+      }
+      if let Some(username) = c.username() {
+        o = o.username(username)
+      }
+      if let Some(password) = c.password() {
+        o = o.password(password)
+      }
+      o
+    }
+  };
+  if let Some(search_path) = c.schema_search_path() {
+    opts = opts.options([("search_path", search_path)]);
+  }
+  Ok(opts)
+}
+
+/// 解析主机名为 IP 地址，无法解析时返回错误
+pub(crate) fn resolve_connect_options(mut opts: PgConnectOptions) -> Result<PgConnectOptions> {
+  // TODO 若 opts.host 是域名，需要进行DNS查找将期转换为 ip addr
+  let non_ip_addr = opts.get_host().parse::<std::net::IpAddr>().is_err();
+  if non_ip_addr {
+    let original_host = format!("{}:{}", opts.get_host(), opts.get_port());
+    let sock_addr = original_host.to_socket_addrs().map_err(sqlx::Error::Io)?.next().ok_or_else(|| {
+      sqlx::Error::Io(io::Error::new(io::ErrorKind::NotFound, format!("No address resolved for {}", original_host)))
+    })?;
+    opts = opts.host(&sock_addr.ip().to_string());
+    trace!("Resolve original host, from {} to {}", original_host, opts.get_host());
+  }
+
+  let level = log::LevelFilter::Debug;
+  Ok(opts.log_statements(level))
+}
+
+pub(crate) fn with_database(opts: PgConnectOptions, database: &str) -> PgConnectOptions {
+  opts.database(database)
+}
+
+pub(crate) fn last_insert_id(_result: &PgQueryResult) -> Option<i64> {
+  None
+}
+
+/// 开启事务后设置事务特性
+pub(crate) async fn begin_txn(db: &Db, options: &TxnOptions) -> sqlx::Result<Transaction<'static, DbType>> {
+  let mut transaction = db.begin().await?;
+  if let Some(sql) = options.set_transaction_sql() {
+    sqlx::query(&sql).execute(transaction.as_mut()).await?;
+  }
+  Ok(transaction)
+}
+
+/// 返回值类型名的表达式
+pub(crate) fn type_of_expr(expr: SimpleExpr) -> SimpleExpr {
+  Expr::cust_with_expr("pg_typeof($1)::text", expr)
+}
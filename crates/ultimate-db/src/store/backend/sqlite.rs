@@ -0,0 +1,99 @@
+use sea_query::{Alias, Func, SimpleExpr};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteQueryResult};
+use sqlx::{ConnectOptions, Transaction};
+use tracing::trace;
+use ultimate::configuration::model::DbConf;
+
+use super::DbKind;
+use crate::store::dbx::{Db, Error, Result};
+use crate::store::TxnOptions;
+
+pub use sea_query::SqliteQueryBuilder as DbQueryBuilder;
+
+/// 数据库类型
+pub type DbType = sqlx::Sqlite;
+
+/// 连接选项
+pub type DbConnectOptions = SqliteConnectOptions;
+
+pub const DB_KIND: DbKind = DbKind::Sqlite;
+
+/// 将列的值转换为文本的类型
+pub(crate) const TEXT_TYPE: &str = "TEXT";
+
+/// 将值转换为整数、浮点数的类型
+pub(crate) const INTEGER_TYPE: &str = "INTEGER";
+pub(crate) const FLOAT_TYPE: &str = "REAL";
+
+/// 数据库被锁定的错误码（SQLITE_BUSY、SQLITE_BUSY_SNAPSHOT），重试整个事务通常可以成功
+pub(crate) const SERIALIZATION_FAILURE_CODES: &[&str] = &["5", "517"];
+
+/// `url` 形如 `sqlite://data.db`、`sqlite::memory:`；未设置 `url` 时使用 `database` 作为数据库文件，不存在时创建。
+/// 设置了 `sqlcipher_key` 时使用该密钥打开加密的数据库（需要链接 SQLCipher）
+pub(crate) fn connect_options_from_config(c: &DbConf) -> Result<SqliteConnectOptions> {
+  let mut opts: SqliteConnectOptions = match (c.url(), c.database()) {
+    (Some(url), _) => url.parse()?,
+    (None, Some(database)) => SqliteConnectOptions::new().filename(database).create_if_missing(true),
+    (None, None) => return Err(Error::ConfigInvalid("Need set ultimate.db.url or ultimate.db.database")),
+  };
+  if let Some(key) = c.sqlcipher_key() {
+    opts = opts.pragma("key", format!("'{}'", key.replace('\'', "''")));
+  }
+  Ok(opts)
+}
+
+pub(crate) fn resolve_connect_options(opts: SqliteConnectOptions) -> Result<SqliteConnectOptions> {
+  let level = log::LevelFilter::Debug;
+  Ok(opts.log_statements(level))
+}
+
+/// SQLite 的数据库即为数据库文件
+pub(crate) fn with_database(opts: SqliteConnectOptions, database: &str) -> SqliteConnectOptions {
+  opts.filename(database).create_if_missing(true)
+}
+
+pub(crate) fn last_insert_id(result: &SqliteQueryResult) -> Option<i64> {
+  Some(result.last_insert_rowid()).filter(|id| *id > 0)
+}
+
+/// SQLite 的事务总是可串行化的，忽略隔离级别及只读选项
+pub(crate) async fn begin_txn(db: &Db, options: &TxnOptions) -> sqlx::Result<Transaction<'static, DbType>> {
+  if let Some(sql) = options.set_transaction_sql() {
+    trace!("SQLite ignores transaction options: {}", sql);
+  }
+  db.begin().await
+}
+
+/// 返回值类型名的表达式
+pub(crate) fn type_of_expr(expr: SimpleExpr) -> SimpleExpr {
+  Func::cust(Alias::new("typeof")).arg(expr).into()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_connect_options_from_config() -> anyhow::Result<()> {
+    let conf: DbConf = serde_json::from_value(serde_json::json!({ "enable": true, "database": "data.db" }))?;
+    let opts = connect_options_from_config(&conf)?;
+    assert_eq!(opts.get_filename().as_ref(), std::path::Path::new("data.db"));
+
+    let conf: DbConf = serde_json::from_value(serde_json::json!({ "enable": true }))?;
+    assert!(matches!(connect_options_from_config(&conf), Err(Error::ConfigInvalid(_))));
+    Ok(())
+  }
+
+  #[test]
+  fn test_sqlcipher_key_pragma() -> anyhow::Result<()> {
+    let conf: DbConf = serde_json::from_value(serde_json::json!({
+      "enable": true,
+      "url": "sqlite::memory:",
+      "sqlcipher_key": "it's",
+    }))?;
+    let opts = connect_options_from_config(&conf)?;
+    // 密钥中的单引号需要转义
+    assert!(format!("{:?}", opts).contains(r#""key": Some("'it''s'")"#), "{:?}", opts);
+    Ok(())
+  }
+}
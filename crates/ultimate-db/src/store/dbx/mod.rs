@@ -1,31 +1,55 @@
 // region:    --- Modules
 
-use std::net::ToSocketAddrs;
 use std::ops::{Deref, DerefMut};
+#[cfg(feature = "postgres")]
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
-use sqlx::postgres::any::AnyConnectionBackend;
-use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use async_stream::try_stream;
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
+use sqlx::pool::PoolConnection;
 use sqlx::query::{Query, QueryAs};
-use sqlx::{ConnectOptions, FromRow, IntoArguments, Pool, Postgres, Transaction};
+#[cfg(feature = "postgres")]
+use sqlx::Connection;
+use sqlx::{Execute, FromRow, IntoArguments, Pool, Transaction};
 use tokio::sync::Mutex;
 use tracing::trace;
 use ultimate::configuration::model::DbConf;
 
+#[cfg(feature = "postgres")]
+use super::backend::DbConnection;
+use super::backend::{self, DbConnectOptions, DbPoolOptions, DbQueryResult, DbRow, DbType};
+
 mod error;
+mod replica;
+mod txn;
 
 pub use error::{Error, Result};
+pub use replica::ReplicaSet;
+pub use txn::{IsolationLevel, TxnOptions};
 
 // endregion: --- Modules
 
-pub type Db = Pool<Postgres>;
+pub type Db = Pool<DbType>;
+
+/// 服务端游标名称的序号
+#[cfg(feature = "postgres")]
+static CURSOR_SEQ: AtomicU64 = AtomicU64::new(0);
 
 pub async fn new_db_pool_from_config(c: &DbConf) -> Result<Db> {
+  let (opt, opts) = db_options_from_config(c)?;
+  let db = opt.connect_with(opts).await?;
+  Ok(db)
+}
+
+/// 从配置生成连接池选项和连接选项
+pub fn db_options_from_config(c: &DbConf) -> Result<(DbPoolOptions, DbConnectOptions)> {
   if !c.enable() {
     return Err(Error::ConfigInvalid("Need set ultimate.db.enable = true"));
   }
 
-  let mut opt = PgPoolOptions::new();
+  let mut opt = DbPoolOptions::new();
   if let Some(v) = c.max_connections() {
     opt = opt.max_connections(v);
   }
@@ -44,58 +68,46 @@ pub async fn new_db_pool_from_config(c: &DbConf) -> Result<Db> {
 
   trace!("Db connection options are: {:?}", opt);
 
-  let level = log::LevelFilter::Debug;
-  let mut opts: PgConnectOptions = match c.url() {
-    Some(url) => url.parse()?,
-    None => {
-      let mut o = PgConnectOptions::new();
-      if let Some(host) = c.host() {
-        o = o.host(host);
-      }
-      if let Some(port) = c.port() {
-        o = o.port(port);
-      }
-      if let Some(socket) = c.socket() {
-        o = o.socket(socket);
-      }
-      if let Some(database) = c.database() {
-        o = o.database(database)
-      }
-      if let Some(username) = c.username() {
-        o = o.username(username)
-      }
-      if let Some(password) = c.password() {
-        o = o.password(password)
-      }
-      o
-    }
-  };
-
-  // TODO 若 opts.host 是域名，需要进行DNS查找将期转换为 ip addr
-  let non_ip_addr = opts.get_host().parse::<std::net::IpAddr>().is_err();
-  if non_ip_addr {
-    let original_host = format!("{}:{}", opts.get_host(), opts.get_port());
-    let sock_addr = original_host.to_socket_addrs().unwrap().next().unwrap();
-    opts = opts.host(&sock_addr.ip().to_string());
-    trace!("Resolve original host, from {} to {}", original_host, opts.get_host());
-  }
-
-  opts = opts.log_statements(level);
+  let opts = backend::connect_options_from_config(c)?;
+  Ok((opt, backend::resolve_connect_options(opts)?))
+}
 
-  let db = opt.connect_with(opts).await?;
-  Ok(db)
+/// 从配置生成只读副本连接池，未配置副本时返回 None
+pub fn new_replica_set_from_config(c: &DbConf) -> Result<Option<Arc<ReplicaSet>>> {
+  let (opt, _) = db_options_from_config(c)?;
+  ReplicaSet::from_config(c, &opt)
 }
 
+/// 数据库执行器。
+///
+/// 配置了只读副本时，事务外的只读查询（不加锁的 SELECT）发送到副本，写操作和事务内的查询发送到主库。
+/// 副本存在复制延迟，需要读取刚写入的数据时使用 [Dbx::with_read_primary]。
 #[derive(Debug, Clone)]
 pub struct Dbx {
   db_pool: Db,
   txn_holder: Arc<Mutex<Option<TxnHolder>>>,
   txn: bool,
+  replicas: Option<Arc<ReplicaSet>>,
+  read_primary: bool,
 }
 
 impl Dbx {
   pub fn new(db_pool: Db, txn: bool) -> Result<Self> {
-    Ok(Dbx { db_pool, txn_holder: Arc::default(), txn })
+    Ok(Dbx { db_pool, txn_holder: Arc::default(), txn, replicas: None, read_primary: false })
+  }
+
+  pub fn with_replicas(mut self, replicas: Option<Arc<ReplicaSet>>) -> Self {
+    self.replicas = replicas;
+    self
+  }
+
+  /// 返回所有查询都发送到主库的 Dbx（共享同一事务）
+  pub fn with_read_primary(&self) -> Self {
+    Self { read_primary: true, ..self.clone() }
+  }
+
+  pub fn replicas(&self) -> Option<&ReplicaSet> {
+    self.replicas.as_deref()
   }
 
   pub fn is_txn(&self) -> bool {
@@ -105,6 +117,11 @@ impl Dbx {
   pub fn non_txn(&self) -> bool {
     !self.txn
   }
+
+  /// 是否已开启事务
+  pub async fn has_open_txn(&self) -> bool {
+    self.txn_holder.lock().await.is_some()
+  }
 }
 
 impl Dbx {
@@ -157,8 +174,8 @@ impl Dbx {
       // so we can commit.
       if counter == 0 {
         // here we take the txh out of the option
-        if let Some(mut txn) = txh_g.take() {
-          txn.txn.as_mut().commit().await?;
+        if let Some(txh) = txh_g.take() {
+          txh.txn.commit().await?;
         } // TODO: Might want to add a warning on the else.
       } // TODO: Might want to add a warning on the else.
 
@@ -170,14 +187,86 @@ impl Dbx {
     }
   }
 
-  pub fn db(&self) -> &Pool<Postgres> {
+  /// 开启事务，已存在事务时创建保存点（`SAVEPOINT`）。返回是否开启了最外层事务。
+  ///
+  /// `options` 只在开启最外层事务时生效。需要与 [Dbx::commit_nested]、[Dbx::rollback_nested] 成对调用。
+  pub async fn begin_nested(&self, options: &TxnOptions) -> Result<bool> {
+    if !self.txn {
+      return Err(Error::CannotBeginTxnWithTxnFalse);
+    }
+
+    let mut txh_g = self.txn_holder.lock().await;
+    if let Some(txh) = txh_g.as_mut() {
+      txh.savepoints += 1;
+      let sql = format!("SAVEPOINT {}", savepoint_name(txh.savepoints));
+      sqlx::query(&sql).execute(txh.txn.as_mut()).await?;
+      Ok(false)
+    } else {
+      let transaction = backend::begin_txn(&self.db_pool, options).await?;
+      let _ = txh_g.insert(TxnHolder::new(transaction));
+      Ok(true)
+    }
+  }
+
+  /// 释放最近的保存点，不存在保存点时提交事务
+  pub async fn commit_nested(&self) -> Result<()> {
+    if !self.txn {
+      return Err(Error::CannotCommitTxnWithTxnFalse);
+    }
+
+    let mut txh_g = self.txn_holder.lock().await;
+    match txh_g.as_mut() {
+      Some(txh) if txh.savepoints > 0 => {
+        let sql = format!("RELEASE SAVEPOINT {}", savepoint_name(txh.savepoints));
+        txh.savepoints -= 1;
+        sqlx::query(&sql).execute(txh.txn.as_mut()).await?;
+      }
+      Some(_) => {
+        if let Some(txh) = txh_g.take() {
+          txh.txn.commit().await?;
+        }
+      }
+      None => return Err(Error::TxnCantCommitNoOpenTxn),
+    }
+    Ok(())
+  }
+
+  /// 回滚到最近的保存点，不存在保存点时回滚事务
+  pub async fn rollback_nested(&self) -> Result<()> {
+    let mut txh_g = self.txn_holder.lock().await;
+    match txh_g.as_mut() {
+      Some(txh) if txh.savepoints > 0 => {
+        let name = savepoint_name(txh.savepoints);
+        txh.savepoints -= 1;
+        sqlx::query(&format!("ROLLBACK TO SAVEPOINT {name}")).execute(txh.txn.as_mut()).await?;
+        sqlx::query(&format!("RELEASE SAVEPOINT {name}")).execute(txh.txn.as_mut()).await?;
+      }
+      Some(_) => {
+        if let Some(txh) = txh_g.take() {
+          txh.txn.rollback().await?;
+        }
+      }
+      None => return Err(Error::NoTxn),
+    }
+    Ok(())
+  }
+
+  pub fn db(&self) -> &Pool<DbType> {
     &self.db_pool
   }
 
-  pub async fn fetch_one<'q, O, A>(&self, query: QueryAs<'q, Postgres, O, A>) -> Result<O>
+  /// 事务外的只读查询从副本获取连接，无可用副本或不应使用副本时返回 None
+  async fn replica_conn(&self, sql: &str) -> Option<PoolConnection<DbType>> {
+    if self.txn || self.read_primary || !replica::is_read_only_sql(sql) {
+      return None;
+    }
+    self.replicas.as_ref()?.acquire().await
+  }
+
+  pub async fn fetch_one<'q, O, A>(&self, query: QueryAs<'q, DbType, O, A>) -> Result<O>
   where
-    O: for<'r> FromRow<'r, <Postgres as sqlx::Database>::Row> + Send + Unpin,
-    A: IntoArguments<'q, Postgres> + 'q,
+    O: for<'r> FromRow<'r, DbRow> + Send + Unpin,
+    A: IntoArguments<'q, DbType> + 'q,
   {
     let data = if self.txn {
       let mut txh_g = self.txn_holder.lock().await;
@@ -186,6 +275,8 @@ impl Dbx {
       } else {
         query.fetch_one(self.db()).await?
       }
+    } else if let Some(mut conn) = self.replica_conn(query.sql()).await {
+      query.fetch_one(&mut *conn).await?
     } else {
       query.fetch_one(self.db()).await?
     };
@@ -193,10 +284,10 @@ impl Dbx {
     Ok(data)
   }
 
-  pub async fn fetch_optional<'q, O, A>(&self, query: QueryAs<'q, Postgres, O, A>) -> Result<Option<O>>
+  pub async fn fetch_optional<'q, O, A>(&self, query: QueryAs<'q, DbType, O, A>) -> Result<Option<O>>
   where
-    O: for<'r> FromRow<'r, <Postgres as sqlx::Database>::Row> + Send + Unpin,
-    A: IntoArguments<'q, Postgres> + 'q,
+    O: for<'r> FromRow<'r, DbRow> + Send + Unpin,
+    A: IntoArguments<'q, DbType> + 'q,
   {
     let data = if self.txn {
       let mut txh_g = self.txn_holder.lock().await;
@@ -205,6 +296,8 @@ impl Dbx {
       } else {
         query.fetch_optional(self.db()).await?
       }
+    } else if let Some(mut conn) = self.replica_conn(query.sql()).await {
+      query.fetch_optional(&mut *conn).await?
     } else {
       query.fetch_optional(self.db()).await?
     };
@@ -212,10 +305,10 @@ impl Dbx {
     Ok(data)
   }
 
-  pub async fn fetch_all<'q, O, A>(&self, query: QueryAs<'q, Postgres, O, A>) -> Result<Vec<O>>
+  pub async fn fetch_all<'q, O, A>(&self, query: QueryAs<'q, DbType, O, A>) -> Result<Vec<O>>
   where
-    O: for<'r> FromRow<'r, <Postgres as sqlx::Database>::Row> + Send + Unpin,
-    A: IntoArguments<'q, Postgres> + 'q,
+    O: for<'r> FromRow<'r, DbRow> + Send + Unpin,
+    A: IntoArguments<'q, DbType> + 'q,
   {
     let data = if self.txn {
       let mut txh_g = self.txn_holder.lock().await;
@@ -224,6 +317,8 @@ impl Dbx {
       } else {
         query.fetch_all(self.db()).await?
       }
+    } else if let Some(mut conn) = self.replica_conn(query.sql()).await {
+      query.fetch_all(&mut *conn).await?
     } else {
       query.fetch_all(self.db()).await?
     };
@@ -231,34 +326,176 @@ impl Dbx {
     Ok(data)
   }
 
-  pub async fn execute<'q, A>(&self, query: Query<'q, Postgres, A>) -> Result<u64>
+  /// 以流的方式逐行读取查询结果，内存占用与结果集大小无关。
+  ///
+  /// 存在事务时在事务内查询，并在流结束（或被 drop）前一直持有事务。
+  /// 注意：迭代期间不能在同一事务内执行其它查询，否则会死锁。
+  pub fn fetch_stream<'q, O, A>(&self, query: QueryAs<'q, DbType, O, A>) -> BoxStream<'q, Result<O>>
+  where
+    O: for<'r> FromRow<'r, DbRow> + Send + Unpin + 'q,
+    A: IntoArguments<'q, DbType> + 'q,
+  {
+    let dbx = self.clone();
+    Box::pin(try_stream! {
+      let mut txh_g = match dbx.txn {
+        true => Some(dbx.txn_holder.clone().lock_owned().await),
+        false => None,
+      };
+      match txh_g.as_mut().and_then(|g| g.as_deref_mut()) {
+        Some(txn) => {
+          let mut rows = query.fetch(txn.as_mut());
+          while let Some(row) = rows.try_next().await? {
+            yield row;
+          }
+        }
+        None => {
+          drop(txh_g);
+          let mut conn = match dbx.replica_conn(query.sql()).await {
+            Some(conn) => conn,
+            None => dbx.db_pool.acquire().await?,
+          };
+          let mut rows = query.fetch(&mut *conn);
+          while let Some(row) = rows.try_next().await? {
+            yield row;
+          }
+        }
+      }
+    })
+  }
+
+  /// 使用服务端游标（`DECLARE ... CURSOR`）分批读取查询结果，每批读取 `batch_size` 行。
+  ///
+  /// 游标需要在事务内使用：存在事务时使用该事务，否则开启一个只用于本次查询的事务。
+  /// 同 [Dbx::fetch_stream]，迭代期间不能在同一事务内执行其它查询。
+  #[cfg(feature = "postgres")]
+  pub fn fetch_cursor<O, A>(&self, sql: String, args: A, batch_size: u32) -> BoxStream<'static, Result<O>>
+  where
+    O: for<'r> FromRow<'r, DbRow> + Send + Unpin + 'static,
+    A: for<'q> IntoArguments<'q, DbType> + Send + 'static,
+  {
+    let dbx = self.clone();
+    let batch_size = batch_size.max(1);
+    Box::pin(try_stream! {
+      let name = format!("ultimate_cursor_{}", CURSOR_SEQ.fetch_add(1, Ordering::Relaxed));
+      let declare_sql = format!("DECLARE {name} NO SCROLL CURSOR FOR {sql}");
+      let fetch_sql = format!("FETCH FORWARD {batch_size} FROM {name}");
+      let close_sql = format!("CLOSE {name}");
+
+      let mut txh_g = match dbx.txn {
+        true => Some(dbx.txn_holder.clone().lock_owned().await),
+        false => None,
+      };
+      let mut own_conn = None;
+      let mut own_txn = None;
+      let conn: &mut DbConnection = match txh_g.as_mut().and_then(|g| g.as_deref_mut()) {
+        Some(txn) => txn.as_mut(),
+        None => {
+          let own_conn = match dbx.replica_conn(&sql).await {
+            Some(conn) => own_conn.insert(conn),
+            None => own_conn.insert(dbx.db_pool.acquire().await?),
+          };
+          own_txn.insert(Connection::begin(&mut **own_conn).await?).as_mut()
+        }
+      };
+
+      sqlx::query_with(&declare_sql, args).execute(&mut *conn).await?;
+      loop {
+        let rows: Vec<O> = sqlx::query_as(&fetch_sql).fetch_all(&mut *conn).await?;
+        let n = rows.len();
+        for row in rows {
+          yield row;
+        }
+        if n < batch_size as usize {
+          break;
+        }
+      }
+      sqlx::query(&close_sql).execute(&mut *conn).await?;
+
+      if let Some(txn) = own_txn {
+        txn.commit().await?;
+      }
+    })
+  }
+
+  /// 分批读取查询结果。MySQL、SQLite 不支持服务端游标，同 [Dbx::fetch_stream]，`batch_size` 被忽略
+  #[cfg(not(feature = "postgres"))]
+  pub fn fetch_cursor<O, A>(&self, sql: String, args: A, _batch_size: u32) -> BoxStream<'static, Result<O>>
   where
-    A: IntoArguments<'q, Postgres> + 'q,
+    O: for<'r> FromRow<'r, DbRow> + Send + Unpin + 'static,
+    A: for<'q> IntoArguments<'q, DbType> + Send + 'static,
   {
-    let row_affected = if self.txn {
+    let dbx = self.clone();
+    Box::pin(try_stream! {
+      let mut rows = dbx.fetch_stream(sqlx::query_as_with::<_, O, _>(&sql, args));
+      while let Some(row) = rows.try_next().await? {
+        yield row;
+      }
+    })
+  }
+
+  pub async fn execute<'q, A>(&self, query: Query<'q, DbType, A>) -> Result<u64>
+  where
+    A: IntoArguments<'q, DbType> + 'q,
+  {
+    Ok(self.execute_query(query).await?.rows_affected())
+  }
+
+  /// 执行插入语句，返回插入的记录数及插入的第一行的自增 ID。只有 MySQL、SQLite 返回自增 ID
+  pub async fn execute_insert<'q, A>(&self, query: Query<'q, DbType, A>) -> Result<(u64, Option<i64>)>
+  where
+    A: IntoArguments<'q, DbType> + 'q,
+  {
+    let result = self.execute_query(query).await?;
+    Ok((result.rows_affected(), backend::last_insert_id(&result)))
+  }
+
+  /// 执行不带参数的 SQL，可以包含多条以分号分隔的语句，如建表脚本。返回受影响的记录数
+  pub async fn execute_raw(&self, sql: &str) -> Result<u64> {
+    let query = sqlx::raw_sql(sql);
+    let result = if self.txn {
       let mut txh_g = self.txn_holder.lock().await;
       if let Some(txn) = txh_g.as_deref_mut() {
-        query.execute(txn.as_mut()).await?.rows_affected()
+        query.execute(txn.as_mut()).await?
       } else {
-        query.execute(self.db()).await?.rows_affected()
+        query.execute(self.db()).await?
       }
     } else {
-      query.execute(self.db()).await?.rows_affected()
+      query.execute(self.db()).await?
     };
 
-    Ok(row_affected)
+    Ok(result.rows_affected())
+  }
+
+  async fn execute_query<'q, A>(&self, query: Query<'q, DbType, A>) -> Result<DbQueryResult>
+  where
+    A: IntoArguments<'q, DbType> + 'q,
+  {
+    let result = if self.txn {
+      let mut txh_g = self.txn_holder.lock().await;
+      if let Some(txn) = txh_g.as_deref_mut() {
+        query.execute(txn.as_mut()).await?
+      } else {
+        query.execute(self.db()).await?
+      }
+    } else {
+      query.execute(self.db()).await?
+    };
+
+    Ok(result)
   }
 }
 
 #[derive(Debug)]
 struct TxnHolder {
-  txn: Transaction<'static, Postgres>,
+  txn: Transaction<'static, DbType>,
   counter: i32,
+  /// 当前的保存点数量
+  savepoints: u32,
 }
 
 impl TxnHolder {
-  fn new(txn: Transaction<'static, Postgres>) -> Self {
-    TxnHolder { txn, counter: 1 }
+  fn new(txn: Transaction<'static, DbType>) -> Self {
+    TxnHolder { txn, counter: 1, savepoints: 0 }
   }
 
   fn inc(&mut self) {
@@ -272,7 +509,7 @@ impl TxnHolder {
 }
 
 impl Deref for TxnHolder {
-  type Target = Transaction<'static, Postgres>;
+  type Target = Transaction<'static, DbType>;
 
   fn deref(&self) -> &Self::Target {
     &self.txn
@@ -284,3 +521,7 @@ impl DerefMut for TxnHolder {
     &mut self.txn
   }
 }
+
+fn savepoint_name(n: u32) -> String {
+  format!("ultimate_sp_{n}")
+}
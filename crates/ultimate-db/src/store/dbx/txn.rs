@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+/// 事务隔离级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolationLevel {
+  ReadCommitted,
+  RepeatableRead,
+  Serializable,
+}
+
+impl IsolationLevel {
+  fn as_sql(&self) -> &'static str {
+    match self {
+      IsolationLevel::ReadCommitted => "READ COMMITTED",
+      IsolationLevel::RepeatableRead => "REPEATABLE READ",
+      IsolationLevel::Serializable => "SERIALIZABLE",
+    }
+  }
+}
+
+/// `ModelManager::transaction_with` 的事务选项。隔离级别和只读只对最外层事务有效
+#[derive(Debug, Clone)]
+pub struct TxnOptions {
+  isolation_level: Option<IsolationLevel>,
+  read_only: bool,
+  max_retries: u32,
+  retry_backoff: Duration,
+}
+
+impl Default for TxnOptions {
+  fn default() -> Self {
+    Self { isolation_level: None, read_only: false, max_retries: 0, retry_backoff: Duration::from_millis(50) }
+  }
+}
+
+impl TxnOptions {
+  pub fn with_isolation_level(mut self, isolation_level: IsolationLevel) -> Self {
+    self.isolation_level = Some(isolation_level);
+    self
+  }
+
+  pub fn with_read_only(mut self, read_only: bool) -> Self {
+    self.read_only = read_only;
+    self
+  }
+
+  /// 遇到序列化失败（SQLSTATE 40001）时最多重试 `max_retries` 次，第 n 次重试前等待 `backoff * 2^(n-1)`
+  pub fn with_retries(mut self, max_retries: u32, backoff: Duration) -> Self {
+    self.max_retries = max_retries;
+    self.retry_backoff = backoff;
+    self
+  }
+
+  pub fn isolation_level(&self) -> Option<IsolationLevel> {
+    self.isolation_level
+  }
+
+  pub fn read_only(&self) -> bool {
+    self.read_only
+  }
+
+  pub fn max_retries(&self) -> u32 {
+    self.max_retries
+  }
+
+  /// 第 `attempt` 次（从 1 开始）重试前的等待时间
+  pub fn retry_delay(&self, attempt: u32) -> Duration {
+    self.retry_backoff.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+  }
+
+  /// 开启事务后设置事务特性的 SQL，无需设置时返回 None
+  pub(crate) fn set_transaction_sql(&self) -> Option<String> {
+    let mut modes = Vec::new();
+    if let Some(level) = self.isolation_level {
+      modes.push(format!("ISOLATION LEVEL {}", level.as_sql()));
+    }
+    if self.read_only {
+      modes.push("READ ONLY".to_string());
+    }
+    (!modes.is_empty()).then(|| format!("SET TRANSACTION {}", modes.join(", ")))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_txn_options() {
+    assert_eq!(TxnOptions::default().set_transaction_sql(), None);
+
+    let options = TxnOptions::default()
+      .with_isolation_level(IsolationLevel::Serializable)
+      .with_read_only(true)
+      .with_retries(3, Duration::from_millis(10));
+    assert_eq!(
+      options.set_transaction_sql().as_deref(),
+      Some("SET TRANSACTION ISOLATION LEVEL SERIALIZABLE, READ ONLY")
+    );
+    assert_eq!(options.retry_delay(1), Duration::from_millis(10));
+    assert_eq!(options.retry_delay(3), Duration::from_millis(40));
+  }
+}
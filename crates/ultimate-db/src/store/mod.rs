@@ -1,3 +1,5 @@
+pub mod backend;
 pub(crate) mod dbx;
 
-pub use dbx::{Db, Dbx};
+pub use backend::{DbKind, DbQueryBuilder, DbRow, DbType, DB_KIND};
+pub use dbx::{Db, Dbx, IsolationLevel, ReplicaSet, TxnOptions};
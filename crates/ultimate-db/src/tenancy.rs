@@ -0,0 +1,149 @@
+//! 多租户支持。
+//!
+//! 通过 `ultimate.db.tenancy` 配置隔离模式，租户 ID 取自 `Ctx::tenant_id`：
+//!
+//! - `shared_table`：所有租户共享表，`crud_fns` 自动按 `tenant_id` 列过滤，并在插入时填充
+//! - `schema_per_tenant`：每个租户使用独立的 schema，表引用在查询时根据租户解析。SQLite 不支持
+//! - `database_per_tenant`：每个租户使用独立的数据库，连接池在首次使用时创建
+//!
+//! 只有 [DbBmc::multi_tenant] 为 true 的表才会隔离。根用户未设置租户时访问默认的表和数据库，
+//! 其它用户未设置租户时返回 [Error::TenantRequired]。
+use std::{
+  collections::HashMap,
+  sync::{Arc, RwLock},
+};
+
+use modql::SIden;
+use sea_query::{Alias, IntoIden, TableRef};
+use ultimate::{
+  configuration::model::{DbConf, TenancyMode},
+  ctx::Ctx,
+};
+
+use crate::{
+  base::DbBmc,
+  store::backend::{self, DbConnectOptions, DbPoolOptions, DB_KIND},
+  store::dbx::{db_options_from_config, Db},
+  Error, Result,
+};
+
+const TENANT_ID_PLACEHOLDER: &str = "{tenant_id}";
+
+#[derive(Clone, Default)]
+pub struct Tenancy {
+  mode: TenancyMode,
+  schema_template: String,
+  database_template: String,
+  pools: Option<Arc<TenantPools>>,
+}
+
+struct TenantPools {
+  pool_options: DbPoolOptions,
+  connect_options: DbConnectOptions,
+  pools: RwLock<HashMap<i64, Db>>,
+}
+
+impl Tenancy {
+  pub fn from_config(db_conf: &DbConf) -> Result<Self> {
+    let Some(c) = db_conf.tenancy() else {
+      return Ok(Self::default());
+    };
+    if c.mode() == TenancyMode::SchemaPerTenant && !DB_KIND.supports_schema() {
+      return Err(Error::UnsupportedByBackend { feature: "schema_per_tenant", backend: DB_KIND });
+    }
+
+    let pools = if c.mode() == TenancyMode::DatabasePerTenant {
+      let (pool_options, connect_options) = db_options_from_config(db_conf)?;
+      Some(Arc::new(TenantPools { pool_options, connect_options, pools: RwLock::default() }))
+    } else {
+      None
+    };
+
+    Ok(Self {
+      mode: c.mode(),
+      schema_template: c.schema_template().to_string(),
+      database_template: c.database_template().to_string(),
+      pools,
+    })
+  }
+
+  pub fn mode(&self) -> TenancyMode {
+    self.mode
+  }
+
+  /// 表所属的租户，不需要隔离时返回 None
+  pub fn tenant_of<MC: DbBmc>(&self, ctx: Option<&Ctx>) -> Result<Option<i64>> {
+    if self.mode == TenancyMode::None || !MC::multi_tenant() {
+      return Ok(None);
+    }
+    let ctx = ctx.ok_or(Error::Unauthorized)?;
+    match ctx.tenant_id() {
+      Some(tenant_id) => Ok(Some(tenant_id)),
+      None if ctx.is_root() => Ok(None),
+      None => Err(Error::TenantRequired),
+    }
+  }
+
+  /// 租户感知的表引用。`schema_per_tenant` 模式下 schema 由租户 ID 生成
+  pub fn table_ref<MC: DbBmc>(&self, ctx: Option<&Ctx>) -> Result<TableRef> {
+    match self.tenant_of::<MC>(ctx)? {
+      Some(tenant_id) if self.mode == TenancyMode::SchemaPerTenant => {
+        let schema = self.schema_template.replace(TENANT_ID_PLACEHOLDER, &tenant_id.to_string());
+        Ok(TableRef::SchemaTable(Alias::new(schema).into_iden(), SIden(MC::TABLE).into_iden()))
+      }
+      _ => Ok(MC::table_ref()),
+    }
+  }
+
+  /// `database_per_tenant` 模式下获取租户的连接池，连接在首次使用时建立
+  pub fn tenant_pool(&self, tenant_id: i64) -> Option<Db> {
+    let pools = self.pools.as_ref()?;
+    if let Some(db) = pools.pools.read().unwrap().get(&tenant_id) {
+      return Some(db.clone());
+    }
+
+    let database = self.database_template.replace(TENANT_ID_PLACEHOLDER, &tenant_id.to_string());
+    let connect_options = backend::with_database(pools.connect_options.clone(), &database);
+    let db = pools
+      .pools
+      .write()
+      .unwrap()
+      .entry(tenant_id)
+      .or_insert_with(|| pools.pool_options.clone().connect_lazy_with(connect_options))
+      .clone();
+    Some(db)
+  }
+}
+
+#[cfg(all(test, feature = "postgres"))]
+mod tests {
+  use sea_query::{PostgresQueryBuilder, Query};
+  use ultimate::ctx::Ctx;
+
+  use super::*;
+
+  struct OrderBmc;
+  impl DbBmc for OrderBmc {
+    const TABLE: &'static str = "order";
+  }
+
+  #[test]
+  fn test_schema_per_tenant_table_ref() -> anyhow::Result<()> {
+    let tenancy = Tenancy {
+      mode: TenancyMode::SchemaPerTenant,
+      schema_template: "t_{tenant_id}".to_string(),
+      ..Default::default()
+    };
+
+    let ctx = Ctx::new_root().with_tenant_id(42);
+    let sql = Query::select()
+      .column(SIden("id"))
+      .from(tenancy.table_ref::<OrderBmc>(Some(&ctx))?)
+      .to_string(PostgresQueryBuilder);
+    assert_eq!(sql, r#"SELECT "id" FROM "t_42"."order""#);
+
+    assert_eq!(tenancy.tenant_of::<OrderBmc>(Some(&Ctx::new_root()))?, None);
+    assert!(matches!(tenancy.tenant_of::<OrderBmc>(Some(&Ctx::new_super_admin())), Err(Error::TenantRequired)));
+    Ok(())
+  }
+}
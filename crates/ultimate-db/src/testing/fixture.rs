@@ -0,0 +1,265 @@
+use std::fmt;
+use std::path::Path;
+
+use sea_query::{Alias, Expr, IntoIden, Query, SimpleExpr, TableRef};
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use crate::store::{backend, DbQueryBuilder};
+use crate::{Error, ModelManager, Result};
+
+/// 一行测试数据，键为列名
+pub type FixtureRow = serde_json::Map<String, Value>;
+
+/// TOML 的日期时间值转换为 JSON 时的私有键
+const TOML_DATETIME_KEY: &str = "$__toml_private_datetime";
+
+/// 测试数据。按添加（或在文件中出现）的顺序插入，存在外键关联时先列出被引用的表。
+///
+/// 文件格式为表名到记录数组的映射，表名可以是 `schema.table` 或 `table`：
+///
+/// ```toml
+/// [[iam.user]]
+/// id = 1
+/// email = "admin@example.com"
+///
+/// [[iam.user_role]]
+/// user_id = 1
+/// role_id = 1
+/// ```
+///
+/// ```json
+/// { "iam.user": [{ "id": 1, "email": "admin@example.com" }], "iam.user_role": [{ "user_id": 1, "role_id": 1 }] }
+/// ```
+///
+/// 值为 `null` 时插入 `NULL`，数组及对象序列化为 JSON 字符串，其余值作为字面量由数据库转换为列的类型。
+#[derive(Debug, Clone, Default)]
+pub struct Fixtures {
+  tables: Vec<(String, Vec<FixtureRow>)>,
+}
+
+impl Fixtures {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn from_json(text: &str) -> Result<Self> {
+    Ok(serde_json::from_str(text)?)
+  }
+
+  pub fn from_toml(text: &str) -> Result<Self> {
+    toml::from_str(text).map_err(|e| Error::InvalidArgument { message: format!("Invalid TOML fixtures: {}", e) })
+  }
+
+  /// 根据扩展名（`.json` 或 `.toml`）读取测试数据文件
+  pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)
+      .map_err(|e| Error::InvalidArgument { message: format!("Read fixtures '{}' failed: {}", path.display(), e) })?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+      Some("json") => Self::from_json(&text),
+      Some("toml") => Self::from_toml(&text),
+      _ => Err(Error::InvalidArgument {
+        message: format!("Unsupported fixtures file '{}', expect .json or .toml", path.display()),
+      }),
+    }
+  }
+
+  /// 添加一行记录，`row` 需要序列化为 JSON 对象
+  pub fn with_row(self, table: impl Into<String>, row: impl Serialize) -> Result<Self> {
+    self.with_rows(table, [row])
+  }
+
+  /// 添加多行记录，`rows` 的每个元素需要序列化为 JSON 对象
+  pub fn with_rows<T: Serialize>(
+    mut self,
+    table: impl Into<String>,
+    rows: impl IntoIterator<Item = T>,
+  ) -> Result<Self> {
+    let rows = rows
+      .into_iter()
+      .map(|row| match serde_json::to_value(row)? {
+        Value::Object(row) => Ok(row),
+        v => Err(Error::InvalidArgument { message: format!("Fixture row must be an object, but got: {}", v) }),
+      })
+      .collect::<Result<Vec<_>>>()?;
+    self.push(table.into(), rows);
+    Ok(self)
+  }
+
+  /// 追加 `other` 中的记录
+  pub fn merge(mut self, other: Fixtures) -> Self {
+    for (table, rows) in other.tables {
+      self.push(table, rows);
+    }
+    self
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.tables.iter().all(|(_, rows)| rows.is_empty())
+  }
+
+  pub fn tables(&self) -> &[(String, Vec<FixtureRow>)] {
+    &self.tables
+  }
+
+  /// 生成插入所有记录的 SQL，每行记录一条 `INSERT` 语句
+  pub fn to_sql(&self) -> Result<Vec<String>> {
+    let mut sqls = Vec::new();
+    for (table, rows) in &self.tables {
+      for row in rows {
+        let mut query = Query::insert();
+        query
+          .into_table(fixture_table_ref(table))
+          .columns(row.keys().map(|column| Alias::new(column).into_iden()))
+          .values(row.values().map(literal_expr))?;
+        sqls.push(query.to_string(DbQueryBuilder));
+      }
+    }
+    Ok(sqls)
+  }
+
+  /// 使用 `mm` 插入所有记录，`mm` 在事务中时在事务内插入
+  pub async fn insert(&self, mm: &ModelManager) -> Result<()> {
+    for sql in self.to_sql()? {
+      mm.dbx().execute_raw(&sql).await?;
+    }
+    Ok(())
+  }
+
+  fn push(&mut self, table: String, rows: Vec<FixtureRow>) {
+    match self.tables.last_mut() {
+      Some((last, last_rows)) if *last == table => last_rows.extend(rows),
+      _ => self.tables.push((table, rows)),
+    }
+  }
+}
+
+fn fixture_table_ref(table: &str) -> TableRef {
+  match table.split_once('.') {
+    Some((schema, table)) => backend::table_ref(Alias::new(schema).into_iden(), Alias::new(table).into_iden()),
+    None => TableRef::Table(Alias::new(table).into_iden()),
+  }
+}
+
+fn literal_expr(value: &Value) -> SimpleExpr {
+  match value {
+    Value::Null => Expr::cust("NULL"),
+    Value::Bool(b) => (*b).into(),
+    Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+      (Some(i), _) => i.into(),
+      (None, Some(u)) => u.into(),
+      _ => n.as_f64().unwrap_or_default().into(),
+    },
+    Value::String(s) => s.as_str().into(),
+    Value::Object(map) if map.len() == 1 => match map.get(TOML_DATETIME_KEY) {
+      Some(Value::String(datetime)) => datetime.as_str().into(),
+      _ => value.to_string().into(),
+    },
+    Value::Array(_) | Value::Object(_) => value.to_string().into(),
+  }
+}
+
+/// 表名对应记录数组；TOML 的 `[[iam.user]]` 解析为嵌套的表，其键与上层的键以 `.` 连接作为表名
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FixtureEntry {
+  Rows(Vec<FixtureRow>),
+  Tables(OrderedEntries),
+}
+
+/// 保持键在文档中的顺序
+struct OrderedEntries(Vec<(String, FixtureEntry)>);
+
+impl<'de> Deserialize<'de> for OrderedEntries {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+    struct EntriesVisitor;
+
+    impl<'de> Visitor<'de> for EntriesVisitor {
+      type Value = OrderedEntries;
+
+      fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of table name to rows")
+      }
+
+      fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<Self::Value, A::Error> {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or_default());
+        while let Some(entry) = map.next_entry()? {
+          entries.push(entry);
+        }
+        Ok(OrderedEntries(entries))
+      }
+    }
+
+    deserializer.deserialize_map(EntriesVisitor)
+  }
+}
+
+impl<'de> Deserialize<'de> for Fixtures {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+    fn flatten(fixtures: &mut Fixtures, prefix: Option<&str>, entries: OrderedEntries) {
+      for (key, entry) in entries.0 {
+        let table = match prefix {
+          Some(prefix) => format!("{}.{}", prefix, key),
+          None => key,
+        };
+        match entry {
+          FixtureEntry::Rows(rows) => fixtures.push(table, rows),
+          FixtureEntry::Tables(entries) => flatten(fixtures, Some(&table), entries),
+        }
+      }
+    }
+
+    let mut fixtures = Fixtures::default();
+    flatten(&mut fixtures, None, OrderedEntries::deserialize(deserializer)?);
+    Ok(fixtures)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_fixtures_keep_document_order() -> anyhow::Result<()> {
+    let toml = r#"
+      [[iam.user]]
+      id = 2
+      name = "b"
+
+      [[iam.user]]
+      id = 1
+      name = "a"
+
+      [[iam.role]]
+      id = 1
+
+      [[dict]]
+      key = "k"
+      ctime = 2024-01-01T00:00:00Z
+    "#;
+    let fixtures = Fixtures::from_toml(toml)?;
+    let tables: Vec<_> = fixtures.tables().iter().map(|(table, rows)| (table.as_str(), rows.len())).collect();
+    assert_eq!(tables, vec![("iam.user", 2), ("iam.role", 1), ("dict", 1)]);
+    assert_eq!(fixtures.tables()[0].1[0]["id"], 2);
+
+    let json = r#"{ "iam.user": [{ "id": 1 }], "dict": [{ "key": "k" }], "iam.role": [] }"#;
+    let fixtures = Fixtures::from_json(json)?.with_row("iam.role", serde_json::json!({ "id": 1 }))?;
+    let tables: Vec<_> = fixtures.tables().iter().map(|(table, _)| table.as_str()).collect();
+    assert_eq!(tables, vec!["iam.user", "dict", "iam.role"]);
+    assert!(Fixtures::new().with_row("dict", 1).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_fixtures_to_sql() -> anyhow::Result<()> {
+    let fixtures = Fixtures::from_toml("[[dict]]\nkey = \"it's\"\nctime = 2024-01-01T00:00:00Z\nextra = { a = 1 }")?;
+    let sql = fixtures.to_sql()?;
+    assert_eq!(sql.len(), 1);
+    assert!(sql[0].contains("'it''s'") || sql[0].contains(r"E'it\'s'"), "{}", sql[0]);
+    assert!(sql[0].contains("'2024-01-01T00:00:00Z'"), "{}", sql[0]);
+    assert!(sql[0].contains(r#"'{"a":1}'"#) || sql[0].contains(r#"E'{\"a\":1}'"#), "{}", sql[0]);
+    Ok(())
+  }
+}
@@ -0,0 +1,384 @@
+//! 测试辅助。
+//!
+//! [TestDb] 连接测试数据库，执行迁移、建表脚本并插入测试数据后，提供一个在事务中的 [ModelManager]，
+//! 测试结束时回滚事务，测试之间互不影响，可以并行执行。
+//!
+//! 数据库地址通过 [TestDbOptions::with_url] 或环境变量 `ULTIMATE_TEST_DATABASE_URL` 指定；
+//! 启用 `sqlite` 后端且都未设置时使用 `sqlite::memory:`，每个 [TestDb] 各自使用一个新的内存数据库。
+//!
+//! PostgreSQL 可通过 [TestDbOptions::with_isolated_schema] 为每个 [TestDb] 创建独立的 schema，迁移也在其中执行，
+//! [TestDb::rollback] 时删除。测试 panic 等未调用 [TestDb::rollback] 时，drop 时在后台线程删除该 schema，
+//! 测试进程在删除完成前退出时会残留名为 `ultimate_test_*` 的 schema，需要手动删除。
+//!
+//! 注意事项：
+//! - 迁移在事务外执行并且会被保留（已执行的迁移会被跳过），建表脚本及测试数据在事务内执行，测试结束时回滚；
+//! - 多个迁移器依次执行，各迁移器忽略其它迁移器已执行的迁移，版本号不能重复；
+//! - MySQL 的 DDL 会隐式提交事务，使用 MySQL 时建表应放在迁移中；
+//! - PostgreSQL 的序列不会因插入显式指定的 ID 而递增，测试数据中指定 ID 时应避免与序列生成的 ID 冲突。
+//!
+//! ```rust,no_run
+//! use ultimate::ctx::Ctx;
+//! use ultimate_db::testing::{Fixtures, TestDb, TestDbOptions};
+//!
+//! static MIGRATOR: ultimate_db::migration::Migrator = sqlx::migrate!("./migrations");
+//!
+//! # async fn example() -> ultimate_db::Result<()> {
+//! let fixtures = Fixtures::from_file("tests/fixtures/users.toml")?
+//!   .with_row("iam.user_role", serde_json::json!({ "user_id": 1, "role_id": 1 }))?;
+//! let db = TestDb::new(
+//!   TestDbOptions::default().with_migrator(&MIGRATOR).with_ctx(Ctx::new_root()).with_fixtures(fixtures),
+//! )
+//! .await?;
+//! let mm = db.mm();
+//! // 使用 mm 执行测试 ...
+//! db.rollback().await?;
+//! # Ok(())
+//! # }
+//! ```
+mod fixture;
+
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::warn;
+use ultimate::configuration::model::DbConf;
+use ultimate::ctx::Ctx;
+
+pub use fixture::{FixtureRow, Fixtures};
+
+use crate::migration::{MigrationRunner, Migrator};
+use crate::store::dbx::{self, new_db_pool_from_config, Db};
+use crate::store::{DbKind, DB_KIND};
+use crate::{Error, ModelManager, Result};
+
+/// 指定测试数据库地址的环境变量
+pub const TEST_DATABASE_URL_ENV: &str = "ULTIMATE_TEST_DATABASE_URL";
+
+/// 独立 schema 名称的序号
+static SCHEMA_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// [TestDb] 的选项
+#[derive(Default)]
+pub struct TestDbOptions {
+  url: Option<String>,
+  db_conf: Option<DbConf>,
+  ctx: Option<Ctx>,
+  migrators: Vec<&'static Migrator>,
+  scripts: Vec<String>,
+  fixtures: Fixtures,
+  isolated_schema: bool,
+}
+
+impl TestDbOptions {
+  /// 数据库地址，未设置时使用环境变量 `ULTIMATE_TEST_DATABASE_URL`
+  pub fn with_url(mut self, url: impl Into<String>) -> Self {
+    self.url = Some(url.into());
+    self
+  }
+
+  /// 使用完整的数据库配置，优先于 [TestDbOptions::with_url]
+  pub fn with_db_conf(mut self, db_conf: DbConf) -> Self {
+    self.db_conf = Some(db_conf);
+    self
+  }
+
+  /// [TestDb::mm] 使用的 Ctx
+  pub fn with_ctx(mut self, ctx: Ctx) -> Self {
+    self.ctx = Some(ctx);
+    self
+  }
+
+  /// 按添加的顺序执行迁移，各迁移器忽略其它迁移器已执行的迁移
+  pub fn with_migrator(mut self, migrator: &'static Migrator) -> Self {
+    self.migrators.push(migrator);
+    self
+  }
+
+  /// 在新建的独立 schema 中执行迁移、建表脚本及测试数据，[TestDb::rollback] 时删除该 schema。
+  ///
+  /// 连接的 `search_path` 为 `<独立 schema>, public`，只隔离未指定 schema 的表。只支持 PostgreSQL
+  pub fn with_isolated_schema(mut self) -> Self {
+    self.isolated_schema = true;
+    self
+  }
+
+  /// 在事务内执行的 SQL 脚本，可以包含多条语句，先于测试数据执行
+  pub fn with_script(mut self, sql: impl Into<String>) -> Self {
+    self.scripts.push(sql.into());
+    self
+  }
+
+  /// 在事务内插入的测试数据，多次调用时按顺序追加
+  pub fn with_fixtures(mut self, fixtures: Fixtures) -> Self {
+    self.fixtures = std::mem::take(&mut self.fixtures).merge(fixtures);
+    self
+  }
+
+  fn db_conf(&mut self) -> Result<DbConf> {
+    if let Some(db_conf) = self.db_conf.take() {
+      return Ok(db_conf);
+    }
+    let url = match self.url.take().or_else(|| env::var(TEST_DATABASE_URL_ENV).ok()) {
+      Some(url) => url,
+      None if cfg!(feature = "sqlite") => "sqlite::memory:".to_string(),
+      None => {
+        return Err(Error::InvalidArgument {
+          message: format!(
+            "Need set the test database url by TestDbOptions::with_url or env {}",
+            TEST_DATABASE_URL_ENV
+          ),
+        })
+      }
+    };
+    Ok(serde_json::from_value(serde_json::json!({ "enable": true, "url": url }))?)
+  }
+}
+
+/// 测试数据库，持有一个已开启的事务，[TestDb::rollback] 或 drop 时回滚
+pub struct TestDb {
+  mm: ModelManager,
+  /// [TestDbOptions::with_isolated_schema] 创建的 schema
+  schema: Option<String>,
+  /// 连接的配置，drop 时用于删除独立 schema
+  db_conf: DbConf,
+}
+
+impl TestDb {
+  pub async fn new(mut options: TestDbOptions) -> Result<Self> {
+    let db_conf = options.db_conf()?;
+    let schema = if options.isolated_schema { Some(create_isolated_schema(&db_conf).await?) } else { None };
+    let mm_conf = match &schema {
+      Some(schema) => db_conf.clone().with_schema_search_path(format!("\"{}\", public", schema)),
+      None => db_conf.clone(),
+    };
+
+    let mm = ModelManager::new(&mm_conf).await?;
+    for migrator in &options.migrators {
+      let migrator =
+        Migrator { migrations: migrator.migrations.clone(), ignore_missing: true, locking: migrator.locking };
+      MigrationRunner::new(mm.dbx().db().clone(), &migrator).apply().await?;
+    }
+
+    let mm = match options.ctx {
+      Some(ctx) => mm.try_with_ctx(ctx)?,
+      None => mm,
+    };
+    let mm = mm.clone_with_txn()?;
+    mm.dbx().begin_txn().await?;
+
+    let db = Self { mm, schema, db_conf };
+    db.begin_tenant_txn(&db.mm).await?;
+    for script in &options.scripts {
+      db.execute_script(script).await?;
+    }
+    db.seed(&options.fixtures).await?;
+    Ok(db)
+  }
+
+  /// 事务内的 ModelManager，其上的 [ModelManager::transaction] 使用保存点，不会提交外层事务
+  pub fn mm(&self) -> &ModelManager {
+    &self.mm
+  }
+
+  /// 使用另一个 Ctx 的 ModelManager，与 [TestDb::mm] 共享同一个事务。
+  ///
+  /// `database_per_tenant` 模式下 `ctx` 租户的数据库也在事务内，[TestDb::rollback] 时一并回滚
+  pub async fn mm_with_ctx(&self, ctx: Ctx) -> Result<ModelManager> {
+    let mm = self.mm.clone().try_with_ctx(ctx)?;
+    self.begin_tenant_txn(&mm).await?;
+    Ok(mm)
+  }
+
+  /// 在事务内插入测试数据
+  pub async fn seed(&self, fixtures: &Fixtures) -> Result<()> {
+    fixtures.insert(&self.mm).await
+  }
+
+  /// 在事务内执行 SQL 脚本，返回受影响的记录数
+  pub async fn execute_script(&self, sql: &str) -> Result<u64> {
+    Ok(self.mm.dbx().execute_raw(sql).await?)
+  }
+
+  /// 回滚事务，丢弃测试中的所有修改，并删除 [TestDbOptions::with_isolated_schema] 创建的 schema
+  pub async fn rollback(mut self) -> Result<()> {
+    for dbx in self.mm.tenant_txn_dbxs() {
+      if dbx.has_open_txn().await {
+        dbx.rollback_txn().await?;
+      }
+    }
+    self.mm.dbx().rollback_txn().await?;
+    if let Some(schema) = self.schema.take() {
+      drop_isolated_schema(self.mm.dbx().db(), &schema).await?;
+    }
+    Ok(())
+  }
+
+  async fn begin_tenant_txn(&self, mm: &ModelManager) -> Result<()> {
+    if let Some(dbx) = mm.tenant_dbx() {
+      if !dbx.has_open_txn().await {
+        dbx.begin_txn().await?;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl Drop for TestDb {
+  /// 未调用 [TestDb::rollback] 时事务随连接关闭而回滚，独立 schema 在后台线程中删除。
+  ///
+  /// 不能阻塞当前线程等待删除完成：当前运行时停止前事务的连接不会关闭，删除 schema 会一直等待事务持有的锁
+  fn drop(&mut self) {
+    let Some(schema) = self.schema.take() else {
+      return;
+    };
+    let db_conf = self.db_conf.clone();
+    std::thread::spawn(move || {
+      let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(e) => return warn!("Drop the isolated schema '{}' failed: {}", schema, e),
+      };
+      let result: Result<()> = runtime.block_on(async {
+        let db = new_db_pool_from_config(&db_conf).await?;
+        let result = drop_isolated_schema(&db, &schema).await;
+        db.close().await;
+        result
+      });
+      if let Err(e) = result {
+        warn!("Drop the isolated schema '{}' failed: {}", schema, e);
+      }
+    });
+  }
+}
+
+/// 删除 [create_isolated_schema] 创建的 schema
+async fn drop_isolated_schema(db: &Db, schema: &str) -> Result<()> {
+  let sql = format!("DROP SCHEMA IF EXISTS \"{}\" CASCADE", schema);
+  sqlx::query(&sql).execute(db).await.map_err(dbx::Error::from)?;
+  Ok(())
+}
+
+/// 创建名称唯一的 schema
+async fn create_isolated_schema(db_conf: &DbConf) -> Result<String> {
+  if DB_KIND != DbKind::Postgres {
+    return Err(Error::InvalidArgument { message: "Isolated schema only supports PostgreSQL".to_string() });
+  }
+  let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos();
+  let schema = format!("ultimate_test_{}_{}_{}", std::process::id(), nanos, SCHEMA_SEQ.fetch_add(1, Ordering::Relaxed));
+
+  let db = new_db_pool_from_config(db_conf).await?;
+  let result = sqlx::query(&format!("CREATE SCHEMA \"{}\"", schema)).execute(&db).await;
+  db.close().await;
+  result.map_err(dbx::Error::from)?;
+  Ok(schema)
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+  use std::sync::LazyLock;
+
+  use super::*;
+  use crate::base::{self, DbBmc};
+  use crate::migration::{Migration, MigrationType};
+
+  struct DictBmc;
+  impl DbBmc for DictBmc {
+    const TABLE: &'static str = "dict";
+  }
+
+  #[derive(modql::field::Fields)]
+  struct DictForCreate {
+    name: String,
+  }
+
+  const CREATE_TABLE: &str = "CREATE TABLE dict (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE, value TEXT);";
+
+  #[tokio::test]
+  async fn test_test_db() -> anyhow::Result<()> {
+    let fixtures = Fixtures::from_toml("[[dict]]\nid = 1\nname = \"a\"\n\n[[dict]]\nid = 2\nname = \"b\"")?;
+    let options = TestDbOptions::default().with_ctx(Ctx::new_root()).with_script(CREATE_TABLE).with_fixtures(fixtures);
+    let db = TestDb::new(options).await?;
+    let mm = db.mm();
+    assert!(mm.ctx_ref().is_ok());
+    assert_eq!(base::count_on::<DictBmc, _>(mm, |_| Ok(())).await?, 2);
+
+    // 测试内的事务使用保存点，失败时只回滚内层的修改
+    let result: Result<()> = mm
+      .transaction(|mm| async move {
+        base::delete_by_id::<DictBmc>(&mm, 1.into()).await?;
+        Err(Error::InvalidArgument { message: "rollback".to_string() })
+      })
+      .await;
+    assert!(result.is_err());
+    db.seed(&Fixtures::new().with_row("dict", serde_json::json!({ "id": 3, "name": "c" }))?).await?;
+    assert_eq!(base::count_on::<DictBmc, _>(mm, |_| Ok(())).await?, 3);
+    db.rollback().await?;
+
+    // 回滚后事务内建的表及插入的数据都被丢弃
+    let db = TestDb::new(TestDbOptions::default().with_script(CREATE_TABLE)).await?;
+    assert_eq!(db.execute_script("INSERT INTO dict (id, name) VALUES (1, 'a'), (2, 'b')").await?, 2);
+    Ok(())
+  }
+
+  fn migrator(version: i64, sql: &'static str) -> Migrator {
+    let migration = Migration::new(version, "test".into(), MigrationType::Simple, sql.into());
+    Migrator { migrations: vec![migration].into(), ignore_missing: false, locking: true }
+  }
+
+  static DICT_MIGRATOR: LazyLock<Migrator> = LazyLock::new(|| migrator(1, CREATE_TABLE));
+  static TAG_MIGRATOR: LazyLock<Migrator> =
+    LazyLock::new(|| migrator(2, "CREATE TABLE tag (id INTEGER PRIMARY KEY, name TEXT NOT NULL);"));
+
+  #[tokio::test]
+  async fn test_test_db_with_migrators() -> anyhow::Result<()> {
+    // 第二个迁移器不包含第一个迁移器已执行的迁移，依次执行时不报错
+    let options = TestDbOptions::default().with_migrator(&DICT_MIGRATOR).with_migrator(&TAG_MIGRATOR);
+    let db = TestDb::new(options).await?;
+    assert_eq!(
+      db.execute_script("INSERT INTO dict (id, name) VALUES (1, 'a'); INSERT INTO tag (id, name) VALUES (1, 'a')")
+        .await?,
+      2
+    );
+    db.rollback().await?;
+
+    let err = TestDb::new(TestDbOptions::default().with_isolated_schema()).await.err().unwrap();
+    assert!(matches!(err, Error::InvalidArgument { .. }), "{}", err);
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_test_db_tenant_in_txn() -> anyhow::Result<()> {
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_nanos();
+    let dir = env::temp_dir().join(format!("ultimate-db-test-{}-{}", std::process::id(), nanos));
+    std::fs::create_dir_all(&dir)?;
+    let database_template = dir.join("tenant_{tenant_id}.db").display().to_string();
+    let tenant_db = dir.join("tenant_1.db").display().to_string();
+    let tenant_url = format!("sqlite://{}?mode=rwc", tenant_db);
+    let tenant_pool = sqlx::SqlitePool::connect(&tenant_url).await?;
+    let create_table = "CREATE TABLE dict (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE, value TEXT, \
+      cid INTEGER, ctime TEXT, mid INTEGER, mtime TEXT)";
+    sqlx::raw_sql(create_table).execute(&tenant_pool).await?;
+    tenant_pool.close().await;
+
+    let db_conf: DbConf = serde_json::from_value(serde_json::json!({
+      "enable": true,
+      "url": format!("sqlite://{}?mode=rwc", dir.join("main.db").display()),
+      "tenancy": { "mode": "database_per_tenant", "database_template": database_template },
+    }))?;
+    let db = TestDb::new(TestDbOptions::default().with_db_conf(db_conf).with_ctx(Ctx::new_root())).await?;
+    let mm = db.mm_with_ctx(Ctx::new_root().with_tenant_id(1)).await?;
+    base::create::<DictBmc, _>(&mm, DictForCreate { name: "a".to_string() }).await?;
+    assert_eq!(base::count_on::<DictBmc, _>(&mm, |_| Ok(())).await?, 1);
+
+    // 同一事务内再次切换到该租户时共享租户数据库的事务
+    let mm = db.mm_with_ctx(Ctx::new_root().with_tenant_id(1)).await?;
+    assert_eq!(base::count_on::<DictBmc, _>(&mm, |_| Ok(())).await?, 1);
+    db.rollback().await?;
+
+    let tenant_pool = sqlx::SqlitePool::connect(&tenant_url).await?;
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM dict").fetch_one(&tenant_pool).await?;
+    assert_eq!(count, 0);
+    tenant_pool.close().await;
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+  }
+}
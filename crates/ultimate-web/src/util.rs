@@ -41,7 +41,14 @@ pub fn unauthorized_app_error(msg: impl Into<String>) -> (StatusCode, Json<AppEr
   (StatusCode::UNAUTHORIZED, Json(AppError::new(msg).with_err_code(401)))
 }
 
-/// 从 Http Request Parts 中获取 [SessionCtx]
+/// 请求 ID 的 Http Header
+pub const X_REQUEST_ID: &str = "x-request-id";
+
+/// 请求 ID 的最大长度
+pub const REQUEST_ID_MAX_LEN: usize = 255;
+
+/// 从 Http Request Parts 中获取 [SessionCtx]，请求头中有 `x-request-id` 时设置为请求 ID。
+/// 请求 ID 为空、超过 [REQUEST_ID_MAX_LEN] 或包含非可打印 ASCII 字符时忽略
 pub fn extract_session(parts: &Parts, sc: &SecurityConf) -> Result<Ctx, DataError> {
   let req_time = time::now();
 
@@ -56,7 +63,17 @@ pub fn extract_session(parts: &Parts, sc: &SecurityConf) -> Result<Ctx, DataErro
   let (payload, _) =
     SecurityUtils::decrypt_jwt(sc.pwd(), &token).map_err(|_e| DataError::unauthorized("Failed decode jwt"))?;
 
-  Ctx::try_from_jwt_payload(&payload, Some(req_time))
+  let ctx = Ctx::try_from_jwt_payload(&payload, Some(req_time))?;
+  match parts.headers.get(X_REQUEST_ID).and_then(|v| v.to_str().ok()).filter(|v| is_valid_request_id(v)) {
+    Some(request_id) => Ok(ctx.with_request_id(request_id)),
+    None => Ok(ctx),
+  }
+}
+
+fn is_valid_request_id(request_id: &str) -> bool {
+  !request_id.is_empty()
+    && request_id.len() <= REQUEST_ID_MAX_LEN
+    && request_id.bytes().all(|b| b.is_ascii_graphic() || b == b' ')
 }
 
 pub fn opt_to_app_result<T>(opt: Option<T>) -> AppResult<T>
@@ -6,6 +6,12 @@ use crate::RunMode;
 pub struct AppConf {
   run_mode: RunMode,
   name: String,
+
+  /// 本地时区（IANA 时区名），未设置时为 `Asia/Shanghai`
+  time_zone: Option<String>,
+
+  /// Snowflake ID 生成器的工作节点 ID（0 ~ 1023），各节点必须唯一
+  worker_id: Option<u16>,
 }
 
 impl AppConf {
@@ -16,4 +22,12 @@ impl AppConf {
   pub fn name(&self) -> &str {
     &self.name
   }
+
+  pub fn time_zone(&self) -> Option<&str> {
+    self.time_zone.as_deref()
+  }
+
+  pub fn worker_id(&self) -> Option<u16> {
+    self.worker_id
+  }
 }
@@ -48,6 +48,22 @@ pub struct DbConf {
 
   /// Schema search path (PostgreSQL only)
   schema_search_path: Option<String>,
+
+  /// 只读副本的 URI。配置后事务外的只读查询发送到副本，写操作和事务发送到主库
+  replicas: Option<Vec<UriString>>,
+
+  /// 只读副本健康检查的间隔，默认为 10 秒
+  replica_check_interval: Option<Duration>,
+
+  /// 多租户配置
+  tenancy: Option<TenancyConf>,
+
+  /// 是否在启动时执行数据库迁移，见 `DbState::from_config_with_migrator`。默认为 false
+  migrate_on_startup: Option<bool>,
+
+  /// 游标分页的加密密钥。未设置时使用进程内随机生成的密钥，游标在重启后或其它实例上失效
+  #[serde(skip_serializing)]
+  cursor_secret: Option<String>,
 }
 
 impl DbConf {
@@ -117,4 +133,76 @@ impl DbConf {
   pub fn schema_search_path(&self) -> Option<&str> {
     self.schema_search_path.as_deref()
   }
+
+  /// 设置连接的 schema 搜索路径，多个 schema 以逗号分隔
+  pub fn with_schema_search_path(mut self, schema_search_path: impl Into<String>) -> Self {
+    self.schema_search_path = Some(schema_search_path.into());
+    self
+  }
+
+  pub fn replicas(&self) -> &[UriString] {
+    self.replicas.as_deref().unwrap_or_default()
+  }
+
+  pub fn replica_check_interval(&self) -> Option<&Duration> {
+    self.replica_check_interval.as_ref()
+  }
+
+  pub fn tenancy(&self) -> Option<&TenancyConf> {
+    self.tenancy.as_ref()
+  }
+
+  pub fn migrate_on_startup(&self) -> bool {
+    self.migrate_on_startup.unwrap_or(false)
+  }
+
+  pub fn cursor_secret(&self) -> Option<&str> {
+    self.cursor_secret.as_deref()
+  }
 }
+
+/// 多租户隔离模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TenancyMode {
+  /// 不隔离
+  #[default]
+  None,
+
+  /// 共享表，通过 `tenant_id` 列隔离
+  SharedTable,
+
+  /// 每个租户使用独立的 schema
+  SchemaPerTenant,
+
+  /// 每个租户使用独立的数据库
+  DatabasePerTenant,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TenancyConf {
+  #[serde(default)]
+  mode: TenancyMode,
+
+  /// 租户 schema 名称模板，`{tenant_id}` 将被替换为租户 ID。默认为：`tenant_{tenant_id}`
+  schema_template: Option<String>,
+
+  /// 租户数据库名称模板，`{tenant_id}` 将被替换为租户 ID。默认为：`tenant_{tenant_id}`
+  database_template: Option<String>,
+}
+
+impl TenancyConf {
+  pub fn mode(&self) -> TenancyMode {
+    self.mode
+  }
+
+  pub fn schema_template(&self) -> &str {
+    self.schema_template.as_deref().unwrap_or(DEFAULT_TENANT_TEMPLATE)
+  }
+
+  pub fn database_template(&self) -> &str {
+    self.database_template.as_deref().unwrap_or(DEFAULT_TENANT_TEMPLATE)
+  }
+}
+
+const DEFAULT_TENANT_TEMPLATE: &str = "tenant_{tenant_id}";
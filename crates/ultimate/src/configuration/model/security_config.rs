@@ -1,5 +1,9 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 use ultimate_common::{
+  crypto::Keyring,
+  digest,
   string::{deser_str_to_vecu8, ser_vecu8_to_str},
   time::{self, Duration, UtcDateTime},
 };
@@ -8,6 +12,7 @@ use ultimate_common::{
 pub struct SecurityConf {
   pwd: PwdConf,
   token: TokenConf,
+  encryption: Option<EncryptionConf>,
 }
 
 impl SecurityConf {
@@ -18,6 +23,10 @@ impl SecurityConf {
   pub fn token(&self) -> &TokenConf {
     &self.token
   }
+
+  pub fn encryption(&self) -> Option<&EncryptionConf> {
+    self.encryption.as_ref()
+  }
 }
 
 pub trait KeyConf {
@@ -101,3 +110,75 @@ impl KeyConf for TokenConf {
     time::now_utc() + Duration::seconds(self.expires_in())
   }
 }
+
+/// 字段级加密配置
+#[derive(Clone, Deserialize, Serialize)]
+pub struct EncryptionConf {
+  /// 当前用于加密的密钥 ID
+  key_id: String,
+
+  /// 密钥 ID 与 base64 编码的 32 字节密钥的映射（可使用 `openssl rand -base64 32` 生成）。
+  /// 轮换密钥时保留旧密钥以解密已有数据
+  #[serde(skip_serializing)]
+  keys: HashMap<String, String>,
+
+  /// base64 编码的盲索引（HMAC）密钥
+  #[serde(skip_serializing)]
+  blind_index_key: Option<String>,
+}
+
+impl EncryptionConf {
+  pub fn key_id(&self) -> &str {
+    &self.key_id
+  }
+
+  pub fn to_keyring(&self) -> ultimate_common::Result<Keyring> {
+    let key = self.keys.get(&self.key_id).ok_or_else(|| ultimate_common::Error::KeyNotFound(self.key_id.clone()))?;
+    let mut keyring = Keyring::new(&self.key_id, &decode_key(&self.key_id, key)?)
+      .map_err(|_| ultimate_common::Error::InvalidEncryptionKey(self.key_id.clone()))?;
+    for (key_id, key) in self.keys.iter().filter(|(key_id, _)| **key_id != self.key_id) {
+      keyring = keyring
+        .with_key(key_id, &decode_key(key_id, key)?)
+        .map_err(|_| ultimate_common::Error::InvalidEncryptionKey(key_id.clone()))?;
+    }
+    if let Some(key) = self.blind_index_key.as_deref() {
+      keyring = keyring.with_blind_index_key(decode_key("blind_index_key", key)?);
+    }
+    Ok(keyring)
+  }
+}
+
+/// 解码 base64 编码的密钥，错误信息中只包含密钥名称
+fn decode_key(name: &str, key: &str) -> ultimate_common::Result<Vec<u8>> {
+  digest::b64_decode(key.trim()).map_err(|_| ultimate_common::Error::InvalidEncryptionKey(name.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_encryption_conf_to_keyring() -> anyhow::Result<()> {
+    let key_1 = "MDEyMzQ1Njc4OUFCQ0RFRjAxMjM0NTY3ODlBQkNERUY="; // 0123456789ABCDEF0123456789ABCDEF
+    let key_2 = "QUJDREVGMDEyMzQ1Njc4OUFCQ0RFRjAxMjM0NTY3ODk="; // ABCDEF0123456789ABCDEF0123456789
+    let old: EncryptionConf = serde_json::from_value(serde_json::json!({
+      "key_id": "1", "keys": { "1": key_1 }, "blind_index_key": "aG1hYy1rZXk="
+    }))?;
+    let conf: EncryptionConf = serde_json::from_value(serde_json::json!({
+      "key_id": "2", "keys": { "1": key_1, "2": key_2 }, "blind_index_key": "aG1hYy1rZXk="
+    }))?;
+    let ciphertext = old.to_keyring()?.encrypt(b"hello")?;
+    let keyring = conf.to_keyring()?;
+    assert_eq!(keyring.current_key_id(), "2");
+    assert_eq!(keyring.decrypt(&ciphertext)?, b"hello");
+    assert_eq!(keyring.blind_index(b"hello")?, old.to_keyring()?.blind_index(b"hello")?);
+
+    // 未编码的 32 字节字符串不再被接受
+    let raw: EncryptionConf = serde_json::from_value(
+      serde_json::json!({ "key_id": "1", "keys": { "1": "0123456789ABCDEF0123456789ABCDEF" } }),
+    )?;
+    let err = raw.to_keyring().err().unwrap();
+    assert!(matches!(err, ultimate_common::Error::InvalidEncryptionKey(ref key_id) if key_id == "1"), "{}", err);
+    Ok(())
+  }
+}
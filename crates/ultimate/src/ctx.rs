@@ -5,7 +5,13 @@ use ultimate_common::time::{self, DateTime, Duration, Utc, UtcDateTime};
 
 use crate::DataError;
 
-#[derive(Debug, Default)]
+/// 根用户 ID，见 [Ctx::new_root]
+pub const ROOT_UID: i64 = 0;
+
+/// 超级管理员用户 ID，见 [Ctx::new_super_admin]
+pub const SUPER_ADMIN_UID: i64 = 1;
+
+#[derive(Debug, Clone, Default)]
 pub struct InnerCtx {
   /// 会话用户 ID
   uid: i64,
@@ -24,6 +30,12 @@ pub struct InnerCtx {
 
   /// 权限ID列表。用于需要通过不同权限ID进行细粒度权限控制
   ext_privileges: Vec<i64>,
+
+  /// 租户ID。用于多租户数据隔离
+  tenant_id: Option<i64>,
+
+  /// 请求ID。用于关联日志、审计记录等
+  request_id: Option<String>,
 }
 
 /// 会话上下文。
@@ -39,13 +51,23 @@ impl Ctx {
   pub fn new_root() -> Self {
     let req_time = time::now_utc();
     let expires_at = req_time + Duration::minutes(30);
-    Self::new(0, req_time, expires_at)
+    Self::new(ROOT_UID, req_time, expires_at)
   }
 
   pub fn new_super_admin() -> Self {
     let req_time = time::now_utc();
     let expires_at = req_time + Duration::minutes(30);
-    Self::new(1, req_time, expires_at)
+    Self::new(SUPER_ADMIN_UID, req_time, expires_at)
+  }
+
+  /// 是否为根用户
+  pub fn is_root(&self) -> bool {
+    self.uid == ROOT_UID
+  }
+
+  /// 是否为超级管理员
+  pub fn is_super_admin(&self) -> bool {
+    self.uid == SUPER_ADMIN_UID
   }
 
   pub fn uid(&self) -> i64 {
@@ -69,28 +91,46 @@ impl Ctx {
     &self.ext_orgs
   }
 
-  // pub fn with_ext_orgs(mut self, ext_orgs: Vec<i64>) -> Self {
-  //   self.ext_orgs = ext_orgs;
-  //   self
-  // }
+  pub fn with_ext_orgs(mut self, ext_orgs: Vec<i64>) -> Self {
+    Arc::make_mut(&mut self.0).ext_orgs = ext_orgs;
+    self
+  }
 
   pub fn ext_roles(&self) -> &[i64] {
     &self.ext_roles
   }
 
-  // pub fn with_ext_roles(mut self, ext_roles: Vec<i64>) -> Self {
-  //   self.0.ext_roles = ext_roles;
-  //   self
-  // }
+  pub fn with_ext_roles(mut self, ext_roles: Vec<i64>) -> Self {
+    Arc::make_mut(&mut self.0).ext_roles = ext_roles;
+    self
+  }
 
   pub fn ext_privileges(&self) -> &[i64] {
     &self.ext_privileges
   }
 
-  // pub fn with_ext_privileges(mut self, ext_privileges: Vec<i64>) -> Self {
-  //   self.ext_privileges = ext_privileges;
-  //   self
-  // }
+  pub fn with_ext_privileges(mut self, ext_privileges: Vec<i64>) -> Self {
+    Arc::make_mut(&mut self.0).ext_privileges = ext_privileges;
+    self
+  }
+
+  pub fn tenant_id(&self) -> Option<i64> {
+    self.tenant_id
+  }
+
+  pub fn with_tenant_id(mut self, tenant_id: i64) -> Self {
+    Arc::make_mut(&mut self.0).tenant_id = Some(tenant_id);
+    self
+  }
+
+  pub fn request_id(&self) -> Option<&str> {
+    self.request_id.as_deref()
+  }
+
+  pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+    Arc::make_mut(&mut self.0).request_id = Some(request_id.into());
+    self
+  }
 
   pub fn try_from_jwt_payload(payload: &JwtPayload, req_time: Option<UtcDateTime>) -> Result<Self, DataError> {
     let req_time = req_time.unwrap_or_else(time::now_utc);
@@ -109,7 +149,11 @@ impl Ctx {
       UtcDateTime::MAX_UTC
     };
 
-    Ok(Ctx::new(uid, req_time, expires_at))
+    let ctx = Ctx::new(uid, req_time, expires_at);
+    match payload.claim("tenant_id").and_then(|v| v.as_i64()) {
+      Some(tenant_id) => Ok(ctx.with_tenant_id(tenant_id)),
+      None => Ok(ctx),
+    }
   }
 }
 
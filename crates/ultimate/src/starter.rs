@@ -1,9 +1,39 @@
+use tracing::warn;
+use ultimate_common::{
+  crypto,
+  id::{self, Snowflake},
+  time,
+};
+
 use crate::{configuration::ConfigState, trace};
 
 pub fn load_and_init() -> ConfigState {
   let config_state = config_load();
   let ultimate_config = config_state.configuration();
   trace::init_trace(ultimate_config);
+  if let Some(tz) = ultimate_config.app().time_zone() {
+    // 时区配置错误应提前终止程序
+    let tz = time::parse_zone(tz).unwrap();
+    if let Err(e) = time::init_local_zone(tz) {
+      warn!("Skip init local time zone: {}", e);
+    }
+  }
+  if let Some(worker_id) = ultimate_config.app().worker_id() {
+    // 工作节点 ID 配置错误应提前终止程序
+    let snowflake = Snowflake::new(worker_id).unwrap();
+    if let Err(e) = id::init_snowflake(snowflake) {
+      warn!("Skip init snowflake: {}", e);
+    }
+  }
+  if let Some(c) = ultimate_config.security().encryption() {
+    // 加密密钥配置错误应提前终止程序
+    let keyring = c
+      .to_keyring()
+      .unwrap_or_else(|e| panic!("Invalid config ultimate.security.encryption, key id is {}: {}", c.key_id(), e));
+    if let Err(e) = crypto::init_keyring(keyring) {
+      warn!("Skip init keyring: {}", e);
+    }
+  }
   config_state
 }
 
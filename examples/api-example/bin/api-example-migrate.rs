@@ -0,0 +1,20 @@
+use api_example::app::MIGRATOR;
+use clap::Parser;
+use ultimate::starter;
+use ultimate_db::{migration::MigrateCommand, DbState};
+
+/// 管理 api-example 的数据库迁移
+#[derive(Parser)]
+struct Cli {
+  #[command(subcommand)]
+  command: MigrateCommand,
+}
+
+#[tokio::main]
+async fn main() -> ultimate::Result<()> {
+  let cli = Cli::parse();
+  let config = starter::load_and_init();
+  let db = DbState::from_config(config.configuration().db()).await?;
+  cli.command.run(&db.migration_runner(&MIGRATOR)).await?;
+  Ok(())
+}
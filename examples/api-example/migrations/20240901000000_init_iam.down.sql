@@ -0,0 +1,6 @@
+drop table if exists iam.role_permission;
+drop table if exists iam.user_role;
+drop table if exists iam.permission;
+drop table if exists iam.role;
+drop table if exists iam.user_credential;
+drop table if exists iam."user";
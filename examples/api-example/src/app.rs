@@ -7,10 +7,13 @@ use ultimate::{
   ctx::Ctx,
   starter,
 };
-use ultimate_db::{DbState, ModelManager};
+use ultimate_db::{migration::Migrator, DbState, ModelManager};
 
 use crate::ctx::{CtxW, RequestMetadata};
 
+/// 数据库迁移，迁移文件位于 `migrations` 目录
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
 #[derive(Clone, TypedBuilder, Getters)]
 pub struct AppState {
   pub config_state: ConfigState,
@@ -37,7 +40,7 @@ impl AppState {
 
 pub async fn new_app_state() -> ultimate::Result<AppState> {
   let config = starter::load_and_init();
-  let db = DbState::from_config(config.configuration().db()).await?;
+  let db = DbState::from_config_with_migrator(config.configuration().db(), &MIGRATOR).await?;
   let app = AppState::builder().config_state(config).db_state(db).build();
   Ok(app)
 }
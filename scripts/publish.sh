@@ -1,5 +1,7 @@
 #!/bin/sh
 
+cargo publish --registry crates-io --allow-dirty -p ultimate-common-macros
+
 cargo publish --registry crates-io --allow-dirty -p ultimate-common
 
 cargo publish --registry crates-io --allow-dirty -p ultimate